@@ -1,7 +1,8 @@
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
 use std::fs;
 use serde::Deserialize;
+use zenith_storage::{ExportFormat, ReplayMode, ReplayRange, ReplayRate, Replayer, StorageEngine};
 
 #[derive(Parser)]
 #[command(name = "zenith")]
@@ -19,10 +20,126 @@ enum Commands {
         #[arg(short, long, default_value = "config/zenith.toml")]
         config: PathBuf,
     },
+    /// Replay stored events into an engine
+    Replay {
+        /// Storage directory
+        #[arg(short, long)]
+        storage: PathBuf,
+        /// Source ID to replay
+        #[arg(long)]
+        source: u32,
+        /// First seq_no to replay (inclusive)
+        #[arg(long, conflicts_with_all = ["from_time", "to_time"])]
+        from_seq: Option<u64>,
+        /// Last seq_no to replay (inclusive)
+        #[arg(long, conflicts_with_all = ["from_time", "to_time"])]
+        to_seq: Option<u64>,
+        /// First timestamp in ns to replay (inclusive)
+        #[arg(long)]
+        from_time: Option<u64>,
+        /// Last timestamp in ns to replay (inclusive)
+        #[arg(long)]
+        to_time: Option<u64>,
+        /// Pacing: "original", "max", or a multiplier such as "10x"
+        #[arg(short, long, default_value = "max")]
+        rate: ReplayRate,
+        /// Read columnar segments instead of row events (default for the
+        /// engine target, which only accepts Arrow data: with
+        /// `--columnar=false`, row payloads must be Arrow IPC streams)
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        columnar: Option<bool>,
+        /// Engine to publish into
+        #[arg(short, long, value_enum, default_value_t = ReplayTarget::Engine)]
+        target: ReplayTarget,
+        /// Configuration file (buffer size and plugins for the engine target)
+        #[arg(short, long, default_value = "config/zenith.toml")]
+        config: PathBuf,
+    },
+    /// Inspect and move stored data
+    Storage {
+        /// Storage directory
+        #[arg(short, long, global = true, default_value = "./data")]
+        path: PathBuf,
+        #[command(subcommand)]
+        command: StorageCommands,
+    },
     /// Show version
     Version,
 }
 
+#[derive(Subcommand)]
+enum StorageCommands {
+    /// Per-source counts, sizes and seq/timestamp ranges
+    Stats {
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Export a source to JSON Lines or Arrow IPC
+    Export {
+        /// Source ID to export
+        #[arg(long)]
+        source: u32,
+        /// First seq_no (inclusive)
+        #[arg(long, conflicts_with_all = ["from_time", "to_time"])]
+        from_seq: Option<u64>,
+        /// Last seq_no (inclusive)
+        #[arg(long, conflicts_with_all = ["from_time", "to_time"])]
+        to_seq: Option<u64>,
+        /// First timestamp in ns (inclusive)
+        #[arg(long)]
+        from_time: Option<u64>,
+        /// Last timestamp in ns (inclusive)
+        #[arg(long)]
+        to_time: Option<u64>,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = FileFormat::Jsonl)]
+        format: FileFormat,
+        /// Export columnar segments (Arrow IPC only)
+        #[arg(long)]
+        columnar: bool,
+        /// Output file (stdout if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Import a file produced by `export`
+    Import {
+        /// Input format
+        #[arg(short, long, value_enum, default_value_t = FileFormat::Jsonl)]
+        format: FileFormat,
+        /// Input file (stdin if omitted)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+    },
+    /// Check every record and segment file for corruption
+    Verify,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FileFormat {
+    /// JSON Lines, one event per line
+    Jsonl,
+    /// Arrow IPC stream
+    Arrow,
+}
+
+impl From<FileFormat> for ExportFormat {
+    fn from(format: FileFormat) -> Self {
+        match format {
+            FileFormat::Jsonl => ExportFormat::JsonLines,
+            FileFormat::Arrow => ExportFormat::ArrowIpc,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ReplayTarget {
+    /// Zenith core engine with WASM plugins
+    Engine,
+    /// Data plane engine
+    Dataplane,
+}
+
 #[derive(Deserialize)]
 struct Config {
     server: ServerConfig,
@@ -40,6 +157,126 @@ struct EngineConfig {
     plugins: Vec<String>,
 }
 
+fn load_config(path: &Path) -> anyhow::Result<Config> {
+    let config_content = fs::read_to_string(path)
+        .unwrap_or_else(|_| "
+[server]
+port = 8080
+
+[engine]
+buffer_size = 1024
+plugins = []
+".to_string());
+    
+    Ok(toml::from_str(&config_content)?)
+}
+
+fn build_engine(cfg: &Config) -> anyhow::Result<zenith_core::Engine> {
+    let engine = zenith_core::Engine::new(cfg.engine.buffer_size)?;
+    
+    // Load Plugins
+    for plugin_path in &cfg.engine.plugins {
+         println!("Loading plugin: {}", plugin_path);
+         let wasm_bytes = fs::read(plugin_path)?;
+         engine.load_plugin(&wasm_bytes)?;
+    }
+    
+    Ok(engine)
+}
+
+fn replay_range(from_seq: Option<u64>, to_seq: Option<u64>, from_time: Option<u64>, to_time: Option<u64>) -> ReplayRange {
+    match (from_seq, to_seq, from_time, to_time) {
+        (None, None, None, None) => ReplayRange::All,
+        (_, _, None, None) => ReplayRange::Seq(from_seq.unwrap_or(0)..=to_seq.unwrap_or(u64::MAX)),
+        _ => ReplayRange::Time(from_time.unwrap_or(0)..=to_time.unwrap_or(u64::MAX)),
+    }
+}
+
+/// Open a store that must already exist, rather than creating an empty one
+fn open_existing(path: &Path) -> anyhow::Result<StorageEngine> {
+    if !path.join("db").is_file() {
+        anyhow::bail!("no storage found at {}", path.display());
+    }
+    StorageEngine::open(path)
+}
+
+fn run_storage(path: &Path, command: StorageCommands) -> anyhow::Result<()> {
+    // Only import may create a new store
+    let store = match command {
+        StorageCommands::Import { .. } => StorageEngine::open(path)?,
+        _ => open_existing(path)?,
+    };
+    
+    match command {
+        StorageCommands::Stats { json } => {
+            let stats = store.stats()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+                return Ok(());
+            }
+            
+            println!("{:>10} {:>10} {:>12} {:>8} {:>10} {:>12} {:>22} {:>22}",
+                "SOURCE", "EVENTS", "BYTES", "SEGS", "SEG_ROWS", "SEG_BYTES", "SEQ", "TIMESTAMP_NS");
+            for s in &stats.sources {
+                println!("{:>10} {:>10} {:>12} {:>8} {:>10} {:>12} {:>22} {:>22}",
+                    s.source_id, s.events, s.stored_bytes, s.segments, s.segment_rows, s.segment_bytes,
+                    format_range(s.min_seq, s.max_seq),
+                    format_range(s.min_timestamp_ns, s.max_timestamp_ns));
+            }
+            println!();
+            println!("Events: {}  Segments: {}  Compression: {:.2}x  Corrupt: {}  On disk: {} bytes",
+                stats.total_events, stats.total_segments, stats.compression.ratio(),
+                stats.corrupt_records, stats.disk_size_bytes);
+        }
+        StorageCommands::Export { source, from_seq, to_seq, from_time, to_time, format, columnar, output } => {
+            let range = replay_range(from_seq, to_seq, from_time, to_time);
+            let writer: Box<dyn std::io::Write> = match &output {
+                Some(path) => Box::new(fs::File::create(path)?),
+                None => Box::new(std::io::stdout().lock()),
+            };
+            
+            let count = if columnar {
+                if !matches!(format, FileFormat::Arrow) {
+                    anyhow::bail!("columnar export requires --format arrow");
+                }
+                store.export_batches(source, &range, writer)?
+            } else {
+                store.export(source, &range, format.into(), writer)?
+            };
+            eprintln!("Exported {} {} from source {}", count, if columnar { "batches" } else { "events" }, source);
+        }
+        StorageCommands::Import { format, input } => {
+            let reader: Box<dyn std::io::Read> = match &input {
+                Some(path) => Box::new(fs::File::open(path)?),
+                None => Box::new(std::io::stdin().lock()),
+            };
+            
+            let stats = store.import(reader, format.into())?;
+            println!("Imported {} events and {} batches into {}", stats.events, stats.batches, path.display());
+        }
+        StorageCommands::Verify => {
+            let report = store.verify()?;
+            for record in &report.corrupt {
+                println!("CORRUPT {} source={} seq={}: {}", record.location, record.source_id, record.seq_no, record.reason);
+            }
+            println!("Checked {} events and {} segments: {} corrupt",
+                report.events_checked, report.segments_checked, report.corrupt.len());
+            if !report.is_clean() {
+                std::process::exit(1);
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+fn format_range(min: Option<u64>, max: Option<u64>) -> String {
+    match (min, max) {
+        (Some(min), Some(max)) => format!("{}..={}", min, max),
+        _ => "-".to_string(),
+    }
+}
+
 fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
     let cli = Cli::parse();
@@ -49,30 +286,13 @@ fn main() -> anyhow::Result<()> {
             println!("Starting Zenith Engine...");
             
             // Read Config
-            let config_content = fs::read_to_string(&config)
-                .unwrap_or_else(|_| "
-[server]
-port = 8080
-
-[engine]
-buffer_size = 1024
-plugins = []
-".to_string());
-            
-            let cfg: Config = toml::from_str(&config_content)?;
+            let cfg = load_config(&config)?;
             
             println!("Config loaded: buffer_size={}, port={}", cfg.engine.buffer_size, cfg.server.port);
 
             // Init Engine
             // Note: In a real CLI, we might want to attach signals to shutdown cleanly
-            let engine = zenith_core::Engine::new(cfg.engine.buffer_size)?;
-            
-            // Load Plugins
-            for plugin_path in cfg.engine.plugins {
-                 println!("Loading plugin: {}", plugin_path);
-                 let wasm_bytes = fs::read(&plugin_path)?;
-                 engine.load_plugin(&wasm_bytes)?;
-            }
+            let engine = build_engine(&cfg)?;
 
             engine.start();
             println!("Engine started. Admin API at http://localhost:{}", cfg.server.port);
@@ -80,6 +300,59 @@ plugins = []
             // Block forever
             std::thread::park();
         }
+        Commands::Replay { storage, source, from_seq, to_seq, from_time, to_time, rate, columnar, target, config } => {
+            let range = replay_range(from_seq, to_seq, from_time, to_time);
+            let columnar = columnar.unwrap_or(matches!(target, ReplayTarget::Engine));
+            let mode = if columnar { ReplayMode::Batches } else { ReplayMode::Events };
+            
+            let store = open_existing(&storage)?;
+            let replayer = Replayer::new(&store, source).range(range).mode(mode).rate(rate);
+            println!("Replaying source {} from {} ({:?})", source, storage.display(), rate);
+            
+            let stats = match target {
+                ReplayTarget::Engine => {
+                    let cfg = load_config(&config)?;
+                    let engine = build_engine(&cfg)?;
+                    engine.start();
+                    
+                    let stats = replayer.run(&engine)?;
+                    
+                    // Let the consumer drain before shutting down
+                    let buffer = engine.get_ring_buffer();
+                    while !buffer.is_empty() {
+                        std::thread::sleep(std::time::Duration::from_millis(10));
+                    }
+                    engine.shutdown();
+                    stats
+                }
+                ReplayTarget::Dataplane => {
+                    let rt = tokio::runtime::Runtime::new()?;
+                    let dataplane = zenith_dataplane::DataPlaneEngine::new(1024);
+                    rt.block_on(dataplane.start())?;
+                    
+                    let stats = replayer.run(&dataplane)?;
+                    
+                    // Let the processor drain before shutting down
+                    while dataplane.queued() > 0 {
+                        std::thread::sleep(std::time::Duration::from_millis(10));
+                    }
+                    dataplane.stop();
+                    stats
+                }
+            };
+            
+            println!(
+                "Replayed {} events ({} rows), seq {:?}..={:?} in {:.3}s",
+                stats.events_replayed,
+                stats.rows_replayed,
+                stats.first_seq,
+                stats.last_seq,
+                stats.elapsed.as_secs_f64()
+            );
+        }
+        Commands::Storage { path, command } => {
+            run_storage(&path, command)?;
+        }
         Commands::Version => {
             println!("Zenith Data Plane v0.1.0");
         }
@@ -84,6 +84,10 @@ impl ZenithEngine {
         });
     }
 
+    pub fn is_running(&self) -> bool {
+        self.running.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
     pub fn shutdown(&self) {
         self.running.store(false, std::sync::atomic::Ordering::Relaxed);
     }
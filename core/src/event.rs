@@ -1,6 +1,9 @@
 use arrow::record_batch::RecordBatch;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Header flag: event was re-injected from storage by a replay
+pub const FLAG_REPLAYED: u32 = 0x1;
+
 #[derive(Debug, Clone)]
 pub struct EventHeader {
     pub source_id: u32,
@@ -4,6 +4,7 @@ pub mod engine;
 pub mod wasm_host;
 pub mod error;
 pub mod admin_api;
+pub mod replay;
 
 use std::ffi::c_void;
 use std::sync::Arc;
@@ -0,0 +1,113 @@
+//! Replay sink - lets `zenith_storage::Replayer` re-inject stored events
+
+use crate::engine::ZenithEngine;
+use crate::error::ZenithError;
+use crate::event::{EventHeader, ZenithEvent, FLAG_REPLAYED};
+use arrow::record_batch::RecordBatch;
+use std::thread;
+use std::time::Duration;
+use zenith_storage::{ReplaySink, StoredBatch, StoredEvent};
+
+impl ZenithEngine {
+    /// Push with backpressure: wait for the consumer instead of dropping
+    fn push_replayed(&self, source_id: u32, seq_no: u64, timestamp_ns: u64, batch: RecordBatch) -> anyhow::Result<()> {
+        let event = ZenithEvent {
+            header: EventHeader {
+                source_id,
+                seq_no,
+                timestamp_ns,
+                flags: FLAG_REPLAYED,
+            },
+            payload: Some(batch),
+        };
+
+        let buffer = self.get_ring_buffer();
+        loop {
+            match buffer.push(event.clone()) {
+                Ok(()) => return Ok(()),
+                Err(ZenithError::BufferFull) if self.is_running() => {
+                    thread::park_timeout(Duration::from_micros(50));
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl ReplaySink for ZenithEngine {
+    /// Row payloads must be Arrow IPC streams (as written by `encode_batch`)
+    fn send_event(&self, event: StoredEvent) -> anyhow::Result<()> {
+        let batch = zenith_storage::decode_batch(&event.data)?;
+        self.push_replayed(event.source_id, event.seq_no, event.timestamp_ns, batch)
+    }
+
+    fn send_batch(&self, batch: StoredBatch) -> anyhow::Result<()> {
+        self.push_replayed(batch.source_id, batch.seq_no, batch.timestamp_ns, batch.batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    fn batch(values: Vec<i64>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![Field::new("v", DataType::Int64, false)]));
+        RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(values))]).unwrap()
+    }
+
+    fn stored(seq_no: u64) -> StoredBatch {
+        StoredBatch { source_id: 7, seq_no, timestamp_ns: seq_no * 10, batch: batch(vec![seq_no as i64]) }
+    }
+
+    #[test]
+    fn test_replay_waits_for_consumer_and_flags_events() {
+        let engine = ZenithEngine::new(1).unwrap();
+        let buffer = engine.get_ring_buffer();
+
+        // Drain slowly so the second push finds the buffer full
+        let consumer = thread::spawn(move || {
+            let mut received = Vec::new();
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while received.len() < 3 && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(20));
+                received.extend(buffer.pop());
+            }
+            received
+        });
+
+        engine.send_batch(stored(0)).unwrap();
+        engine.send_batch(stored(1)).unwrap();
+        let event = StoredEvent {
+            source_id: 7,
+            seq_no: 2,
+            timestamp_ns: 20,
+            data: zenith_storage::encode_batch(&batch(vec![2])).unwrap(),
+        };
+        engine.send_event(event).unwrap();
+
+        let received = consumer.join().unwrap();
+        let seqs: Vec<u64> = received.iter().map(|e| e.header.seq_no).collect();
+        assert_eq!(seqs, vec![0, 1, 2]);
+        assert!(received.iter().all(|e| e.header.flags & FLAG_REPLAYED != 0));
+        assert_eq!(received[2].header.timestamp_ns, 20);
+        assert_eq!(received[2].payload.as_ref().unwrap(), &batch(vec![2]));
+    }
+
+    #[test]
+    fn test_replay_into_stopped_engine_fails_instead_of_waiting() {
+        let engine = ZenithEngine::new(1).unwrap();
+        engine.send_batch(stored(0)).unwrap();
+        engine.shutdown();
+
+        let err = engine.send_batch(stored(1)).unwrap_err();
+        assert!(matches!(err.downcast_ref::<ZenithError>(), Some(ZenithError::BufferFull)));
+
+        // Row payloads that are not Arrow IPC are rejected
+        let event = StoredEvent { source_id: 7, seq_no: 2, timestamp_ns: 0, data: vec![1, 2, 3] };
+        assert!(engine.send_event(event).is_err());
+    }
+}
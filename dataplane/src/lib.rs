@@ -10,6 +10,7 @@ use anyhow::Result;
 
 pub mod pipeline;
 pub mod processor;
+pub mod replay;
 pub mod router;
 
 pub use pipeline::Pipeline;
@@ -87,6 +88,11 @@ impl DataPlaneEngine {
         Ok(())
     }
     
+    /// Events ingested but not yet picked up for processing
+    pub fn queued(&self) -> usize {
+        self.ingress_rx.len()
+    }
+    
     /// Get statistics
     pub fn get_stats(&self) -> DataPlaneStats {
         let processed = self.stats.load(Ordering::Relaxed);
@@ -129,7 +135,42 @@ mod tests {
         
         let stats = dp.get_stats();
         assert_eq!(stats.events_processed, 10);
+        assert_eq!(dp.queued(), 0);
         
         dp.stop();
     }
+
+    #[tokio::test]
+    async fn test_replay_into_dataplane() {
+        use zenith_storage::{Replayer, StorageEngine, StoredEvent};
+
+        let dir = tempfile::tempdir().unwrap();
+        let storage = StorageEngine::open(dir.path()).unwrap();
+        for i in 0..20 {
+            storage.store_event(StoredEvent {
+                source_id: 3,
+                seq_no: i,
+                timestamp_ns: i * 1000,
+                data: vec![i as u8],
+            }).unwrap();
+        }
+
+        let dp = DataPlaneEngine::new(1024);
+        dp.start().await.unwrap();
+
+        let stats = Replayer::new(&storage, 3).run(&dp).unwrap();
+        assert_eq!(stats.events_replayed, 20);
+
+        // The workers drain the queue asynchronously
+        let deadline = tokio::time::Instant::now() + tokio::time::Duration::from_secs(10);
+        while (dp.queued() > 0 || dp.get_stats().events_processed < 20)
+            && tokio::time::Instant::now() < deadline
+        {
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(dp.queued(), 0);
+        assert_eq!(dp.get_stats().events_processed, 20);
+
+        dp.stop();
+    }
 }
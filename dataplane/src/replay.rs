@@ -0,0 +1,25 @@
+/// Replay sink - lets `zenith_storage::Replayer` re-inject stored events
+use crate::{DataPlaneEngine, Event};
+use anyhow::Result;
+use zenith_storage::{ReplaySink, StoredBatch, StoredEvent};
+
+impl ReplaySink for DataPlaneEngine {
+    fn send_event(&self, event: StoredEvent) -> Result<()> {
+        self.ingest(Event {
+            id: event.seq_no,
+            source_id: event.source_id,
+            timestamp_ns: event.timestamp_ns,
+            data: event.data,
+        })
+    }
+
+    /// Batches are forwarded as Arrow IPC stream bytes
+    fn send_batch(&self, batch: StoredBatch) -> Result<()> {
+        self.ingest(Event {
+            id: batch.seq_no,
+            source_id: batch.source_id,
+            timestamp_ns: batch.timestamp_ns,
+            data: zenith_storage::encode_batch(&batch.batch)?,
+        })
+    }
+}
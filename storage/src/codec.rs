@@ -0,0 +1,195 @@
+/// Record Codec
+/// Compression and integrity envelope for values stored in sled trees.
+///
+/// Layout: `[codec:1][raw_len:4 BE][crc32:4 BE][payload]`, where the CRC
+/// covers the codec byte, `raw_len` and the (possibly compressed) payload as
+/// written to disk, so a damaged header is caught before decompressing.
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Envelope header size in bytes
+pub const HEADER_LEN: usize = 9;
+
+/// Largest raw value a record may hold; bounds the buffer decompression allocates
+pub const MAX_RAW_LEN: usize = 256 * 1024 * 1024;
+
+/// Payload compression
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Compression {
+    /// Store payloads as-is
+    #[default]
+    None,
+    /// LZ4 block compression (fast, moderate ratio)
+    Lz4,
+    /// Zstandard at the given level (slower, better ratio)
+    Zstd(i32),
+}
+
+impl Compression {
+    fn id(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Lz4 => 1,
+            Self::Zstd(_) => 2,
+        }
+    }
+
+    /// Equivalent Arrow IPC body compression for segment files
+    pub fn ipc(self) -> Option<arrow_ipc::CompressionType> {
+        match self {
+            Self::None => None,
+            Self::Lz4 => Some(arrow_ipc::CompressionType::LZ4_FRAME),
+            Self::Zstd(_) => Some(arrow_ipc::CompressionType::ZSTD),
+        }
+    }
+}
+
+/// Record decoding errors
+#[derive(Debug, Error)]
+pub enum CodecError {
+    #[error("record truncated: {0} bytes")]
+    Truncated(usize),
+
+    #[error("unknown codec id {0}")]
+    UnknownCodec(u8),
+
+    #[error("checksum mismatch: stored {stored:#010x}, computed {computed:#010x}")]
+    ChecksumMismatch { stored: u32, computed: u32 },
+
+    #[error("decompression failed: {0}")]
+    Decompress(String),
+
+    #[error("decompressed length {actual} does not match header {expected}")]
+    LengthMismatch { expected: usize, actual: usize },
+
+    #[error("raw length {0} exceeds the {MAX_RAW_LEN} byte limit")]
+    TooLarge(usize),
+}
+
+/// Header fields of an encoded record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordHeader {
+    pub codec: u8,
+    pub raw_len: u32,
+    pub crc32: u32,
+}
+
+/// Compress and checksum a raw value
+pub fn encode(raw: &[u8], compression: Compression) -> anyhow::Result<Vec<u8>> {
+    if raw.len() > MAX_RAW_LEN {
+        return Err(CodecError::TooLarge(raw.len()).into());
+    }
+
+    let payload = match compression {
+        Compression::None => raw.to_vec(),
+        Compression::Lz4 => lz4_flex::block::compress(raw),
+        Compression::Zstd(level) => zstd::bulk::compress(raw, level)?,
+    };
+
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+    out.push(compression.id());
+    out.extend_from_slice(&(raw.len() as u32).to_be_bytes());
+    let crc32 = checksum(&out, &payload);
+    out.extend_from_slice(&crc32.to_be_bytes());
+    out.extend_from_slice(&payload);
+    Ok(out)
+}
+
+/// CRC32 of the codec byte and `raw_len` followed by the payload
+fn checksum(header: &[u8], payload: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&header[..5]);
+    hasher.update(payload);
+    hasher.finalize()
+}
+
+/// Parse the header without touching the payload
+pub fn header(record: &[u8]) -> Result<RecordHeader, CodecError> {
+    if record.len() < HEADER_LEN {
+        return Err(CodecError::Truncated(record.len()));
+    }
+
+    Ok(RecordHeader {
+        codec: record[0],
+        raw_len: u32::from_be_bytes(record[1..5].try_into().expect("4 bytes")),
+        crc32: u32::from_be_bytes(record[5..9].try_into().expect("4 bytes")),
+    })
+}
+
+/// Verify the checksum and decompress a record
+pub fn decode(record: &[u8]) -> Result<Vec<u8>, CodecError> {
+    let header = header(record)?;
+    let payload = &record[HEADER_LEN..];
+
+    let computed = checksum(record, payload);
+    if computed != header.crc32 {
+        return Err(CodecError::ChecksumMismatch { stored: header.crc32, computed });
+    }
+
+    let expected = header.raw_len as usize;
+    if expected > MAX_RAW_LEN {
+        return Err(CodecError::TooLarge(expected));
+    }
+    let raw = match header.codec {
+        0 => payload.to_vec(),
+        1 => lz4_flex::block::decompress(payload, expected)
+            .map_err(|e| CodecError::Decompress(e.to_string()))?,
+        2 => zstd::bulk::decompress(payload, expected)
+            .map_err(|e| CodecError::Decompress(e.to_string()))?,
+        other => return Err(CodecError::UnknownCodec(other)),
+    };
+
+    if raw.len() != expected {
+        return Err(CodecError::LengthMismatch { expected, actual: raw.len() });
+    }
+
+    Ok(raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_all_codecs() {
+        let raw: Vec<u8> = b"cpu=0.51 mem=0.73 ".iter().copied().cycle().take(4096).collect();
+
+        for compression in [Compression::None, Compression::Lz4, Compression::Zstd(3)] {
+            let encoded = encode(&raw, compression).unwrap();
+            assert_eq!(decode(&encoded).unwrap(), raw);
+
+            if compression != Compression::None {
+                assert!(encoded.len() < raw.len() / 4);
+            }
+        }
+    }
+
+    #[test]
+    fn test_corruption_detected() {
+        let mut encoded = encode(b"hello zenith", Compression::Lz4).unwrap();
+        let last = encoded.len() - 1;
+        encoded[last] ^= 0xff;
+
+        assert!(matches!(decode(&encoded), Err(CodecError::ChecksumMismatch { .. })));
+        assert!(matches!(decode(&encoded[..4]), Err(CodecError::Truncated(4))));
+    }
+
+    #[test]
+    fn test_header_covered_by_checksum() {
+        let encoded = encode(b"hello zenith", Compression::Zstd(1)).unwrap();
+
+        // A flipped codec byte or raw_len fails the checksum before decompressing
+        for index in [0, 1, 4] {
+            let mut damaged = encoded.clone();
+            damaged[index] ^= 0x80;
+            assert!(matches!(decode(&damaged), Err(CodecError::ChecksumMismatch { .. })));
+        }
+
+        // An oversized raw_len is refused even with a matching checksum
+        let mut oversized = encoded.clone();
+        oversized[1..5].copy_from_slice(&u32::MAX.to_be_bytes());
+        let crc32 = checksum(&oversized, &oversized[HEADER_LEN..]);
+        oversized[5..9].copy_from_slice(&crc32.to_be_bytes());
+        assert!(matches!(decode(&oversized), Err(CodecError::TooLarge(_))));
+    }
+}
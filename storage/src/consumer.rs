@@ -0,0 +1,227 @@
+/// Consumer Groups
+/// Named cursors over stored events with offsets persisted in sled.
+///
+/// An offset is the next seq_no to deliver. `poll` advances an in-memory
+/// position; only `commit` makes it durable, so a consumer that crashes
+/// before committing re-reads from its last commit (at-least-once).
+use crate::{StorageEngine, StoredEvent};
+use anyhow::{anyhow, Result};
+use sled::Tree;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// A named consumer cursor
+pub struct ConsumerGroup {
+    name: String,
+    offsets: Tree,
+    events: Tree,
+    positions: Mutex<HashMap<u32, u64>>,
+}
+
+impl ConsumerGroup {
+    pub(crate) fn new(name: &str, offsets: Tree, events: Tree) -> Result<Self> {
+        if name.is_empty() || name.contains('\0') {
+            return Err(anyhow!("invalid consumer group name: {:?}", name));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            offsets,
+            events,
+            positions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Group name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Poll up to `max` events from the current position and advance it
+    pub fn poll(&self, source_id: u32, max: usize) -> Result<Vec<StoredEvent>> {
+        let position = self.position(source_id)?;
+        let events = self.poll_from(source_id, position, max)?;
+
+        if let Some(last) = events.last() {
+            self.set_position(source_id, last.seq_no.saturating_add(1))?;
+        }
+
+        Ok(events)
+    }
+
+    /// Read up to `max` events starting at `offset` without moving the cursor
+    pub fn poll_from(&self, source_id: u32, offset: u64, max: usize) -> Result<Vec<StoredEvent>> {
+        let start = StorageEngine::make_key(source_id, offset);
+        let end = StorageEngine::make_key(source_id, u64::MAX);
+        let mut events = Vec::new();
+
+        for item in self.events.range(start..=end).take(max) {
+            let (_key, value) = item?;
+            events.push(StorageEngine::decode_event(&value)?);
+        }
+
+        Ok(events)
+    }
+
+    /// Durably commit the current position
+    pub fn commit(&self, source_id: u32) -> Result<u64> {
+        let position = self.position(source_id)?;
+        self.commit_offset(source_id, position)?;
+        Ok(position)
+    }
+
+    /// Durably commit an explicit offset and move the cursor there
+    pub fn commit_offset(&self, source_id: u32, offset: u64) -> Result<()> {
+        self.offsets.insert(self.offset_key(source_id), &offset.to_be_bytes())?;
+        self.offsets.flush()?;
+        self.set_position(source_id, offset)
+    }
+
+    /// Last committed offset, if any
+    pub fn committed(&self, source_id: u32) -> Result<Option<u64>> {
+        match self.offsets.get(self.offset_key(source_id))? {
+            Some(value) => {
+                let bytes: [u8; 8] = value
+                    .as_ref()
+                    .try_into()
+                    .map_err(|_| anyhow!("corrupt offset for group {}", self.name))?;
+                Ok(Some(u64::from_be_bytes(bytes)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Current (possibly uncommitted) position
+    pub fn position(&self, source_id: u32) -> Result<u64> {
+        if let Some(position) = self.lock_positions()?.get(&source_id) {
+            return Ok(*position);
+        }
+        Ok(self.committed(source_id)?.unwrap_or(0))
+    }
+
+    /// Move the cursor to `offset` (not committed)
+    pub fn seek(&self, source_id: u32, offset: u64) -> Result<()> {
+        self.set_position(source_id, offset)
+    }
+
+    /// Move the cursor to the first event at or after `timestamp_ns`.
+    /// Returns the new position (one past the last event if none qualify).
+    pub fn seek_to_timestamp(&self, source_id: u32, timestamp_ns: u64) -> Result<u64> {
+        let mut target = None;
+        let mut next_after_last = 0;
+
+        for item in self.events.scan_prefix(source_id.to_be_bytes()) {
+            let (_key, value) = item?;
+            let event = StorageEngine::decode_event(&value)?;
+            if event.timestamp_ns >= timestamp_ns {
+                target = Some(event.seq_no);
+                break;
+            }
+            next_after_last = event.seq_no.saturating_add(1);
+        }
+
+        let position = target.unwrap_or(next_after_last);
+        self.set_position(source_id, position)?;
+        Ok(position)
+    }
+
+    /// Number of stored events at or after the committed offset
+    pub fn lag(&self, source_id: u32) -> Result<usize> {
+        let committed = self.committed(source_id)?.unwrap_or(0);
+        let start = StorageEngine::make_key(source_id, committed);
+        let end = StorageEngine::make_key(source_id, u64::MAX);
+        Ok(self.events.range(start..=end).count())
+    }
+
+    fn set_position(&self, source_id: u32, position: u64) -> Result<()> {
+        self.lock_positions()?.insert(source_id, position);
+        Ok(())
+    }
+
+    fn lock_positions(&self) -> Result<std::sync::MutexGuard<'_, HashMap<u32, u64>>> {
+        self.positions.lock().map_err(|_| anyhow!("consumer positions poisoned"))
+    }
+
+    // Key: [group name][0x00][source_id:4 bytes]
+    fn offset_key(&self, source_id: u32) -> Vec<u8> {
+        let mut key = Self::group_prefix(&self.name);
+        key.extend_from_slice(&source_id.to_be_bytes());
+        key
+    }
+
+    pub(crate) fn group_prefix(name: &str) -> Vec<u8> {
+        let mut prefix = name.as_bytes().to_vec();
+        prefix.push(0);
+        prefix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{StorageEngine, StoredEvent};
+    use tempfile::tempdir;
+
+    fn fill(storage: &StorageEngine, source_id: u32, count: u64) {
+        for i in 0..count {
+            storage.store_event(StoredEvent {
+                source_id,
+                seq_no: i,
+                timestamp_ns: i * 100,
+                data: vec![i as u8],
+            }).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_poll_commit_resume() {
+        let dir = tempdir().unwrap();
+
+        {
+            let storage = StorageEngine::open(dir.path()).unwrap();
+            fill(&storage, 1, 10);
+
+            let group = storage.consumer_group("etl").unwrap();
+            let first = group.poll(1, 4).unwrap();
+            assert_eq!(first.iter().map(|e| e.seq_no).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+            assert_eq!(group.commit(1).unwrap(), 4);
+
+            // Polled but never committed
+            let uncommitted = group.poll(1, 3).unwrap();
+            assert_eq!(uncommitted[0].seq_no, 4);
+            assert_eq!(group.lag(1).unwrap(), 6);
+            drop(group);
+            storage.flush().unwrap();
+        }
+
+        // Reopen: delivery resumes from the last commit
+        let storage = crate::tests::reopen(dir.path());
+        let group = storage.consumer_group("etl").unwrap();
+        assert_eq!(group.committed(1).unwrap(), Some(4));
+        assert_eq!(group.poll(1, 100).unwrap().len(), 6);
+
+        // Other groups are independent
+        let other = storage.consumer_group("audit").unwrap();
+        assert_eq!(other.position(1).unwrap(), 0);
+        assert_eq!(storage.consumer_groups().unwrap(), vec!["etl".to_string()]);
+
+        assert!(storage.delete_consumer_group("etl").unwrap());
+        assert_eq!(storage.consumer_group("etl").unwrap().committed(1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_seek_to_timestamp() {
+        let dir = tempdir().unwrap();
+        let storage = StorageEngine::open(dir.path()).unwrap();
+        fill(&storage, 2, 10);
+
+        let group = storage.consumer_group("replay").unwrap();
+        assert_eq!(group.seek_to_timestamp(2, 450).unwrap(), 5);
+        assert_eq!(group.poll(2, 1).unwrap()[0].seq_no, 5);
+
+        // Past the end lands after the last event
+        assert_eq!(group.seek_to_timestamp(2, 10_000).unwrap(), 10);
+        assert!(group.poll(2, 10).unwrap().is_empty());
+
+        assert!(storage.consumer_group("").is_err());
+    }
+}
@@ -3,8 +3,28 @@
 use sled::{Db, Tree};
 use serde::{Serialize, Deserialize};
 use anyhow::Result;
+use arrow_array::RecordBatch;
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
 use std::path::Path;
 
+pub mod codec;
+pub mod consumer;
+pub mod export;
+pub mod replay;
+pub mod segment;
+pub mod stats;
+
+pub use codec::{CodecError, Compression};
+pub use consumer::ConsumerGroup;
+pub use export::{ExportFormat, ImportStats};
+pub use replay::{ReplayMode, ReplayRange, ReplayRate, ReplaySink, ReplayStats, Replayer};
+pub use segment::{decode_batch, encode_batch, SegmentMeta, SegmentStore, StoredBatch};
+pub use stats::{CompressionStats, SourceStats, StorageStats};
+
+/// Key in the default tree marking every `events` value as a codec envelope
+const EVENTS_ENVELOPE_MARKER: &[u8] = b"events_format:envelope";
+
 /// Event storage record
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoredEvent {
@@ -14,25 +34,94 @@ pub struct StoredEvent {
     pub data: Vec<u8>,
 }
 
+/// Storage engine configuration
+#[derive(Debug, Clone)]
+pub struct StorageConfig {
+    /// Rows buffered per source before a columnar segment file is written
+    pub segment_max_rows: usize,
+    /// Compression for new records in the `events` tree
+    pub event_compression: Compression,
+    /// Compression for new columnar segment files
+    pub segment_compression: Compression,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            segment_max_rows: segment::DEFAULT_SEGMENT_MAX_ROWS,
+            event_compression: Compression::Lz4,
+            segment_compression: Compression::Lz4,
+        }
+    }
+}
+
+/// A record that failed verification
+#[derive(Debug, Clone)]
+pub struct CorruptRecord {
+    /// Tree or file the record lives in
+    pub location: String,
+    pub source_id: u32,
+    pub seq_no: u64,
+    pub reason: String,
+}
+
+/// Result of a `verify()` scan
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    pub events_checked: u64,
+    pub segments_checked: u64,
+    pub corrupt: Vec<CorruptRecord>,
+}
+
+impl VerifyReport {
+    /// No corruption found
+    pub fn is_clean(&self) -> bool {
+        self.corrupt.is_empty()
+    }
+}
+
 /// Storage engine for Zenith events
 pub struct StorageEngine {
     db: Db,
     events: Tree,
+    consumer_offsets: Tree,
+    segments: SegmentStore,
+    event_compression: Compression,
 }
 
 impl StorageEngine {
     /// Open or create storage at path
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with_config(path, StorageConfig::default())
+    }
+    
+    /// Open or create storage at path with custom configuration
+    pub fn open_with_config<P: AsRef<Path>>(path: P, config: StorageConfig) -> Result<Self> {
+        let path = path.as_ref();
         let db = sled::open(path)?;
         let events = db.open_tree("events")?;
+        Self::migrate_legacy_events(&db, &events, config.event_compression)?;
+        let consumer_offsets = db.open_tree("consumer_offsets")?;
+        let segments = SegmentStore::open(
+            path.join("segments"),
+            db.open_tree("segment_index")?,
+            config.segment_max_rows,
+            config.segment_compression,
+        )?;
         
-        Ok(Self { db, events })
+        Ok(Self {
+            db,
+            events,
+            consumer_offsets,
+            segments,
+            event_compression: config.event_compression,
+        })
     }
     
     /// Store an event
     pub fn store_event(&self, event: StoredEvent) -> Result<()> {
         let key = Self::make_key(event.source_id, event.seq_no);
-        let value = bincode::serialize(&event)?;
+        let value = codec::encode(&bincode::serialize(&event)?, self.event_compression)?;
         self.events.insert(key, value)?;
         Ok(())
     }
@@ -41,10 +130,7 @@ impl StorageEngine {
     pub fn get_event(&self, source_id: u32, seq_no: u64) -> Result<Option<StoredEvent>> {
         let key = Self::make_key(source_id, seq_no);
         match self.events.get(key)? {
-            Some(data) => {
-                let event = bincode::deserialize(&data)?;
-                Ok(Some(event))
-            }
+            Some(data) => Ok(Some(Self::decode_event(&data)?)),
             None => Ok(None),
         }
     }
@@ -56,13 +142,145 @@ impl StorageEngine {
         
         for item in self.events.scan_prefix(prefix) {
             let (_key, value) = item?;
-            let event: StoredEvent = bincode::deserialize(&value)?;
-            events.push(event);
+            events.push(Self::decode_event(&value)?);
+        }
+        
+        Ok(events)
+    }
+    
+    /// Get events for a source within an inclusive seq_no range
+    pub fn get_event_range(&self, source_id: u32, seq_range: RangeInclusive<u64>) -> Result<Vec<StoredEvent>> {
+        let start = Self::make_key(source_id, *seq_range.start());
+        let end = Self::make_key(source_id, *seq_range.end());
+        let mut events = Vec::new();
+        
+        for item in self.events.range(start..=end) {
+            let (_key, value) = item?;
+            events.push(Self::decode_event(&value)?);
         }
         
         Ok(events)
     }
     
+    /// Compression ratio of the `events` tree (reads record headers only)
+    pub fn compression_stats(&self) -> Result<CompressionStats> {
+        let mut stats = CompressionStats::default();
+        
+        for item in self.events.iter() {
+            let (_key, value) = item?;
+            let header = codec::header(&value)?;
+            stats.records += 1;
+            stats.raw_bytes += header.raw_len as u64;
+            stats.stored_bytes += value.len() as u64;
+        }
+        
+        Ok(stats)
+    }
+    
+    /// Scan every event record and segment file, reporting corruption
+    pub fn verify(&self) -> Result<VerifyReport> {
+        let mut report = VerifyReport::default();
+        
+        for item in self.events.iter() {
+            let (key, value) = item?;
+            let (source_id, seq_no) = Self::parse_key(&key);
+            report.events_checked += 1;
+            
+            let reason = match Self::decode_event(&value) {
+                Ok(event) if (event.source_id, event.seq_no) != (source_id, seq_no) => {
+                    Some(format!("key/record mismatch: record is {}:{}", event.source_id, event.seq_no))
+                }
+                Ok(_) => None,
+                Err(e) => Some(e.to_string()),
+            };
+            
+            if let Some(reason) = reason {
+                report.corrupt.push(CorruptRecord {
+                    location: "events".to_string(),
+                    source_id,
+                    seq_no,
+                    reason,
+                });
+            }
+        }
+        
+        let segments = self.segments.all_segments()?;
+        report.segments_checked = segments.len() as u64;
+        report.corrupt.extend(self.segments.verify(&segments)?);
+        
+        Ok(report)
+    }
+    
+    /// Get events for a source within a seq_no or timestamp range
+    pub fn events_in_range(&self, source_id: u32, range: &ReplayRange) -> Result<Vec<StoredEvent>> {
+        match range {
+            ReplayRange::All => self.get_source_events(source_id),
+            ReplayRange::Seq(range) => self.get_event_range(source_id, range.clone()),
+            ReplayRange::Time(range) => {
+                let mut events = self.get_source_events(source_id)?;
+                events.retain(|e| range.contains(&e.timestamp_ns));
+                Ok(events)
+            }
+        }
+    }
+    
+    /// Get columnar batches for a source within a seq_no or timestamp range
+    pub fn batches_in_range(&self, source_id: u32, range: &ReplayRange) -> Result<Vec<StoredBatch>> {
+        match range {
+            ReplayRange::All => self.segments.read_source(source_id),
+            ReplayRange::Seq(range) => self.segments.read_seq_range(source_id, range.clone()),
+            ReplayRange::Time(range) => self.segments.read_time_range(source_id, range.clone()),
+        }
+    }
+    
+    /// Per-source counts, sizes and ranges plus on-disk size
+    pub fn stats(&self) -> Result<StorageStats> {
+        let mut stats = StorageStats::default();
+        let mut sources: BTreeMap<u32, SourceStats> = BTreeMap::new();
+        
+        for item in self.events.iter() {
+            let (key, value) = item?;
+            let (source_id, seq_no) = Self::parse_key(&key);
+            let source = sources.entry(source_id).or_insert_with(|| SourceStats::new(source_id));
+            
+            let Ok(header) = codec::header(&value) else {
+                stats.corrupt_records += 1;
+                continue;
+            };
+            source.events += 1;
+            source.raw_bytes += header.raw_len as u64;
+            source.stored_bytes += value.len() as u64;
+            
+            match Self::decode_event(&value) {
+                Ok(event) => source.observe(seq_no, event.timestamp_ns),
+                Err(_) => stats.corrupt_records += 1,
+            }
+        }
+        
+        let mut segment_bytes = 0;
+        for segment in self.segments.all_segments()? {
+            let source = sources.entry(segment.source_id).or_insert_with(|| SourceStats::new(segment.source_id));
+            source.segments += 1;
+            source.segment_rows += segment.num_rows;
+            source.segment_bytes += segment.file_size_bytes;
+            source.observe(segment.first_seq, segment.min_timestamp_ns);
+            source.observe(segment.last_seq, segment.max_timestamp_ns);
+            segment_bytes += segment.file_size_bytes;
+        }
+        
+        stats.sources = sources.into_values().collect();
+        stats.total_events = stats.sources.iter().map(|s| s.events).sum();
+        stats.total_segments = stats.sources.iter().map(|s| s.segments).sum();
+        stats.compression = CompressionStats {
+            records: stats.total_events,
+            raw_bytes: stats.sources.iter().map(|s| s.raw_bytes).sum(),
+            stored_bytes: stats.sources.iter().map(|s| s.stored_bytes).sum(),
+        };
+        stats.disk_size_bytes = self.db.size_on_disk()? + segment_bytes;
+        
+        Ok(stats)
+    }
+    
     /// Count total events
     pub fn count_events(&self) -> usize {
         self.events.len()
@@ -74,24 +292,134 @@ impl StorageEngine {
         Ok(self.events.remove(key)?.is_some())
     }
     
-    /// Flush to disk
+    /// Store a RecordBatch in columnar (Arrow IPC segment) mode
+    ///
+    /// The batch is buffered until its source's segment fills up; reads see
+    /// it right away, but it only reaches disk on `flush` or when the
+    /// segment is written. Storing a seq_no already written to a segment is
+    /// an error, and a batch whose store fails is not buffered.
+    pub fn store_batch(&self, source_id: u32, seq_no: u64, timestamp_ns: u64, batch: RecordBatch) -> Result<()> {
+        self.segments.append(source_id, seq_no, timestamp_ns, batch)
+    }
+    
+    /// Read columnar batches of a source by seq_no range
+    pub fn get_batches(&self, source_id: u32, seq_range: RangeInclusive<u64>) -> Result<Vec<StoredBatch>> {
+        self.segments.read_seq_range(source_id, seq_range)
+    }
+    
+    /// Read columnar batches of a source by timestamp range
+    pub fn get_batches_by_time(&self, source_id: u32, time_range: RangeInclusive<u64>) -> Result<Vec<StoredBatch>> {
+        self.segments.read_time_range(source_id, time_range)
+    }
+    
+    /// Columnar segment store
+    pub fn segments(&self) -> &SegmentStore {
+        &self.segments
+    }
+    
+    /// Open a named consumer cursor (offsets persist across restarts)
+    pub fn consumer_group(&self, name: &str) -> Result<ConsumerGroup> {
+        ConsumerGroup::new(name, self.consumer_offsets.clone(), self.events.clone())
+    }
+    
+    /// Names of consumer groups with committed offsets
+    pub fn consumer_groups(&self) -> Result<Vec<String>> {
+        let mut names: Vec<String> = Vec::new();
+        
+        for item in self.consumer_offsets.iter() {
+            let (key, _value) = item?;
+            let name_len = key.iter().position(|b| *b == 0).unwrap_or(key.len());
+            let name = String::from_utf8_lossy(&key[..name_len]).into_owned();
+            if names.last() != Some(&name) {
+                names.push(name);
+            }
+        }
+        
+        Ok(names)
+    }
+    
+    /// Delete all committed offsets of a consumer group
+    pub fn delete_consumer_group(&self, name: &str) -> Result<bool> {
+        let mut removed = false;
+        
+        for item in self.consumer_offsets.scan_prefix(ConsumerGroup::group_prefix(name)) {
+            let (key, _value) = item?;
+            self.consumer_offsets.remove(key)?;
+            removed = true;
+        }
+        
+        Ok(removed)
+    }
+    
+    /// Flush to disk (writes buffered columnar segments first)
     pub fn flush(&self) -> Result<usize> {
+        self.segments.flush()?;
         Ok(self.db.flush()?)
     }
     
-    /// Clear all events
+    /// Clear all events and segments
     pub fn clear(&self) -> Result<()> {
         self.events.clear()?;
+        self.segments.clear()?;
         Ok(())
     }
     
+    /// Wrap `events` values written before the codec envelope existed (raw
+    /// bincode) in one. Runs until it completes once per store; values that
+    /// already are envelopes for their key are skipped, so an interrupted
+    /// migration simply resumes. Returns the number of values rewritten.
+    fn migrate_legacy_events(db: &Db, events: &Tree, compression: Compression) -> Result<u64> {
+        if db.contains_key(EVENTS_ENVELOPE_MARKER)? {
+            return Ok(0);
+        }
+        
+        let mut migrated = 0;
+        for item in events.iter() {
+            let (key, value) = item?;
+            let expected = Self::parse_key(&key);
+            let matches_key = |event: &StoredEvent| (event.source_id, event.seq_no) == expected;
+            
+            if Self::decode_event(&value).is_ok_and(|event| matches_key(&event)) {
+                continue;
+            }
+            // Anything else that is not a legacy record is left for `verify` to report
+            match bincode::deserialize::<StoredEvent>(&value) {
+                Ok(event) if matches_key(&event) => {
+                    events.insert(key, codec::encode(&value, compression)?)?;
+                    migrated += 1;
+                }
+                _ => {}
+            }
+        }
+        
+        db.insert(EVENTS_ENVELOPE_MARKER, &[])?;
+        db.flush()?;
+        Ok(migrated)
+    }
+    
     // Helper: create composite key
-    fn make_key(source_id: u32, seq_no: u64) -> [u8; 12] {
+    pub(crate) fn make_key(source_id: u32, seq_no: u64) -> [u8; 12] {
         let mut key = [0u8; 12];
         key[0..4].copy_from_slice(&source_id.to_be_bytes());
         key[4..12].copy_from_slice(&seq_no.to_be_bytes());
         key
     }
+    
+    // Helper: split composite key (zeros for malformed keys)
+    pub(crate) fn parse_key(key: &[u8]) -> (u32, u64) {
+        if key.len() != 12 {
+            return (0, 0);
+        }
+        let source_id = u32::from_be_bytes(key[0..4].try_into().expect("4 bytes"));
+        let seq_no = u64::from_be_bytes(key[4..12].try_into().expect("8 bytes"));
+        (source_id, seq_no)
+    }
+    
+    // Helper: checksum, decompress and deserialize an events tree value
+    pub(crate) fn decode_event(value: &[u8]) -> Result<StoredEvent> {
+        let raw = codec::decode(value)?;
+        Ok(bincode::deserialize(&raw)?)
+    }
 }
 
 #[cfg(test)]
@@ -99,6 +427,19 @@ mod tests {
     use super::*;
     use tempfile::tempdir;
 
+    /// Open a store that was just dropped. sled's IO threads may hold the
+    /// directory lock for a moment after the engine is gone.
+    pub(crate) fn reopen(path: &Path) -> StorageEngine {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        loop {
+            match StorageEngine::open(path) {
+                Ok(storage) => return storage,
+                Err(e) if std::time::Instant::now() >= deadline => panic!("reopen failed: {}", e),
+                Err(_) => std::thread::sleep(std::time::Duration::from_millis(10)),
+            }
+        }
+    }
+
     #[test]
     fn test_storage_crud() {
         let dir = tempdir().unwrap();
@@ -159,4 +500,93 @@ mod tests {
             assert_eq!(event.seq_no, i as u64);
         }
     }
+
+    #[test]
+    fn test_compression_and_verify() {
+        let dir = tempdir().unwrap();
+        let storage = StorageEngine::open_with_config(dir.path(), StorageConfig {
+            event_compression: Compression::Zstd(3),
+            ..Default::default()
+        }).unwrap();
+        
+        for i in 0..10 {
+            storage.store_event(StoredEvent {
+                source_id: 1,
+                seq_no: i,
+                timestamp_ns: i * 1000,
+                data: b"temperature=21.5;humidity=40;".repeat(32),
+            }).unwrap();
+        }
+        
+        let stats = storage.compression_stats().unwrap();
+        assert_eq!(stats.records, 10);
+        assert!(stats.ratio() > 4.0);
+        assert!(storage.verify().unwrap().is_clean());
+        
+        // Flip a payload byte behind the engine's back
+        let key = StorageEngine::make_key(1, 3);
+        let mut raw = storage.events.get(key).unwrap().unwrap().to_vec();
+        let last = raw.len() - 1;
+        raw[last] ^= 0x55;
+        storage.events.insert(key, raw).unwrap();
+        
+        assert!(storage.get_event(1, 3).is_err());
+        let report = storage.verify().unwrap();
+        assert_eq!(report.events_checked, 10);
+        assert_eq!(report.corrupt.len(), 1);
+        assert_eq!((report.corrupt[0].source_id, report.corrupt[0].seq_no), (1, 3));
+    }
+
+    #[test]
+    fn test_legacy_events_migrated_on_open() {
+        let dir = tempdir().unwrap();
+        let legacy = StoredEvent { source_id: 7, seq_no: 42, timestamp_ns: 1000, data: vec![9; 16] };
+        {
+            // Layout of stores written before the codec envelope: raw bincode
+            let db = sled::open(dir.path()).unwrap();
+            let events = db.open_tree("events").unwrap();
+            events.insert(StorageEngine::make_key(7, 42), bincode::serialize(&legacy).unwrap()).unwrap();
+            db.flush().unwrap();
+        }
+        
+        let storage = StorageEngine::open(dir.path()).unwrap();
+        assert_eq!(storage.get_event(7, 42).unwrap().unwrap().data, legacy.data);
+        assert!(storage.verify().unwrap().is_clean());
+        assert_eq!(storage.stats().unwrap().corrupt_records, 0);
+        
+        // Later opens do not touch the values again
+        drop(storage);
+        let storage = reopen(dir.path());
+        assert_eq!(storage.get_event(7, 42).unwrap().unwrap().timestamp_ns, 1000);
+    }
+
+    #[test]
+    fn test_stats() {
+        let dir = tempdir().unwrap();
+        let storage = StorageEngine::open(dir.path()).unwrap();
+        
+        for (source_id, count) in [(1u32, 5u64), (2, 3)] {
+            for i in 0..count {
+                storage.store_event(StoredEvent {
+                    source_id,
+                    seq_no: 10 + i,
+                    timestamp_ns: 500 + i,
+                    data: vec![0; 64],
+                }).unwrap();
+            }
+        }
+        storage.flush().unwrap();
+        
+        let stats = storage.stats().unwrap();
+        assert_eq!(stats.total_events, 8);
+        assert_eq!(stats.sources.len(), 2);
+        
+        let first = &stats.sources[0];
+        assert_eq!(first.source_id, 1);
+        assert_eq!(first.events, 5);
+        assert_eq!((first.min_seq, first.max_seq), (Some(10), Some(14)));
+        assert_eq!((first.min_timestamp_ns, first.max_timestamp_ns), (Some(500), Some(504)));
+        assert!(first.raw_bytes > first.stored_bytes);
+        assert!(stats.disk_size_bytes > 0);
+    }
 }
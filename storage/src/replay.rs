@@ -0,0 +1,239 @@
+/// Event Replay
+/// Re-injects stored events into a processing engine, optionally paced
+/// by their original timestamps
+use crate::{StorageEngine, StoredBatch, StoredEvent};
+use anyhow::{anyhow, bail, Result};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Replay pacing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayRate {
+    /// Reproduce the original inter-event spacing
+    Original,
+    /// Original spacing divided by the factor (2.0 = twice as fast)
+    Multiplier(f64),
+    /// No pacing
+    AsFastAsPossible,
+}
+
+impl FromStr for ReplayRate {
+    type Err = anyhow::Error;
+
+    /// Accepts `original`, `max`/`fast`, or a multiplier like `2`, `0.5x`
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "original" | "1x" => Ok(Self::Original),
+            "max" | "fast" | "asap" => Ok(Self::AsFastAsPossible),
+            other => {
+                let factor: f64 = other
+                    .trim_end_matches('x')
+                    .parse()
+                    .map_err(|_| anyhow!("invalid replay rate: {}", s))?;
+                if !factor.is_finite() || factor <= 0.0 {
+                    bail!("replay rate multiplier must be positive: {}", s);
+                }
+                Ok(Self::Multiplier(factor))
+            }
+        }
+    }
+}
+
+/// Which part of a source to replay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayRange {
+    /// Everything stored for the source
+    All,
+    /// Inclusive seq_no range
+    Seq(RangeInclusive<u64>),
+    /// Inclusive timestamp range in nanoseconds
+    Time(RangeInclusive<u64>),
+}
+
+/// Which storage mode to read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayMode {
+    /// Row events (`StoredEvent`)
+    Events,
+    /// Columnar segments (`StoredBatch`)
+    Batches,
+}
+
+/// Destination for replayed data
+pub trait ReplaySink {
+    /// Publish a row event
+    fn send_event(&self, event: StoredEvent) -> Result<()>;
+
+    /// Publish a columnar batch
+    fn send_batch(&self, batch: StoredBatch) -> Result<()>;
+}
+
+/// Replay statistics
+#[derive(Debug, Clone, Default)]
+pub struct ReplayStats {
+    pub events_replayed: u64,
+    pub rows_replayed: u64,
+    pub first_seq: Option<u64>,
+    pub last_seq: Option<u64>,
+    pub elapsed: Duration,
+}
+
+impl ReplayStats {
+    fn record(&mut self, seq_no: u64, rows: u64) {
+        self.events_replayed += 1;
+        self.rows_replayed += rows;
+        self.first_seq.get_or_insert(seq_no);
+        self.last_seq = Some(seq_no);
+    }
+}
+
+/// Reads a source range from storage and publishes it to a sink
+pub struct Replayer<'a> {
+    storage: &'a StorageEngine,
+    source_id: u32,
+    range: ReplayRange,
+    mode: ReplayMode,
+    rate: ReplayRate,
+}
+
+impl<'a> Replayer<'a> {
+    /// Replay every row event of a source as fast as possible
+    pub fn new(storage: &'a StorageEngine, source_id: u32) -> Self {
+        Self {
+            storage,
+            source_id,
+            range: ReplayRange::All,
+            mode: ReplayMode::Events,
+            rate: ReplayRate::AsFastAsPossible,
+        }
+    }
+
+    /// Restrict the replay to a range
+    pub fn range(mut self, range: ReplayRange) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// Read row events or columnar batches
+    pub fn mode(mut self, mode: ReplayMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set pacing
+    pub fn rate(mut self, rate: ReplayRate) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// Run the replay to completion
+    pub fn run<S: ReplaySink + ?Sized>(&self, sink: &S) -> Result<ReplayStats> {
+        let mut stats = ReplayStats::default();
+        let started = Instant::now();
+
+        match self.mode {
+            ReplayMode::Events => {
+                let events = self.storage.events_in_range(self.source_id, &self.range)?;
+                let first_ts = events.first().map(|e| e.timestamp_ns).unwrap_or(0);
+
+                for event in events {
+                    self.pace(started, first_ts, event.timestamp_ns);
+                    stats.record(event.seq_no, 1);
+                    sink.send_event(event)?;
+                }
+            }
+            ReplayMode::Batches => {
+                let batches = self.storage.batches_in_range(self.source_id, &self.range)?;
+                let first_ts = batches.first().map(|b| b.timestamp_ns).unwrap_or(0);
+
+                for batch in batches {
+                    self.pace(started, first_ts, batch.timestamp_ns);
+                    stats.record(batch.seq_no, batch.batch.num_rows() as u64);
+                    sink.send_batch(batch)?;
+                }
+            }
+        }
+
+        stats.elapsed = started.elapsed();
+        Ok(stats)
+    }
+
+    /// Sleep until the event's offset from the first event, scaled by the rate
+    fn pace(&self, started: Instant, first_ts: u64, ts: u64) {
+        let factor = match self.rate {
+            ReplayRate::AsFastAsPossible => return,
+            ReplayRate::Original => 1.0,
+            ReplayRate::Multiplier(factor) => factor,
+        };
+
+        let offset = Duration::from_nanos(ts.saturating_sub(first_ts)).div_f64(factor);
+        let elapsed = started.elapsed();
+        if offset > elapsed {
+            std::thread::sleep(offset - elapsed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tempfile::tempdir;
+
+    #[derive(Default)]
+    struct CollectSink {
+        seqs: Mutex<Vec<u64>>,
+    }
+
+    impl ReplaySink for CollectSink {
+        fn send_event(&self, event: StoredEvent) -> Result<()> {
+            self.seqs.lock().unwrap().push(event.seq_no);
+            Ok(())
+        }
+
+        fn send_batch(&self, batch: StoredBatch) -> Result<()> {
+            self.seqs.lock().unwrap().push(batch.seq_no);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_rate_parsing() {
+        assert_eq!("original".parse::<ReplayRate>().unwrap(), ReplayRate::Original);
+        assert_eq!("max".parse::<ReplayRate>().unwrap(), ReplayRate::AsFastAsPossible);
+        assert_eq!("4x".parse::<ReplayRate>().unwrap(), ReplayRate::Multiplier(4.0));
+        assert!("0".parse::<ReplayRate>().is_err());
+        assert!("soon".parse::<ReplayRate>().is_err());
+    }
+
+    #[test]
+    fn test_replay_seq_range_paced() {
+        let dir = tempdir().unwrap();
+        let storage = StorageEngine::open(dir.path()).unwrap();
+
+        // 10ms apart in original time
+        for i in 0..10u64 {
+            storage.store_event(StoredEvent {
+                source_id: 7,
+                seq_no: i,
+                timestamp_ns: i * 10_000_000,
+                data: vec![i as u8],
+            }).unwrap();
+        }
+
+        let sink = CollectSink::default();
+        let stats = Replayer::new(&storage, 7)
+            .range(ReplayRange::Seq(2..=5))
+            .rate(ReplayRate::Multiplier(2.0))
+            .run(&sink)
+            .unwrap();
+
+        assert_eq!(*sink.seqs.lock().unwrap(), vec![2, 3, 4, 5]);
+        assert_eq!(stats.events_replayed, 4);
+        assert_eq!(stats.first_seq, Some(2));
+        assert_eq!(stats.last_seq, Some(5));
+        // 30ms of original time at 2x
+        assert!(stats.elapsed >= Duration::from_millis(15));
+    }
+}
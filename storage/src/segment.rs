@@ -0,0 +1,685 @@
+/// Columnar Segment Store
+/// Persists Arrow RecordBatches as per-source Arrow IPC segment files,
+/// indexed in sled by (source_id, first_seq_no)
+use anyhow::{anyhow, Result};
+use arrow_array::RecordBatch;
+use arrow_ipc::reader::{FileReader, StreamReader};
+use arrow_ipc::writer::{FileWriter, IpcWriteOptions, StreamWriter};
+use arrow_schema::SchemaRef;
+use serde::{Deserialize, Serialize};
+use sled::Tree;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::codec::Compression;
+use crate::CorruptRecord;
+
+/// Default number of rows buffered per source before a segment is written
+pub const DEFAULT_SEGMENT_MAX_ROWS: usize = 65_536;
+
+/// Encode a RecordBatch as a self-contained Arrow IPC stream
+pub fn encode_batch(batch: &RecordBatch) -> Result<Vec<u8>> {
+    let mut writer = StreamWriter::try_new(Vec::new(), &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()?;
+    Ok(writer.into_inner()?)
+}
+
+/// Decode the first RecordBatch of an Arrow IPC stream
+pub fn decode_batch(bytes: &[u8]) -> Result<RecordBatch> {
+    let mut reader = StreamReader::try_new(bytes, None)?;
+    reader
+        .next()
+        .ok_or_else(|| anyhow!("IPC stream contains no record batch"))?
+        .map_err(Into::into)
+}
+
+/// A RecordBatch together with its event header
+#[derive(Debug, Clone)]
+pub struct StoredBatch {
+    pub source_id: u32,
+    pub seq_no: u64,
+    pub timestamp_ns: u64,
+    pub batch: RecordBatch,
+}
+
+/// Per-batch entry inside a segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchMeta {
+    pub seq_no: u64,
+    pub timestamp_ns: u64,
+    pub num_rows: u64,
+}
+
+/// Index record describing one segment file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentMeta {
+    pub source_id: u32,
+    /// File name relative to the segment directory
+    pub file_name: String,
+    pub first_seq: u64,
+    pub last_seq: u64,
+    pub min_timestamp_ns: u64,
+    pub max_timestamp_ns: u64,
+    pub num_rows: u64,
+    pub file_size_bytes: u64,
+    /// Batches in file order
+    pub batches: Vec<BatchMeta>,
+    /// CRC32 of the whole file (`None` for segments indexed before
+    /// checksums were recorded). Must stay the last field: index entries
+    /// are bincode, decoded by position.
+    pub crc32: Option<u32>,
+}
+
+/// Index record layout written before `crc32` was added
+#[derive(Deserialize)]
+struct SegmentMetaV1 {
+    source_id: u32,
+    file_name: String,
+    first_seq: u64,
+    last_seq: u64,
+    min_timestamp_ns: u64,
+    max_timestamp_ns: u64,
+    num_rows: u64,
+    file_size_bytes: u64,
+    batches: Vec<BatchMeta>,
+}
+
+impl SegmentMeta {
+    /// Decode an index entry of either layout
+    fn decode(value: &[u8]) -> Result<Self> {
+        // Old entries end where `crc32` would start, so decoding them as the
+        // current layout fails rather than misreading a field
+        if let Ok(meta) = bincode::deserialize(value) {
+            return Ok(meta);
+        }
+        let v1: SegmentMetaV1 = bincode::deserialize(value)?;
+        Ok(Self {
+            source_id: v1.source_id,
+            file_name: v1.file_name,
+            first_seq: v1.first_seq,
+            last_seq: v1.last_seq,
+            min_timestamp_ns: v1.min_timestamp_ns,
+            max_timestamp_ns: v1.max_timestamp_ns,
+            num_rows: v1.num_rows,
+            file_size_bytes: v1.file_size_bytes,
+            batches: v1.batches,
+            crc32: None,
+        })
+    }
+
+    /// Does this segment contain any seq_no in the range?
+    pub fn overlaps_seq(&self, range: &RangeInclusive<u64>) -> bool {
+        self.first_seq <= *range.end() && self.last_seq >= *range.start()
+    }
+
+    /// Does this segment contain any timestamp in the range?
+    pub fn overlaps_time(&self, range: &RangeInclusive<u64>) -> bool {
+        self.min_timestamp_ns <= *range.end() && self.max_timestamp_ns >= *range.start()
+    }
+}
+
+/// Writer that checksums everything passing through it
+struct CrcWriter<W: Write> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+}
+
+impl<W: Write> Write for CrcWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Batches waiting to be written for one source
+struct PendingSegment {
+    schema: SchemaRef,
+    rows: usize,
+    batches: Vec<StoredBatch>,
+}
+
+/// Arrow IPC segment store
+pub struct SegmentStore {
+    dir: PathBuf,
+    index: Tree,
+    max_rows: usize,
+    compression: Compression,
+    pending: Mutex<HashMap<u32, PendingSegment>>,
+}
+
+impl SegmentStore {
+    /// Open a segment store writing files under `dir` and indexing them in `index`
+    pub fn open<P: AsRef<Path>>(dir: P, index: Tree, max_rows: usize, compression: Compression) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            dir,
+            index,
+            max_rows: max_rows.max(1),
+            compression,
+            pending: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Append a batch. It is buffered until the source's segment fills up,
+    /// its schema changes, or `flush` is called; reads include buffered
+    /// batches. A seq_no already in a segment is rejected. Buffered batches
+    /// stay buffered if writing them fails, and a failed append buffers
+    /// nothing, so it can be retried.
+    pub fn append(&self, source_id: u32, seq_no: u64, timestamp_ns: u64, batch: RecordBatch) -> Result<()> {
+        let mut pending = self.pending.lock().map_err(|_| anyhow!("segment buffer poisoned"))?;
+
+        if let Some(segment) = self.segment_containing(source_id, seq_no)? {
+            return Err(anyhow!(
+                "seq_no {} of source {} is already stored in segment {}",
+                seq_no, source_id, segment.file_name
+            ));
+        }
+
+        // A segment file holds a single schema; seal the current one on change
+        if let Some(segment) = pending.get(&source_id) {
+            if segment.schema != batch.schema() {
+                self.write_segment(source_id, &segment.batches)?;
+                pending.remove(&source_id);
+            }
+        }
+
+        let segment = pending.entry(source_id).or_insert_with(|| PendingSegment {
+            schema: batch.schema(),
+            rows: 0,
+            batches: Vec::new(),
+        });
+        let rows = batch.num_rows();
+        segment.rows += rows;
+        segment.batches.push(StoredBatch { source_id, seq_no, timestamp_ns, batch });
+
+        if segment.rows >= self.max_rows {
+            if let Err(e) = self.write_segment(source_id, &segment.batches) {
+                segment.batches.pop();
+                segment.rows -= rows;
+                if segment.batches.is_empty() {
+                    pending.remove(&source_id);
+                }
+                return Err(e);
+            }
+            pending.remove(&source_id);
+        }
+
+        Ok(())
+    }
+
+    /// Write all buffered batches to segment files. Every source is tried;
+    /// sources that fail stay buffered and their errors are combined.
+    pub fn flush(&self) -> Result<usize> {
+        let mut pending = self.pending.lock().map_err(|_| anyhow!("segment buffer poisoned"))?;
+        let sources: Vec<u32> = pending.keys().copied().collect();
+        let mut written = 0;
+        let mut errors = Vec::new();
+
+        for source_id in sources {
+            match self.write_segment(source_id, &pending[&source_id].batches) {
+                Ok(_) => {
+                    pending.remove(&source_id);
+                    written += 1;
+                }
+                Err(e) => errors.push(format!("source {}: {}", source_id, e)),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(anyhow!(
+                "failed to flush {} of {} sources: {}",
+                errors.len(),
+                errors.len() + written,
+                errors.join("; ")
+            ));
+        }
+
+        Ok(written)
+    }
+
+    /// The indexed segment of a source whose seq_no range contains `seq_no`
+    fn segment_containing(&self, source_id: u32, seq_no: u64) -> Result<Option<SegmentMeta>> {
+        let start = crate::StorageEngine::make_key(source_id, 0);
+        let end = crate::StorageEngine::make_key(source_id, seq_no);
+
+        // Only the last segment starting at or before seq_no can contain it
+        match self.index.range(start..=end).next_back() {
+            Some(item) => {
+                let (_key, value) = item?;
+                let segment = SegmentMeta::decode(&value)?;
+                Ok(segment.overlaps_seq(&(seq_no..=seq_no)).then_some(segment))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// List segments for a source, ordered by first seq_no
+    pub fn segments(&self, source_id: u32) -> Result<Vec<SegmentMeta>> {
+        let mut segments = Vec::new();
+
+        for item in self.index.scan_prefix(source_id.to_be_bytes()) {
+            let (_key, value) = item?;
+            segments.push(SegmentMeta::decode(&value)?);
+        }
+
+        Ok(segments)
+    }
+
+    /// List segments for all sources
+    pub fn all_segments(&self) -> Result<Vec<SegmentMeta>> {
+        let mut segments = Vec::new();
+
+        for item in self.index.iter() {
+            let (_key, value) = item?;
+            segments.push(SegmentMeta::decode(&value)?);
+        }
+
+        Ok(segments)
+    }
+
+    /// Read batches of a source whose seq_no falls in `range`
+    pub fn read_seq_range(&self, source_id: u32, range: RangeInclusive<u64>) -> Result<Vec<StoredBatch>> {
+        self.read_filtered(
+            source_id,
+            |segment| segment.overlaps_seq(&range),
+            |meta| range.contains(&meta.seq_no),
+        )
+    }
+
+    /// Read batches of a source whose timestamp falls in `range`
+    pub fn read_time_range(&self, source_id: u32, range: RangeInclusive<u64>) -> Result<Vec<StoredBatch>> {
+        self.read_filtered(
+            source_id,
+            |segment| segment.overlaps_time(&range),
+            |meta| range.contains(&meta.timestamp_ns),
+        )
+    }
+
+    /// Read every batch of a source
+    pub fn read_source(&self, source_id: u32) -> Result<Vec<StoredBatch>> {
+        self.read_seq_range(source_id, 0..=u64::MAX)
+    }
+
+    /// Delete all segment files and index entries
+    pub fn clear(&self) -> Result<()> {
+        self.pending.lock().map_err(|_| anyhow!("segment buffer poisoned"))?.clear();
+
+        for segment in self.all_segments()? {
+            let path = self.dir.join(&segment.file_name);
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        self.index.clear()?;
+
+        Ok(())
+    }
+
+    /// Check files of the given segments against their index entries
+    pub fn verify(&self, segments: &[SegmentMeta]) -> Result<Vec<CorruptRecord>> {
+        let mut corrupt = Vec::new();
+
+        for segment in segments {
+            if let Err(e) = self.check_file(segment) {
+                corrupt.push(CorruptRecord {
+                    location: format!("segments/{}", segment.file_name),
+                    source_id: segment.source_id,
+                    seq_no: segment.first_seq,
+                    reason: e.to_string(),
+                });
+            }
+        }
+
+        Ok(corrupt)
+    }
+
+    /// Compare a segment file's size and CRC32 with its index entry
+    fn check_file(&self, segment: &SegmentMeta) -> Result<()> {
+        let (size, crc) = self.checksum_file(&segment.file_name)?;
+        if size != segment.file_size_bytes {
+            return Err(anyhow!("size {} does not match index {}", size, segment.file_size_bytes));
+        }
+        match segment.crc32 {
+            Some(stored) if stored != crc => {
+                Err(anyhow!("checksum mismatch: stored {:#010x}, computed {:#010x}", stored, crc))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn checksum_file(&self, file_name: &str) -> Result<(u64, u32)> {
+        let mut file = BufReader::new(File::open(self.dir.join(file_name))?);
+        let mut hasher = crc32fast::Hasher::new();
+        let mut buf = [0u8; 64 * 1024];
+        let mut size = 0u64;
+
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            size += n as u64;
+        }
+
+        Ok((size, hasher.finalize()))
+    }
+
+    fn read_filtered<S, B>(&self, source_id: u32, segment_filter: S, batch_filter: B) -> Result<Vec<StoredBatch>>
+    where
+        S: Fn(&SegmentMeta) -> bool,
+        B: Fn(&BatchMeta) -> bool,
+    {
+        let mut batches = Vec::new();
+
+        // Batches not yet written to a segment file
+        let pending = self.pending.lock().map_err(|_| anyhow!("segment buffer poisoned"))?;
+        if let Some(segment) = pending.get(&source_id) {
+            batches.extend(segment.batches.iter().filter(|b| batch_filter(&BatchMeta {
+                seq_no: b.seq_no,
+                timestamp_ns: b.timestamp_ns,
+                num_rows: b.batch.num_rows() as u64,
+            })).cloned());
+        }
+        drop(pending);
+
+        for segment in self.segments(source_id)?.into_iter().filter(|s| segment_filter(s)) {
+            self.check_file(&segment)
+                .map_err(|e| anyhow!("segment {} is corrupt: {}", segment.file_name, e))?;
+            let file = File::open(self.dir.join(&segment.file_name))?;
+            let mut reader = FileReader::try_new(BufReader::new(file), None)?;
+
+            for (index, meta) in segment.batches.iter().enumerate() {
+                if !batch_filter(meta) {
+                    continue;
+                }
+
+                reader.set_index(index)?;
+                let batch = reader
+                    .next()
+                    .ok_or_else(|| anyhow!("segment {} is missing batch {}", segment.file_name, index))??;
+
+                batches.push(StoredBatch {
+                    source_id,
+                    seq_no: meta.seq_no,
+                    timestamp_ns: meta.timestamp_ns,
+                    batch,
+                });
+            }
+        }
+
+        batches.sort_by_key(|b| b.seq_no);
+        Ok(batches)
+    }
+
+    fn write_segment(&self, source_id: u32, batches: &[StoredBatch]) -> Result<Option<SegmentMeta>> {
+        let Some(first) = batches.first() else {
+            return Ok(None);
+        };
+
+        let first_seq = batches.iter().map(|b| b.seq_no).min().unwrap_or(first.seq_no);
+        let file_name = format!("{:010}-{:020}.arrow", source_id, first_seq);
+        let path = self.dir.join(&file_name);
+
+        // Never replace an existing segment (and its index entry)
+        let file = File::options().write(true).create_new(true).open(&path).map_err(|e| {
+            if e.kind() == io::ErrorKind::AlreadyExists {
+                anyhow!("segment {} already exists: seq_no {} was stored before", file_name, first_seq)
+            } else {
+                e.into()
+            }
+        })?;
+        let crc32 = match self.write_file(file, batches) {
+            Ok(crc32) => crc32,
+            Err(e) => {
+                // Leave no partial file behind so the batches can be written again
+                let _ = fs::remove_file(&path);
+                return Err(e);
+            }
+        };
+
+        let meta = SegmentMeta {
+            source_id,
+            file_name,
+            first_seq,
+            last_seq: batches.iter().map(|b| b.seq_no).max().unwrap_or(first_seq),
+            min_timestamp_ns: batches.iter().map(|b| b.timestamp_ns).min().unwrap_or(0),
+            max_timestamp_ns: batches.iter().map(|b| b.timestamp_ns).max().unwrap_or(0),
+            num_rows: batches.iter().map(|b| b.batch.num_rows() as u64).sum(),
+            file_size_bytes: fs::metadata(&path)?.len(),
+            batches: batches
+                .iter()
+                .map(|b| BatchMeta {
+                    seq_no: b.seq_no,
+                    timestamp_ns: b.timestamp_ns,
+                    num_rows: b.batch.num_rows() as u64,
+                })
+                .collect(),
+            crc32: Some(crc32),
+        };
+
+        self.index.insert(
+            crate::StorageEngine::make_key(source_id, first_seq),
+            bincode::serialize(&meta)?,
+        )?;
+
+        Ok(Some(meta))
+    }
+
+    /// Write batches as an Arrow IPC file, returning its CRC32
+    fn write_file(&self, file: File, batches: &[StoredBatch]) -> Result<u32> {
+        let out = CrcWriter {
+            inner: BufWriter::new(file),
+            hasher: crc32fast::Hasher::new(),
+        };
+        let options = IpcWriteOptions::default().try_with_compression(self.compression.ipc())?;
+        let mut writer = FileWriter::try_new_with_options(out, &batches[0].batch.schema(), options)?;
+        for stored in batches {
+            writer.write(&stored.batch)?;
+        }
+        writer.finish()?;
+        let out = writer.into_inner()?;
+        let crc32 = out.hasher.finalize();
+        out.inner.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        Ok(crc32)
+    }
+}
+
+impl Drop for SegmentStore {
+    fn drop(&mut self) {
+        // Best effort: don't lose buffered batches on a clean shutdown
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Float64Array, Int64Array};
+    use arrow_schema::{DataType, Field, Schema};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    fn tick_batch(start: i64, rows: usize) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("ts", DataType::Int64, false),
+            Field::new("price", DataType::Float64, false),
+        ]));
+        let ts: Vec<i64> = (start..start + rows as i64).collect();
+        let price: Vec<f64> = ts.iter().map(|t| *t as f64 * 0.5).collect();
+
+        RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Int64Array::from(ts)), Arc::new(Float64Array::from(price))],
+        )
+        .unwrap()
+    }
+
+    fn open_store(dir: &Path, max_rows: usize) -> SegmentStore {
+        let db = sled::open(dir.join("db")).unwrap();
+        SegmentStore::open(
+            dir.join("segments"),
+            db.open_tree("segments").unwrap(),
+            max_rows,
+            Compression::Zstd(1),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_segment_roll_and_range_read() {
+        let dir = tempdir().unwrap();
+        let store = open_store(dir.path(), 20);
+
+        // 10 batches of 5 rows -> segments roll every 4 batches
+        for seq in 0..10u64 {
+            store.append(1, seq, seq * 1000, tick_batch(seq as i64 * 5, 5)).unwrap();
+        }
+        store.flush().unwrap();
+
+        let segments = store.segments(1).unwrap();
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0].first_seq, 0);
+        assert_eq!(segments[0].last_seq, 3);
+        assert_eq!(segments.iter().map(|s| s.num_rows).sum::<u64>(), 50);
+
+        let batches = store.read_seq_range(1, 3..=6).unwrap();
+        let seqs: Vec<u64> = batches.iter().map(|b| b.seq_no).collect();
+        assert_eq!(seqs, vec![3, 4, 5, 6]);
+
+        // Columns come back intact
+        let ts = batches[0].batch.column(0).as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(ts.value(0), 15);
+
+        let by_time = store.read_time_range(1, 7000..=9000).unwrap();
+        assert_eq!(by_time.len(), 3);
+
+        assert!(store.verify(&segments).unwrap().is_empty());
+
+        // Truncated file is reported
+        let path = dir.path().join("segments").join(&segments[1].file_name);
+        let len = fs::metadata(&path).unwrap().len();
+        fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 8).unwrap();
+        let corrupt = store.verify(&segments).unwrap();
+        assert_eq!(corrupt.len(), 1);
+        assert_eq!(corrupt[0].seq_no, 4);
+    }
+
+    #[test]
+    fn test_ipc_bytes_roundtrip() {
+        let batch = tick_batch(10, 3);
+        let bytes = encode_batch(&batch).unwrap();
+        assert_eq!(decode_batch(&bytes).unwrap(), batch);
+        assert!(decode_batch(&[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_schema_change_seals_segment() {
+        let dir = tempdir().unwrap();
+        let store = open_store(dir.path(), 1000);
+
+        store.append(2, 0, 0, tick_batch(0, 4)).unwrap();
+
+        let other = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)])),
+            vec![Arc::new(Int64Array::from(vec![1, 2, 3]))],
+        )
+        .unwrap();
+        store.append(2, 1, 10, other).unwrap();
+        store.flush().unwrap();
+
+        assert_eq!(store.segments(2).unwrap().len(), 2);
+        assert_eq!(store.read_source(2).unwrap().len(), 2);
+        assert!(store.segments(1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pending_batches_readable_and_segments_not_overwritten() {
+        let dir = tempdir().unwrap();
+        let store = open_store(dir.path(), 1000);
+
+        store.append(3, 0, 0, tick_batch(0, 4)).unwrap();
+        store.append(3, 1, 10, tick_batch(4, 4)).unwrap();
+        assert!(store.segments(3).unwrap().is_empty());
+        assert_eq!(store.read_seq_range(3, 1..=1).unwrap().len(), 1);
+        assert_eq!(store.read_source(3).unwrap().len(), 2);
+
+        store.flush().unwrap();
+        assert_eq!(store.read_source(3).unwrap().len(), 2);
+
+        // Storing a stored seq_no again is rejected without buffering it
+        assert!(store.append(3, 0, 0, tick_batch(100, 4)).is_err());
+        assert!(store.append(3, 1, 10, tick_batch(100, 4)).is_err());
+        assert_eq!(store.flush().unwrap(), 0);
+        assert_eq!(store.segments(3).unwrap().len(), 1);
+        assert_eq!(store.read_seq_range(3, 0..=1).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_failed_write_leaves_buffer_retryable_and_flush_tries_all_sources() {
+        let dir = tempdir().unwrap();
+        let store = open_store(dir.path(), 8);
+
+        store.append(5, 0, 0, tick_batch(0, 4)).unwrap();
+        store.append(6, 0, 0, tick_batch(0, 4)).unwrap();
+
+        // Block the segment file source 5 will write
+        let blocked = dir.path().join("segments").join(format!("{:010}-{:020}.arrow", 5, 0));
+        fs::write(&blocked, b"").unwrap();
+
+        // The filling batch fails to write and is not buffered
+        assert!(store.append(5, 1, 10, tick_batch(4, 4)).is_err());
+        assert_eq!(store.read_source(5).unwrap().len(), 1);
+
+        // Source 6 is still written although source 5 fails
+        let err = store.flush().unwrap_err();
+        assert!(err.to_string().contains("1 of 2 sources"));
+        assert_eq!(store.segments(6).unwrap().len(), 1);
+
+        fs::remove_file(&blocked).unwrap();
+        store.append(5, 1, 10, tick_batch(4, 4)).unwrap();
+        assert_eq!(store.segments(5).unwrap()[0].last_seq, 1);
+        assert_eq!(store.read_source(5).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_crc_checked_on_read_and_v1_index_entries() {
+        let dir = tempdir().unwrap();
+        let store = open_store(dir.path(), 1000);
+        store.append(4, 0, 0, tick_batch(0, 4)).unwrap();
+        store.flush().unwrap();
+
+        // Index entries written before `crc32` existed still decode
+        let mut meta = store.segments(4).unwrap().remove(0);
+        let key = crate::StorageEngine::make_key(4, 0);
+        let mut v1 = bincode::serialize(&meta).unwrap();
+        v1.truncate(v1.len() - bincode::serialized_size(&meta.crc32).unwrap() as usize);
+        store.index.insert(key, v1).unwrap();
+        assert_eq!(store.segments(4).unwrap()[0].crc32, None);
+        assert_eq!(store.read_source(4).unwrap().len(), 1);
+
+        // A flipped byte of the same size fails reads, not just `verify`
+        store.index.insert(key, bincode::serialize(&meta).unwrap()).unwrap();
+        let path = dir.path().join("segments").join(&meta.file_name);
+        let mut bytes = fs::read(&path).unwrap();
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 0x01;
+        fs::write(&path, bytes).unwrap();
+        let err = store.read_seq_range(4, 0..=0).unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"));
+        meta.crc32 = None;
+        assert_eq!(store.verify(&store.segments(4).unwrap()).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,75 @@
+/// Storage Statistics
+/// Per-source and whole-store summaries
+use serde::{Deserialize, Serialize};
+
+/// Compression statistics for the `events` tree
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressionStats {
+    pub records: u64,
+    /// Serialized size before compression
+    pub raw_bytes: u64,
+    /// Size on disk including record headers
+    pub stored_bytes: u64,
+}
+
+impl CompressionStats {
+    /// raw / stored (1.0 when empty)
+    pub fn ratio(&self) -> f64 {
+        if self.stored_bytes == 0 {
+            1.0
+        } else {
+            self.raw_bytes as f64 / self.stored_bytes as f64
+        }
+    }
+}
+
+/// Statistics for one source; seq/timestamp ranges cover both row events
+/// and columnar segments
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceStats {
+    pub source_id: u32,
+    /// Row events
+    pub events: u64,
+    /// Row event bytes before compression
+    pub raw_bytes: u64,
+    /// Row event bytes on disk
+    pub stored_bytes: u64,
+    /// Columnar segment files
+    pub segments: u64,
+    pub segment_rows: u64,
+    pub segment_bytes: u64,
+    pub min_seq: Option<u64>,
+    pub max_seq: Option<u64>,
+    pub min_timestamp_ns: Option<u64>,
+    pub max_timestamp_ns: Option<u64>,
+}
+
+impl SourceStats {
+    pub(crate) fn new(source_id: u32) -> Self {
+        Self {
+            source_id,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn observe(&mut self, seq_no: u64, timestamp_ns: u64) {
+        self.min_seq = Some(self.min_seq.map_or(seq_no, |v| v.min(seq_no)));
+        self.max_seq = Some(self.max_seq.map_or(seq_no, |v| v.max(seq_no)));
+        self.min_timestamp_ns = Some(self.min_timestamp_ns.map_or(timestamp_ns, |v| v.min(timestamp_ns)));
+        self.max_timestamp_ns = Some(self.max_timestamp_ns.map_or(timestamp_ns, |v| v.max(timestamp_ns)));
+    }
+}
+
+/// Whole-store statistics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageStats {
+    /// Sources ordered by id
+    pub sources: Vec<SourceStats>,
+    pub total_events: u64,
+    pub total_segments: u64,
+    pub compression: CompressionStats,
+    /// Records that failed to decode (see `verify()` for details)
+    pub corrupt_records: u64,
+    /// sled database plus segment files
+    pub disk_size_bytes: u64,
+}
@@ -5,6 +5,9 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use tracing::{info, warn};
 
+/// Bandwidth of one NVLink in GB/s (both directions; the same for NVLink 2-4)
+const NVLINK_GBPS_PER_LINK: u32 = 50;
+
 /// GPU device information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GpuDevice {
@@ -98,7 +101,7 @@ impl GpuTopology {
     }
     
     /// Create empty topology
-    fn empty() -> Self {
+    pub fn empty() -> Self {
         Self {
             devices: vec![],
             nvlink_connections: vec![],
@@ -156,6 +159,56 @@ impl GpuTopology {
         }
     }
     
+    /// Read NVLink connections and NUMA affinity from the output of
+    /// `nvidia-smi topo -m`. Devices are left empty.
+    pub fn from_topo_matrix(matrix: &str) -> Self {
+        let mut topology = Self::empty();
+        let mut lines = matrix.lines().map(|line| line.replace("\x1b[4m", "").replace("\x1b[0m", ""));
+        
+        let Some(header) = lines.next() else {
+            return topology;
+        };
+        // The header starts with an empty cell, so columns line up with row cells
+        let header: Vec<&str> = header.split('\t').map(str::trim).collect();
+        let numa_column = header.iter().position(|c| *c == "NUMA Affinity");
+        
+        for line in lines {
+            let cells: Vec<&str> = line.split('\t').map(str::trim).collect();
+            let Some(source) = cells.first().and_then(|c| gpu_index(c)) else {
+                // Rows end where the legend starts
+                if cells.first().is_some_and(|c| !c.is_empty()) {
+                    break;
+                }
+                continue;
+            };
+            
+            for (column, cell) in cells.iter().enumerate().skip(1) {
+                let target = header.get(column).and_then(|c| gpu_index(c));
+                let links = cell.strip_prefix("NV").and_then(|n| n.parse::<u32>().ok());
+                if let (Some(target), Some(link_count)) = (target, links) {
+                    // The matrix is symmetric: keep each pair once
+                    if source < target {
+                        topology.nvlink_connections.push(NvLinkConnection {
+                            source,
+                            target,
+                            link_count,
+                            bandwidth_gbps: link_count * NVLINK_GBPS_PER_LINK,
+                        });
+                    }
+                }
+            }
+            
+            let numa = numa_column
+                .and_then(|column| cells.get(column))
+                .and_then(|cell| cell.split(['-', ',']).next()?.parse().ok());
+            if let Some(numa) = numa {
+                topology.numa_affinity.insert(source, numa);
+            }
+        }
+        
+        topology
+    }
+    
     /// Get number of GPUs
     pub fn gpu_count(&self) -> usize {
         self.devices.len()
@@ -188,6 +241,11 @@ impl GpuTopology {
     }
 }
 
+/// Index of a `GPU<n>` label in `nvidia-smi topo -m`
+fn gpu_index(label: &str) -> Option<u32> {
+    label.strip_prefix("GPU")?.parse().ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -197,4 +255,24 @@ mod tests {
         let topo = GpuTopology::empty();
         assert_eq!(topo.gpu_count(), 0);
     }
+    
+    #[test]
+    fn test_topo_matrix() {
+        let matrix = "\tGPU0\tGPU1\tGPU2\tmlx5_0\tCPU Affinity\tNUMA Affinity\tGPU NUMA ID\n\
+            GPU0\t X \tNV12\tSYS\tPXB\t0-31\t0\t\tN/A\n\
+            GPU1\tNV12\t X \tSYS\tPXB\t0-31\t0\t\tN/A\n\
+            GPU2\tSYS\tSYS\t X \tSYS\t32-63\t1\t\tN/A\n\
+            mlx5_0\tPXB\tPXB\tSYS\t X \n\
+            \n\
+            Legend:\n\
+            \n\
+              X    = Self\n";
+        
+        let topo = GpuTopology::from_topo_matrix(matrix);
+        assert_eq!(topo.nvlink_connections.len(), 1);
+        assert!(topo.has_nvlink(0, 1));
+        assert!(!topo.has_nvlink(0, 2));
+        assert_eq!(topo.nvlink_connections[0].bandwidth_gbps, 600);
+        assert_eq!(topo.numa_affinity, HashMap::from([(0, 0), (1, 0), (2, 1)]));
+    }
 }
@@ -66,7 +66,7 @@ pub mod nvml;
 
 // Re-exports
 pub use config::GpuRuntimeConfig;
-pub use device::{GpuDevice, GpuTopology};
+pub use device::{GpuDevice, GpuTopology, NvLinkConnection};
 pub use nvml::NvmlManager;
 
 /// Crate version
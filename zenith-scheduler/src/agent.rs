@@ -1,24 +1,35 @@
 //! Node Agent - Runs on compute nodes
 
-use crate::node::{Node, NodeTopology, GpuDevice, NodeHealth};
+use crate::api::rest::{HeartbeatRequest, HeartbeatResponse, RegisterNodeRequest, RegisterNodeResponse};
+use crate::node::{Node, NodeTopology, GpuDevice, GpuStatus, NodeHealth, NvLinkConnection};
 use crate::{Error, Result};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::Notify;
 use tokio::time::interval;
 use tracing::{info, warn, error, debug};
+use zenith_runtime_gpu::GpuTopology;
 
 /// Node agent configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeAgentConfig {
     /// Node ID (usually hostname)
     pub node_id: String,
-    /// Scheduler address
+    /// Scheduler HTTP API address
     pub scheduler_addr: String,
     /// Heartbeat interval in seconds
     pub heartbeat_interval_secs: u64,
     /// GPU monitoring interval in seconds
     pub gpu_monitor_interval_secs: u64,
+    /// Jobs are started on this node through `job_started` and
+    /// `job_finished`, so the jobs reported on registration are complete.
+    /// Otherwise none are reported and the scheduler keeps the jobs it
+    /// placed here.
+    #[serde(default)]
+    pub tracks_jobs: bool,
 }
 
 impl Default for NodeAgentConfig {
@@ -29,9 +40,10 @@ impl Default for NodeAgentConfig {
         
         Self {
             node_id: hostname,
-            scheduler_addr: "http://localhost:50051".to_string(),
+            scheduler_addr: "http://localhost:8080".to_string(),
             heartbeat_interval_secs: 30,
             gpu_monitor_interval_secs: 10,
+            tracks_jobs: false,
         }
     }
 }
@@ -40,7 +52,22 @@ impl Default for NodeAgentConfig {
 pub struct NodeAgent {
     config: NodeAgentConfig,
     node: Node,
-    running: bool,
+    client: reqwest::Client,
+    shutdown: Arc<Notify>,
+    /// Jobs stopped at the scheduler's request, reported with the next
+    /// heartbeat
+    stopped: Vec<String>,
+}
+
+/// Stops a running agent from another task
+#[derive(Clone)]
+pub struct AgentStopHandle(Arc<Notify>);
+
+impl AgentStopHandle {
+    /// Make `NodeAgent::start` deregister and return
+    pub fn stop(&self) {
+        self.0.notify_one();
+    }
 }
 
 impl NodeAgent {
@@ -57,14 +84,24 @@ impl NodeAgent {
         Ok(Self {
             config,
             node,
-            running: false,
+            client: reqwest::Client::new(),
+            shutdown: Arc::new(Notify::new()),
+            stopped: vec![],
         })
     }
     
     /// Discover local GPU topology
     fn discover_topology() -> Result<NodeTopology> {
         // Try to discover real GPUs via nvidia-smi
-        let gpus = Self::discover_gpus();
+        let mut gpus = Self::discover_gpus();
+        let gpu_topology = Self::discover_gpu_topology();
+        for gpu in &mut gpus {
+            let index = gpu.device_id.strip_prefix("cuda:").and_then(|i| i.parse::<u32>().ok());
+            gpu.numa_node = index.and_then(|i| gpu_topology.numa_affinity.get(&i).copied());
+        }
+        let nvlink_connections: Vec<NvLinkConnection> = gpu_topology.nvlink_connections.iter()
+            .map(NvLinkConnection::from)
+            .collect();
         
         let cpu_cores = num_cpus::get() as u32;
         let sys = sysinfo::System::new_all();
@@ -80,9 +117,12 @@ impl NodeAgent {
             cpu_memory,
             cpu_memory_free,
             numa_nodes,
-            nvlink_present: false,  // Would need nvml to detect
+            nvlink_present: !nvlink_connections.is_empty(),
+            nvlink_version: 0,  // Would need nvml to detect
             nvswitch_present: false,
             rdma_capable: Self::detect_rdma(),
+            nvlink_connections,
+            numa_cpu_cores: Self::detect_numa_cpu_cores(),
         })
     }
     
@@ -106,6 +146,19 @@ impl NodeAgent {
         }
     }
     
+    /// Discover NVLink connections and GPU NUMA affinity via `nvidia-smi topo -m`
+    fn discover_gpu_topology() -> GpuTopology {
+        match std::process::Command::new("nvidia-smi").args(["topo", "-m"]).output() {
+            Ok(output) if output.status.success() => {
+                GpuTopology::from_topo_matrix(&String::from_utf8_lossy(&output.stdout))
+            }
+            _ => {
+                debug!("nvidia-smi topo not available, assuming no NVLink");
+                GpuTopology::empty()
+            }
+        }
+    }
+    
     /// Parse nvidia-smi output line
     fn parse_gpu_line(line: &str) -> Option<GpuDevice> {
         let parts: Vec<&str> = line.split(',').map(|s| s.trim()).collect();
@@ -120,6 +173,7 @@ impl NodeAgent {
                 temperature: parts[6].parse::<i32>().unwrap_or(0),
                 allocated: false,
                 allocated_job_id: None,
+                numa_node: None,
             })
         } else {
             None
@@ -151,15 +205,42 @@ impl NodeAgent {
         }
     }
     
+    /// CPU core IDs per NUMA node, from `/sys/devices/system/node/node*/cpulist`
+    fn detect_numa_cpu_cores() -> HashMap<u32, Vec<u32>> {
+        let Ok(entries) = std::fs::read_dir("/sys/devices/system/node") else {
+            return HashMap::new();
+        };
+        entries
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let numa = e.file_name().to_string_lossy().strip_prefix("node")?.parse().ok()?;
+                let cpulist = std::fs::read_to_string(e.path().join("cpulist")).ok()?;
+                Some((numa, Self::parse_cpu_list(&cpulist)))
+            })
+            .collect()
+    }
+    
+    /// Parse a kernel CPU list such as "0-3,8-11,16"
+    fn parse_cpu_list(list: &str) -> Vec<u32> {
+        list.trim()
+            .split(',')
+            .filter_map(|range| match range.split_once('-') {
+                Some((first, last)) => Some(first.parse().ok()?..=last.parse().ok()?),
+                None => range.parse().ok().map(|core| core..=core),
+            })
+            .flatten()
+            .collect()
+    }
+    
     /// Detect RDMA capability
     fn detect_rdma() -> bool {
         std::path::Path::new("/sys/class/infiniband").exists()
     }
     
-    /// Start the node agent
+    /// Start the node agent: register, send heartbeats until stopped, then
+    /// deregister
     pub async fn start(&mut self) -> Result<()> {
         info!("Starting node agent: {}", self.config.node_id);
-        self.running = true;
         
         // Register with scheduler
         self.register().await?;
@@ -169,8 +250,11 @@ impl NodeAgent {
             Duration::from_secs(self.config.heartbeat_interval_secs)
         );
         
-        while self.running {
-            heartbeat_interval.tick().await;
+        loop {
+            tokio::select! {
+                _ = heartbeat_interval.tick() => {}
+                _ = self.shutdown.notified() => break,
+            }
             
             // Update topology
             if let Ok(topology) = Self::discover_topology() {
@@ -183,21 +267,46 @@ impl NodeAgent {
             }
         }
         
-        Ok(())
+        self.deregister().await
     }
     
     /// Stop the node agent
-    pub fn stop(&mut self) {
+    pub fn stop(&self) {
         info!("Stopping node agent");
-        self.running = false;
+        self.shutdown.notify_one();
+    }
+    
+    /// Handle for stopping the agent while `start` runs
+    pub fn stop_handle(&self) -> AgentStopHandle {
+        AgentStopHandle(self.shutdown.clone())
     }
     
     /// Register with scheduler
     async fn register(&self) -> Result<()> {
         info!("Registering with scheduler at {}", self.config.scheduler_addr);
         
-        // In production: gRPC call to scheduler
-        // For now, just log
+        let request = RegisterNodeRequest {
+            node_id: self.node.id.clone(),
+            hostname: self.node.hostname.clone(),
+            ip_address: self.node.ip_address.clone(),
+            topology: self.node.topology.clone(),
+            labels: self.node.labels.clone(),
+            running_jobs: self.config.tracks_jobs.then(|| self.node.running_jobs.clone()),
+        };
+        let response = self.client.post(self.url("/api/v1/nodes"))
+            .json(&request)
+            .send()
+            .await
+            .map_err(scheduler_error)?;
+        if !response.status().is_success() {
+            return Err(Error::Node(format!("Registration rejected: {}", response.status())));
+        }
+        let response: RegisterNodeResponse = response.json().await.map_err(scheduler_error)?;
+        
+        // No job runner on the agent yet, so orphans can only be reported
+        for job_id in &response.orphaned_jobs {
+            warn!("Scheduler does not expect job {} on this node", job_id);
+        }
         info!("Node registered: {} with {} GPUs", 
             self.node.id, 
             self.node.topology.gpus.len()
@@ -206,22 +315,102 @@ impl NodeAgent {
         Ok(())
     }
     
-    /// Send heartbeat to scheduler
-    async fn send_heartbeat(&self) -> Result<()> {
+    /// Send heartbeat to scheduler, registering again if it no longer
+    /// knows the node or has marked it unreachable, and stop the jobs the
+    /// scheduler suspended, timed out or cancelled
+    async fn send_heartbeat(&mut self) -> Result<()> {
         debug!("Sending heartbeat");
         
-        // In production: gRPC call to scheduler
-        // For now, just log
+        let request = HeartbeatRequest {
+            gpus: self.node.topology.gpus.iter()
+                .map(|gpu| GpuStatus {
+                    device_id: gpu.device_id.clone(),
+                    utilization: gpu.utilization,
+                    free_memory: gpu.free_memory,
+                    temperature: gpu.temperature,
+                })
+                .collect(),
+            cpu_memory_free: self.node.topology.cpu_memory_free,
+            stopped_jobs: self.stopped.clone(),
+        };
+        let response = self.client
+            .post(self.url(&format!("/api/v1/nodes/{}/heartbeat", self.node.id)))
+            .json(&request)
+            .send()
+            .await
+            .map_err(scheduler_error)?;
+        
+        match response.status() {
+            status if status.is_success() => {
+                let response: HeartbeatResponse = response.json().await.map_err(scheduler_error)?;
+                self.stopped.clear();
+                for job_id in response.stop_jobs {
+                    self.stop_job(job_id);
+                }
+                Ok(())
+            }
+            StatusCode::NOT_FOUND | StatusCode::CONFLICT => {
+                info!("Scheduler refused heartbeat ({}), registering again", response.status());
+                self.register().await
+            }
+            status => Err(Error::Node(format!("Heartbeat rejected: {}", status))),
+        }
+    }
+    
+    /// Record a job started on this node
+    pub fn job_started(&mut self, job_id: &str) {
+        if !self.node.running_jobs.iter().any(|id| id == job_id) {
+            self.node.running_jobs.push(job_id.to_string());
+        }
+    }
+    
+    /// Record a job on this node exiting
+    pub fn job_finished(&mut self, job_id: &str) {
+        self.node.running_jobs.retain(|id| id != job_id);
+    }
+    
+    /// Stop a job the scheduler suspended, timed out or cancelled and report
+    /// it with the next heartbeat. There is no job runner yet, so this only
+    /// drops the job from those reported as running.
+    fn stop_job(&mut self, job_id: String) {
+        self.job_finished(&job_id);
+        if !self.stopped.contains(&job_id) {
+            info!("Stopping job {} at the scheduler's request", job_id);
+            self.stopped.push(job_id);
+        }
+    }
+    
+    /// Deregister from scheduler
+    async fn deregister(&self) -> Result<()> {
+        let response = self.client
+            .delete(self.url(&format!("/api/v1/nodes/{}", self.node.id)))
+            .send()
+            .await
+            .map_err(scheduler_error)?;
+        if !response.status().is_success() {
+            return Err(Error::Node(format!("Deregistration rejected: {}", response.status())));
+        }
         
+        info!("Node deregistered: {}", self.node.id);
         Ok(())
     }
     
+    /// Scheduler API URL for a path
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.scheduler_addr.trim_end_matches('/'), path)
+    }
+    
     /// Get current node status
     pub fn status(&self) -> &Node {
         &self.node
     }
 }
 
+/// Wrap a failed request to the scheduler
+fn scheduler_error(e: reqwest::Error) -> Error {
+    Error::Node(format!("Scheduler request failed: {}", e))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -232,4 +421,10 @@ mod tests {
         assert!(!config.node_id.is_empty());
         assert_eq!(config.heartbeat_interval_secs, 30);
     }
+    
+    #[test]
+    fn test_parse_cpu_list() {
+        assert_eq!(NodeAgent::parse_cpu_list("0-3,8-9,16\n"), vec![0, 1, 2, 3, 8, 9, 16]);
+        assert!(NodeAgent::parse_cpu_list("").is_empty());
+    }
 }
@@ -4,7 +4,7 @@ use tonic::Status;
 use std::sync::Arc;
 use crate::scheduler::Scheduler;
 use crate::node::NodeRegistry;
-use crate::job::{Job, JobDescriptor, ResourceRequirements, LocalityPreferences, SchedulingPolicy};
+use crate::job::{Actor, ArraySpec, ArrayStatus, Job, JobDescriptor, ResourceRequirements, LocalityPreferences, SchedulingPolicy};
 use std::collections::HashMap;
 
 /// Job submission request
@@ -22,6 +22,8 @@ pub struct SubmitJobRequest {
     pub memory_mb: u64,
     pub priority: i32,
     pub gang_schedule: bool,
+    /// Submit as an array job with one child per index
+    pub array: Option<ArraySpec>,
 }
 
 /// Job submission response
@@ -44,6 +46,8 @@ pub struct GetJobStatusResponse {
     pub state: String,
     pub message: String,
     pub allocated_nodes: Vec<String>,
+    /// Child counts by state, for array jobs
+    pub array: Option<ArrayStatus>,
 }
 
 /// Cancel job request
@@ -110,6 +114,8 @@ impl SchedulerService {
             },
             labels: HashMap::new(),
             annotations: HashMap::new(),
+            dependencies: Default::default(),
+            array: request.array,
         };
         
         let job = Job::new(descriptor);
@@ -123,14 +129,25 @@ impl SchedulerService {
         }
     }
     
-    /// Get job status
+    /// Get job status (aggregated over the children for array jobs)
     pub fn get_job_status(&self, request: GetJobStatusRequest) -> Result<GetJobStatusResponse, Status> {
-        match self.scheduler.get_job(&request.job_id) {
-            Some(job) => Ok(GetJobStatusResponse {
+        if let Some(job) = self.scheduler.get_job(&request.job_id) {
+            return Ok(GetJobStatusResponse {
                 job_id: job.id.to_string(),
                 state: format!("{:?}", job.state),
                 message: job.message.clone(),
                 allocated_nodes: job.allocated_nodes,
+                array: None,
+            });
+        }
+        
+        match self.scheduler.get_array(&request.job_id) {
+            Some((array, status)) => Ok(GetJobStatusResponse {
+                job_id: array.id.to_string(),
+                state: format!("{:?}", status.state),
+                message: format!("Array job with {} children", status.total),
+                allocated_nodes: vec![],
+                array: Some(status),
             }),
             None => Err(Status::not_found(format!("Job not found: {}", request.job_id))),
         }
@@ -138,7 +155,7 @@ impl SchedulerService {
     
     /// Cancel a job
     pub fn cancel_job(&self, request: CancelJobRequest) -> Result<CancelJobResponse, Status> {
-        match self.scheduler.cancel(&request.job_id, &request.reason) {
+        match self.scheduler.cancel(&request.job_id, Actor::User, &request.reason) {
             Ok(()) => Ok(CancelJobResponse {
                 success: true,
                 message: "Job cancelled".to_string(),
@@ -181,8 +198,38 @@ mod tests {
             memory_mb: 16384,
             priority: 50,
             gang_schedule: true,
+            array: None,
         };
         
         assert_eq!(request.gpu_count, 4);
     }
+    
+    #[test]
+    fn test_array_job_status() {
+        use crate::scheduler::SchedulerConfig;
+        
+        let node_registry = Arc::new(NodeRegistry::new(60));
+        let scheduler = Arc::new(Scheduler::new(node_registry.clone(), SchedulerConfig::default()));
+        let service = SchedulerService::new(scheduler, node_registry);
+        
+        let response = service.submit_job(SubmitJobRequest {
+            name: "sweep".to_string(),
+            user_id: "user1".to_string(),
+            project_id: "project1".to_string(),
+            command: "python".to_string(),
+            arguments: vec![],
+            environment: HashMap::new(),
+            working_directory: "/app".to_string(),
+            gpu_count: 0,
+            cpu_cores: 1,
+            memory_mb: 1024,
+            priority: 50,
+            gang_schedule: false,
+            array: Some(ArraySpec { start: 0, end: 9, max_concurrent: 4 }),
+        }).unwrap();
+        
+        let status = service.get_job_status(GetJobStatusRequest { job_id: response.job_id }).unwrap();
+        assert_eq!(status.state, "Queued");
+        assert_eq!(status.array.unwrap().total, 10);
+    }
 }
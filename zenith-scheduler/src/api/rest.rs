@@ -11,13 +11,16 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::collections::HashMap;
 
+use crate::reservation::Reservation;
 use crate::scheduler::Scheduler;
-use crate::node::NodeRegistry;
-use crate::job::{Job, JobDescriptor, ResourceRequirements, LocalityPreferences, SchedulingPolicy};
+use crate::node::{GpuStatus, Node, NodeHealth, NodeRegistry, NodeSchedulingState, NodeTopology};
+use crate::job::{Actor, ArraySpec, ArrayStatus, Job, JobDependencies, JobDescriptor, JobEvent, WorkflowStep, ResourceRequirements, LocalityPreferences, SchedulingPolicy};
 
 /// Application state
 pub struct AppState {
+    /// Scheduler serving job requests
     pub scheduler: Arc<Scheduler>,
+    /// Registry of the cluster's nodes
     pub node_registry: Arc<NodeRegistry>,
 }
 
@@ -26,10 +29,22 @@ pub fn create_router(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/api/v1/jobs", post(submit_job))
         .route("/api/v1/jobs", get(list_jobs))
-        .route("/api/v1/jobs/:job_id", get(get_job))
-        .route("/api/v1/jobs/:job_id", delete(cancel_job))
+        .route("/api/v1/jobs/{job_id}", get(get_job))
+        .route("/api/v1/jobs/{job_id}", delete(cancel_job))
+        .route("/api/v1/jobs/{job_id}/events", get(job_events))
+        .route("/api/v1/workflows", post(submit_workflow))
         .route("/api/v1/cluster/status", get(cluster_status))
         .route("/api/v1/nodes", get(list_nodes))
+        .route("/api/v1/nodes", post(register_node))
+        .route("/api/v1/nodes/{node_id}", delete(deregister_node))
+        .route("/api/v1/nodes/{node_id}/heartbeat", post(node_heartbeat))
+        .route("/api/v1/nodes/{node_id}/cordon", post(cordon_node))
+        .route("/api/v1/nodes/{node_id}/drain", post(drain_node))
+        .route("/api/v1/nodes/{node_id}/uncordon", post(uncordon_node))
+        .route("/api/v1/queues", get(list_queues))
+        .route("/api/v1/reservations", post(create_reservation))
+        .route("/api/v1/reservations", get(list_reservations))
+        .route("/api/v1/reservations/{name}", delete(delete_reservation))
         .route("/health", get(health_check))
         .with_state(state)
 }
@@ -58,6 +73,38 @@ pub struct SubmitJobRequest {
     pub priority: i32,
     #[serde(default)]
     pub gang_schedule: bool,
+    /// Jobs that must complete successfully before this one starts
+    #[serde(default)]
+    pub after_ok: Vec<String>,
+    /// Jobs that must finish, in any end state, before this one starts
+    #[serde(default)]
+    pub after_any: Vec<String>,
+    /// Jobs that must fail before this one starts
+    #[serde(default)]
+    pub after_fail: Vec<String>,
+    /// Submit as an array job with one child per index
+    #[serde(default)]
+    pub array: Option<ArraySpec>,
+    /// Advance reservation to run the job in
+    #[serde(default)]
+    pub reservation: Option<String>,
+}
+
+/// A workflow step: a job request whose dependencies may name other steps
+#[derive(Debug, Deserialize)]
+pub struct WorkflowStepRequest {
+    /// Step name, unique within the workflow
+    pub step: String,
+    /// The step's job
+    #[serde(flatten)]
+    pub job: SubmitJobRequest,
+}
+
+/// A workflow submitted as a whole
+#[derive(Debug, Deserialize)]
+pub struct SubmitWorkflowRequest {
+    /// Steps of the workflow; they must not depend on each other in a cycle
+    pub steps: Vec<WorkflowStepRequest>,
 }
 
 fn default_working_dir() -> String { "/app".to_string() }
@@ -75,6 +122,25 @@ pub struct JobResponse {
     pub created_at: String,
     pub allocated_nodes: Vec<String>,
     pub gpu_count: u32,
+    /// Child counts by state, for array jobs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub array: Option<ArrayStatus>,
+}
+
+/// Jobs created for a submitted workflow
+#[derive(Debug, Serialize)]
+pub struct WorkflowResponse {
+    /// Step name -> job ID
+    pub jobs: HashMap<String, String>,
+}
+
+/// State transitions of a job
+#[derive(Debug, Serialize)]
+pub struct JobEventsResponse {
+    /// Job ID
+    pub job_id: String,
+    /// Transitions, oldest first
+    pub events: Vec<JobEvent>,
 }
 
 #[derive(Debug, Serialize)]
@@ -87,7 +153,7 @@ pub struct ClusterStatusResponse {
     pub queued_jobs: usize,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct NodeResponse {
     pub id: String,
     pub hostname: String,
@@ -95,6 +161,70 @@ pub struct NodeResponse {
     pub total_gpus: usize,
     pub available_gpus: usize,
     pub running_jobs: usize,
+    /// Whether the node takes new jobs, is cordoned or is draining
+    pub scheduling: NodeSchedulingState,
+}
+
+/// Sent by a node agent when it starts, or when the scheduler no longer
+/// accepts its heartbeats
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegisterNodeRequest {
+    /// Node ID
+    pub node_id: String,
+    /// Node hostname
+    pub hostname: String,
+    /// Address the scheduler reaches the agent on
+    pub ip_address: String,
+    /// GPUs, interconnect and CPU of the node
+    pub topology: NodeTopology,
+    /// Labels for node selection
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// Jobs the agent is still running; unset if it does not track them,
+    /// in which case the scheduler keeps the jobs it placed on the node
+    #[serde(default)]
+    pub running_jobs: Option<Vec<String>>,
+}
+
+/// Answer to a node registration
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegisterNodeResponse {
+    /// ID the node was registered under
+    pub node_id: String,
+    /// Reported jobs the scheduler does not expect on the node; the agent
+    /// should stop them
+    pub orphaned_jobs: Vec<String>,
+}
+
+/// Periodic status report of a node agent
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HeartbeatRequest {
+    /// Current state of each GPU
+    pub gpus: Vec<GpuStatus>,
+    /// Free CPU memory in bytes
+    pub cpu_memory_free: u64,
+    /// Jobs stopped as asked by an earlier response's `stop_jobs`
+    #[serde(default)]
+    pub stopped_jobs: Vec<String>,
+}
+
+/// Answer to a heartbeat
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HeartbeatResponse {
+    /// The node as the scheduler sees it
+    #[serde(flatten)]
+    pub node: NodeResponse,
+    /// Suspended, timed-out or cancelled jobs the agent must stop and
+    /// report in `stopped_jobs`; their resources stay held until then
+    pub stop_jobs: Vec<String>,
+}
+
+/// Request to drain a node
+#[derive(Debug, Deserialize)]
+pub struct DrainNodeRequest {
+    /// Seconds to wait for running jobs before requeueing them (none = wait)
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -115,36 +245,14 @@ async fn submit_job(
     State(state): State<Arc<AppState>>,
     Json(request): Json<SubmitJobRequest>,
 ) -> impl IntoResponse {
-    let descriptor = JobDescriptor {
-        name: request.name,
-        user_id: request.user_id,
-        project_id: request.project_id,
-        command: request.command,
-        arguments: request.arguments,
-        environment: request.environment,
-        working_directory: request.working_directory,
-        resources: ResourceRequirements {
-            gpu_count: request.gpu_count,
-            cpu_cores: request.cpu_cores,
-            cpu_memory: request.memory_mb * 1024 * 1024, // Convert MB to bytes
-            ..Default::default()
-        },
-        locality: LocalityPreferences::default(),
-        policy: SchedulingPolicy {
-            priority: request.priority,
-            gang_schedule: request.gang_schedule,
-            ..Default::default()
-        },
-        labels: HashMap::new(),
-        annotations: HashMap::new(),
-    };
+    let descriptor = descriptor_from_request(request);
     
     let job = Job::new(descriptor);
     
     match state.scheduler.submit(job) {
         Ok(job_id) => {
-            if let Some(job) = state.scheduler.get_job(&job_id) {
-                (StatusCode::CREATED, Json(job_to_response(&job)))
+            if let Some(response) = lookup_job(&state, &job_id) {
+                (StatusCode::CREATED, Json(response))
             } else {
                 (StatusCode::INTERNAL_SERVER_ERROR, Json(JobResponse {
                     job_id,
@@ -155,11 +263,12 @@ async fn submit_job(
                     created_at: chrono::Utc::now().to_rfc3339(),
                     allocated_nodes: vec![],
                     gpu_count: 0,
+                    array: None,
                 }))
             }
         }
         Err(e) => {
-            (StatusCode::BAD_REQUEST, Json(JobResponse {
+            (error_status(&e, StatusCode::BAD_REQUEST), Json(JobResponse {
                 job_id: "".to_string(),
                 name: "error".to_string(),
                 state: e.to_string(),
@@ -168,17 +277,38 @@ async fn submit_job(
                 created_at: "".to_string(),
                 allocated_nodes: vec![],
                 gpu_count: 0,
+                array: None,
             }))
         }
     }
 }
 
+async fn submit_workflow(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<SubmitWorkflowRequest>,
+) -> impl IntoResponse {
+    let steps = request.steps.into_iter()
+        .map(|step| WorkflowStep {
+            name: step.step,
+            descriptor: descriptor_from_request(step.job),
+        })
+        .collect();
+    
+    match state.scheduler.submit_workflow(steps) {
+        Ok(jobs) => (StatusCode::CREATED, Json(WorkflowResponse { jobs })).into_response(),
+        Err(e) => (error_status(&e, StatusCode::BAD_REQUEST), Json(ErrorResponse {
+            error: "invalid_workflow".to_string(),
+            message: e.to_string(),
+        })).into_response(),
+    }
+}
+
 async fn get_job(
     State(state): State<Arc<AppState>>,
     Path(job_id): Path<String>,
 ) -> impl IntoResponse {
-    match state.scheduler.get_job(&job_id) {
-        Some(job) => (StatusCode::OK, Json(job_to_response(&job))),
+    match lookup_job(&state, &job_id) {
+        Some(response) => (StatusCode::OK, Json(response)),
         None => (StatusCode::NOT_FOUND, Json(JobResponse {
             job_id,
             name: "not_found".to_string(),
@@ -188,6 +318,7 @@ async fn get_job(
             created_at: "".to_string(),
             allocated_nodes: vec![],
             gpu_count: 0,
+            array: None,
         })),
     }
 }
@@ -205,18 +336,31 @@ async fn cancel_job(
     State(state): State<Arc<AppState>>,
     Path(job_id): Path<String>,
 ) -> impl IntoResponse {
-    match state.scheduler.cancel(&job_id, "User requested cancellation") {
+    match state.scheduler.cancel(&job_id, Actor::User, "User requested cancellation") {
         Ok(()) => (StatusCode::OK, Json(SuccessResponse {
             status: "success".to_string(),
             message: format!("Job {} cancelled", job_id),
         })),
-        Err(e) => (StatusCode::BAD_REQUEST, Json(SuccessResponse {
+        Err(e) => (error_status(&e, StatusCode::BAD_REQUEST), Json(SuccessResponse {
             status: "error".to_string(),
             message: e.to_string(),
         })),
     }
 }
 
+async fn job_events(
+    State(state): State<Arc<AppState>>,
+    Path(job_id): Path<String>,
+) -> impl IntoResponse {
+    match state.scheduler.job_events(&job_id) {
+        Some(events) => (StatusCode::OK, Json(JobEventsResponse { job_id, events })).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(ErrorResponse {
+            error: "not_found".to_string(),
+            message: format!("Job not found: {}", job_id),
+        })).into_response(),
+    }
+}
+
 async fn cluster_status(
     State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
@@ -235,27 +379,234 @@ async fn cluster_status(
 async fn list_nodes(
     State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
-    let nodes: Vec<NodeResponse> = state.node_registry.healthy_nodes()
+    let nodes: Vec<NodeResponse> = state.node_registry.all_nodes()
         .iter()
-        .map(|n| NodeResponse {
-            id: n.id.clone(),
-            hostname: n.hostname.clone(),
-            health: format!("{:?}", n.health),
-            total_gpus: n.total_gpus(),
-            available_gpus: n.available_gpus(),
-            running_jobs: n.running_jobs.len(),
-        })
+        .map(node_to_response)
         .collect();
     
     Json(nodes)
 }
 
+async fn register_node(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<RegisterNodeRequest>,
+) -> impl IntoResponse {
+    let node_id = request.node_id.clone();
+    let mut node = Node::new(request.node_id, request.hostname, request.ip_address, request.topology);
+    node.labels = request.labels;
+    
+    match state.scheduler.register_node(node, request.running_jobs.as_deref()) {
+        Ok(orphaned_jobs) => (StatusCode::CREATED, Json(RegisterNodeResponse {
+            node_id,
+            orphaned_jobs,
+        })).into_response(),
+        Err(e) => (error_status(&e, StatusCode::BAD_REQUEST), Json(ErrorResponse {
+            error: "registration_failed".to_string(),
+            message: e.to_string(),
+        })).into_response(),
+    }
+}
+
+async fn node_heartbeat(
+    State(state): State<Arc<AppState>>,
+    Path(node_id): Path<String>,
+    Json(request): Json<HeartbeatRequest>,
+) -> impl IntoResponse {
+    // Unreachable nodes must register again so their jobs are reconciled
+    if state.node_registry.get(&node_id).is_some_and(|n| n.health == NodeHealth::Unreachable) {
+        return (StatusCode::CONFLICT, Json(ErrorResponse {
+            error: "node_unreachable".to_string(),
+            message: format!("Node {} was marked unreachable and must register again", node_id),
+        })).into_response();
+    }
+    match state.scheduler.heartbeat(&node_id, &request.gpus, request.cpu_memory_free, &request.stopped_jobs) {
+        Ok(node) => (StatusCode::OK, Json(HeartbeatResponse {
+            node: node_to_response(&node),
+            stop_jobs: node.stopping_jobs,
+        })).into_response(),
+        Err(e) => node_error(e),
+    }
+}
+
+async fn deregister_node(
+    State(state): State<Arc<AppState>>,
+    Path(node_id): Path<String>,
+) -> impl IntoResponse {
+    match state.scheduler.deregister_node(&node_id) {
+        Ok(_) => (StatusCode::OK, Json(SuccessResponse {
+            status: "success".to_string(),
+            message: format!("Node {} deregistered", node_id),
+        })),
+        Err(e) => (error_status(&e, StatusCode::NOT_FOUND), Json(SuccessResponse {
+            status: "error".to_string(),
+            message: e.to_string(),
+        })),
+    }
+}
+
+async fn cordon_node(
+    State(state): State<Arc<AppState>>,
+    Path(node_id): Path<String>,
+) -> impl IntoResponse {
+    node_result(state.scheduler.cordon(&node_id))
+}
+
+async fn drain_node(
+    State(state): State<Arc<AppState>>,
+    Path(node_id): Path<String>,
+    Json(request): Json<DrainNodeRequest>,
+) -> impl IntoResponse {
+    let deadline = request.timeout_secs
+        .map(|secs| chrono::Utc::now() + chrono::Duration::seconds(secs as i64));
+    node_result(state.scheduler.drain(&node_id, deadline))
+}
+
+async fn uncordon_node(
+    State(state): State<Arc<AppState>>,
+    Path(node_id): Path<String>,
+) -> impl IntoResponse {
+    node_result(state.scheduler.uncordon(&node_id))
+}
+
+async fn list_queues(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    Json(state.scheduler.queue_stats())
+}
+
+async fn create_reservation(
+    State(state): State<Arc<AppState>>,
+    Json(reservation): Json<Reservation>,
+) -> impl IntoResponse {
+    match state.scheduler.create_reservation(reservation) {
+        Ok(reservation) => (StatusCode::CREATED, Json(reservation)).into_response(),
+        Err(e) => (error_status(&e, StatusCode::BAD_REQUEST), Json(ErrorResponse {
+            error: "invalid_reservation".to_string(),
+            message: e.to_string(),
+        })).into_response(),
+    }
+}
+
+async fn list_reservations(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    Json(state.scheduler.reservations())
+}
+
+async fn delete_reservation(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    match state.scheduler.delete_reservation(&name) {
+        Ok(()) => (StatusCode::OK, Json(SuccessResponse {
+            status: "success".to_string(),
+            message: format!("Reservation {} deleted", name),
+        })),
+        Err(e) => (error_status(&e, StatusCode::NOT_FOUND), Json(SuccessResponse {
+            status: "error".to_string(),
+            message: e.to_string(),
+        })),
+    }
+}
+
 async fn health_check() -> impl IntoResponse {
     (StatusCode::OK, "OK")
 }
 
 // === Helpers ===
 
+fn descriptor_from_request(request: SubmitJobRequest) -> JobDescriptor {
+    JobDescriptor {
+        name: request.name,
+        user_id: request.user_id,
+        project_id: request.project_id,
+        command: request.command,
+        arguments: request.arguments,
+        environment: request.environment,
+        working_directory: request.working_directory,
+        resources: ResourceRequirements {
+            gpu_count: request.gpu_count,
+            cpu_cores: request.cpu_cores,
+            cpu_memory: request.memory_mb * 1024 * 1024, // Convert MB to bytes
+            ..Default::default()
+        },
+        locality: LocalityPreferences::default(),
+        policy: SchedulingPolicy {
+            priority: request.priority,
+            gang_schedule: request.gang_schedule,
+            reservation: request.reservation,
+            ..Default::default()
+        },
+        labels: HashMap::new(),
+        annotations: HashMap::new(),
+        dependencies: JobDependencies {
+            after_ok: request.after_ok,
+            after_any: request.after_any,
+            after_fail: request.after_fail,
+        },
+        array: request.array,
+    }
+}
+
+fn node_to_response(node: &Node) -> NodeResponse {
+    NodeResponse {
+        id: node.id.clone(),
+        hostname: node.hostname.clone(),
+        health: format!("{:?}", node.health),
+        total_gpus: node.total_gpus(),
+        available_gpus: node.available_gpus(),
+        running_jobs: node.running_jobs.len(),
+        scheduling: node.scheduling,
+    }
+}
+
+fn node_result(result: crate::Result<Node>) -> axum::response::Response {
+    match result {
+        Ok(node) => (StatusCode::OK, Json(node_to_response(&node))).into_response(),
+        Err(e) => node_error(e),
+    }
+}
+
+fn node_error(e: crate::Error) -> axum::response::Response {
+    let (status, error) = match e {
+        crate::Error::Node(_) => (StatusCode::NOT_FOUND, "node_not_found"),
+        crate::Error::Io(_) | crate::Error::Serialization(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
+        _ => (StatusCode::BAD_REQUEST, "invalid_request"),
+    };
+    (status, Json(ErrorResponse {
+        error: error.to_string(),
+        message: e.to_string(),
+    })).into_response()
+}
+
+/// `client` for errors in the request, 500 if the state store failed
+fn error_status(e: &crate::Error, client: StatusCode) -> StatusCode {
+    match e {
+        crate::Error::Io(_) | crate::Error::Serialization(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        _ => client,
+    }
+}
+
+/// A job, or an array job aggregated over its children
+fn lookup_job(state: &AppState, job_id: &str) -> Option<JobResponse> {
+    if let Some(job) = state.scheduler.get_job(job_id) {
+        return Some(job_to_response(&job));
+    }
+    
+    let (array, status) = state.scheduler.get_array(job_id)?;
+    Some(JobResponse {
+        job_id: array.id.to_string(),
+        name: array.descriptor.name.clone(),
+        state: format!("{:?}", status.state),
+        user_id: array.descriptor.user_id.clone(),
+        project_id: array.descriptor.project_id.clone(),
+        created_at: array.submit_time.to_rfc3339(),
+        allocated_nodes: vec![],
+        gpu_count: array.descriptor.resources.gpu_count,
+        array: Some(status),
+    })
+}
+
 fn job_to_response(job: &Job) -> JobResponse {
     JobResponse {
         job_id: job.id.to_string(),
@@ -266,5 +617,232 @@ fn job_to_response(job: &Job) -> JobResponse {
         created_at: job.submit_time.to_rfc3339(),
         allocated_nodes: job.allocated_nodes.clone(),
         gpu_count: job.descriptor.resources.gpu_count,
+        array: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::SchedulerConfig;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+    
+    fn test_state() -> Arc<AppState> {
+        let node_registry = Arc::new(NodeRegistry::new(60));
+        let scheduler = Arc::new(Scheduler::new(node_registry.clone(), SchedulerConfig::default()));
+        Arc::new(AppState { scheduler, node_registry })
+    }
+    
+    async fn get_json(router: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        send(router, Request::get(uri).body(Body::empty()).unwrap()).await
+    }
+    
+    async fn post_json(router: Router, uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let request = Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        send(router, request).await
+    }
+    
+    async fn send(router: Router, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+    
+    #[tokio::test]
+    async fn test_job_events() {
+        let state = test_state();
+        let job = Job::new(JobDescriptor {
+            name: "events".to_string(),
+            user_id: "alice".to_string(),
+            project_id: "vision".to_string(),
+            command: "true".to_string(),
+            working_directory: "/".to_string(),
+            ..Default::default()
+        });
+        let job_id = state.scheduler.submit(job).unwrap();
+        state.scheduler.cancel(&job_id, Actor::User, "no longer needed").unwrap();
+        
+        let router = create_router(state);
+        let (status, body) = get_json(router.clone(), &format!("/api/v1/jobs/{}/events", job_id)).await;
+        assert_eq!(status, StatusCode::OK);
+        let events = body["events"].as_array().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["to"], "Queued");
+        assert_eq!(events[1]["from"], "Queued");
+        assert_eq!(events[1]["to"], "Cancelled");
+        assert_eq!(events[1]["actor"], "user");
+        assert_eq!(events[1]["reason"], "no longer needed");
+        
+        let (status, _) = get_json(router, "/api/v1/jobs/missing/events").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+    
+    #[tokio::test]
+    async fn test_submit_workflow() {
+        let state = test_state();
+        let router = create_router(state.clone());
+        let step = |name: &str, after_ok: &[&str]| serde_json::json!({
+            "step": name,
+            "name": name,
+            "user_id": "alice",
+            "project_id": "vision",
+            "command": "python",
+            "after_ok": after_ok,
+        });
+        
+        let (status, body) = post_json(router.clone(), "/api/v1/workflows", serde_json::json!({
+            "steps": [step("train", &["preprocess"]), step("preprocess", &[])],
+        })).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let train = state.scheduler.get_job(body["jobs"]["train"].as_str().unwrap()).unwrap();
+        assert_eq!(train.state, crate::job::JobState::Blocked);
+        assert_eq!(train.descriptor.dependencies.after_ok, vec![body["jobs"]["preprocess"].as_str().unwrap()]);
+        
+        let (status, body) = post_json(router, "/api/v1/workflows", serde_json::json!({
+            "steps": [step("a", &["b"]), step("b", &["a"])],
+        })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["message"].as_str().unwrap().contains("cycle"));
+    }
+    
+    #[tokio::test]
+    async fn test_array_job_aggregates_children() {
+        let state = test_state();
+        let router = create_router(state.clone());
+        
+        let (status, body) = post_json(router.clone(), "/api/v1/jobs", serde_json::json!({
+            "name": "sweep",
+            "user_id": "alice",
+            "project_id": "vision",
+            "command": "python",
+            "array": { "start": 1, "end": 3 },
+        })).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["array"]["total"], 3);
+        
+        let array_id = body["job_id"].as_str().unwrap();
+        let (array, _) = state.scheduler.get_array(array_id).unwrap();
+        state.scheduler.cancel(&array.children[0], Actor::User, "bad params").unwrap();
+        
+        let (status, body) = get_json(router.clone(), &format!("/api/v1/jobs/{}", array_id)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["state"], "Queued");
+        assert_eq!(body["array"]["counts"]["Cancelled"], 1);
+        assert_eq!(body["array"]["counts"]["Queued"], 2);
+        
+        let (status, _) = post_json(router, "/api/v1/jobs", serde_json::json!({
+            "name": "huge",
+            "user_id": "alice",
+            "project_id": "vision",
+            "command": "python",
+            "array": { "start": 0, "end": u32::MAX },
+        })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    
+    #[tokio::test]
+    async fn test_cordon_drain_uncordon() {
+        let state = test_state();
+        let topology = crate::node::NodeTopology {
+            gpus: vec![],
+            cpu_cores: 8,
+            cpu_memory: 32 * 1024 * 1024 * 1024,
+            cpu_memory_free: 32 * 1024 * 1024 * 1024,
+            numa_nodes: 1,
+            nvlink_present: false,
+            nvlink_version: 0,
+            nvswitch_present: false,
+            rdma_capable: false,
+            nvlink_connections: vec![],
+            numa_cpu_cores: HashMap::new(),
+        };
+        let node = Node::new("node-1".to_string(), "host-1".to_string(), "10.0.0.1".to_string(), topology);
+        state.node_registry.register(node).unwrap();
+        let router = create_router(state.clone());
+        
+        let (status, body) = post_json(router.clone(), "/api/v1/nodes/node-1/cordon", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["scheduling"]["state"], "cordoned");
+        
+        let (status, body) = post_json(router.clone(), "/api/v1/nodes/node-1/drain", serde_json::json!({
+            "timeout_secs": 600,
+        })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["scheduling"]["state"], "draining");
+        assert!(body["scheduling"]["deadline"].is_string());
+        
+        // Nothing runs there, so the drain finishes on the next pass
+        assert_eq!(state.scheduler.monitor_nodes().drained, vec!["node-1"]);
+        let (_, body) = get_json(router.clone(), "/api/v1/nodes").await;
+        assert_eq!(body[0]["scheduling"]["state"], "drained");
+        
+        let (status, body) = post_json(router.clone(), "/api/v1/nodes/node-1/uncordon", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["scheduling"]["state"], "schedulable");
+        
+        let (status, _) = post_json(router, "/api/v1/nodes/missing/cordon", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        
+        // A state store failure is not the client's fault
+        let failed = node_error(crate::Error::Io(std::io::Error::other("disk full")));
+        assert_eq!(failed.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    
+    #[tokio::test]
+    async fn test_node_heartbeat() {
+        let state = test_state();
+        let router = create_router(state.clone());
+        let heartbeat = serde_json::json!({
+            "gpus": [{ "device_id": "cuda:0", "utilization": 0.5, "free_memory": 1024, "temperature": 60 }],
+            "cpu_memory_free": 2048,
+        });
+        
+        let (status, _) = post_json(router.clone(), "/api/v1/nodes/node-1/heartbeat", heartbeat.clone()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        
+        let (status, body) = post_json(router.clone(), "/api/v1/nodes", serde_json::json!({
+            "node_id": "node-1",
+            "hostname": "host-1",
+            "ip_address": "10.0.0.1",
+            "topology": {
+                "gpus": [{
+                    "device_id": "cuda:0", "device_name": "NVIDIA A100", "uuid": "GPU-0",
+                    "total_memory": 4096, "free_memory": 4096, "utilization": 0.0, "temperature": 40,
+                    "allocated": false, "allocated_job_id": null,
+                }],
+                "cpu_cores": 8, "cpu_memory": 8192, "cpu_memory_free": 8192, "numa_nodes": 1,
+                "nvlink_present": false, "nvswitch_present": false, "rdma_capable": false,
+            },
+            "running_jobs": ["gone"],
+        })).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["orphaned_jobs"], serde_json::json!(["gone"]));
+        
+        let (status, body) = post_json(router.clone(), "/api/v1/nodes/node-1/heartbeat", heartbeat.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["id"], "node-1");
+        assert_eq!(body["stop_jobs"], serde_json::json!([]));
+        let node = state.node_registry.get("node-1").unwrap();
+        assert_eq!(node.topology.gpus[0].utilization, 0.5);
+        assert_eq!(node.topology.cpu_memory_free, 2048);
+        
+        let mut stale = node;
+        stale.health = NodeHealth::Unreachable;
+        state.node_registry.register(stale).unwrap();
+        let (status, _) = post_json(router.clone(), "/api/v1/nodes/node-1/heartbeat", heartbeat).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (_, body) = get_json(router.clone(), "/api/v1/nodes").await;
+        assert_eq!(body[0]["health"], "Unreachable");
+        
+        let request = Request::delete("/api/v1/nodes/node-1").body(Body::empty()).unwrap();
+        let (status, _) = send(router, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(state.node_registry.get("node-1").is_none());
     }
 }
@@ -1,6 +1,11 @@
 //! Scheduler configuration
 
+use crate::partition::PartitionConfig;
+use crate::quota::QuotaConfig;
+use crate::scheduler::{CpuPlacement, DEFAULT_MAX_ARRAY_SIZE};
+use crate::state::StateStoreConfig;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 /// Scheduler configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,6 +24,43 @@ pub struct SchedulerConfig {
     pub backfill_enabled: bool,
     /// Enable topology-aware placement
     pub topology_aware: bool,
+    /// Placement strategy for CPU-only jobs
+    #[serde(default)]
+    pub cpu_placement: CpuPlacement,
+    /// Per-user/per-project quotas and fair share
+    #[serde(default)]
+    pub quotas: QuotaConfig,
+    /// Named queues with their own nodes and policies
+    #[serde(default)]
+    pub partitions: Vec<PartitionConfig>,
+    /// Largest array job accepted, in children
+    #[serde(default = "default_max_array_size")]
+    pub max_array_size: u32,
+    /// Directory of the state store (checkpoint and WAL)
+    #[serde(default = "default_data_dir")]
+    pub data_dir: PathBuf,
+    /// Seconds between checkpoints of the state store
+    #[serde(default = "default_checkpoint_interval")]
+    pub checkpoint_interval_secs: u64,
+    /// Seconds between node monitor and reaper passes
+    #[serde(default = "default_monitor_interval")]
+    pub monitor_interval_secs: u64,
+}
+
+fn default_max_array_size() -> u32 {
+    DEFAULT_MAX_ARRAY_SIZE
+}
+
+fn default_data_dir() -> PathBuf {
+    PathBuf::from("/var/lib/zenith/scheduler")
+}
+
+fn default_checkpoint_interval() -> u64 {
+    60
+}
+
+fn default_monitor_interval() -> u64 {
+    10
 }
 
 impl Default for SchedulerConfig {
@@ -31,6 +73,38 @@ impl Default for SchedulerConfig {
             max_schedule_batch: 100,
             backfill_enabled: true,
             topology_aware: true,
+            cpu_placement: CpuPlacement::default(),
+            quotas: QuotaConfig::default(),
+            partitions: vec![],
+            max_array_size: DEFAULT_MAX_ARRAY_SIZE,
+            data_dir: default_data_dir(),
+            checkpoint_interval_secs: default_checkpoint_interval(),
+            monitor_interval_secs: default_monitor_interval(),
+        }
+    }
+}
+
+impl SchedulerConfig {
+    /// Load configuration from a YAML or JSON file
+    pub fn from_file(path: &str) -> crate::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| crate::Error::Config(format!("Failed to read config: {}", e)))?;
+        
+        if path.ends_with(".yaml") || path.ends_with(".yml") {
+            serde_yaml::from_str(&content)
+                .map_err(|e| crate::Error::Config(format!("Failed to parse YAML: {}", e)))
+        } else {
+            serde_json::from_str(&content)
+                .map_err(|e| crate::Error::Config(format!("Failed to parse JSON: {}", e)))
+        }
+    }
+    
+    /// Configuration of the state store under `data_dir`
+    pub fn state_store(&self) -> StateStoreConfig {
+        StateStoreConfig {
+            data_dir: self.data_dir.clone(),
+            checkpoint_interval_secs: self.checkpoint_interval_secs,
+            ..StateStoreConfig::default()
         }
     }
 }
@@ -2,14 +2,19 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use crate::node::CpuAllocation;
+use crate::{Error, Result};
 
 /// Job state
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum JobState {
     /// Job is pending submission
     Pending,
+    /// Job is waiting for its dependencies
+    Blocked,
     /// Job is queued waiting for resources
     Queued,
     /// Job has been scheduled to nodes
@@ -34,11 +39,278 @@ impl Default for JobState {
     }
 }
 
+impl JobState {
+    /// Is this a final state (job will not run again)?
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled | Self::Timeout)
+    }
+    
+    /// Is `next` a legal successor of this state?
+    ///
+    /// Terminal states are final except Failed -> Queued, which is how the
+    /// reaper retries a failed job.
+    pub fn can_transition_to(&self, next: JobState) -> bool {
+        use JobState::*;
+        match (*self, next) {
+            (Pending, Queued | Blocked) => true,
+            (Blocked, Queued) => true,
+            (Queued, Scheduled) => true,
+            (Scheduled, Running | Suspended) => true,
+            (Running, Suspended) => true,
+            (Suspended, Scheduled | Queued) => true,
+            (Scheduled | Running, Completed | Timeout) => true,
+            (Failed, Queued) => true,
+            (from, Failed | Cancelled) => !from.is_terminal(),
+            _ => false,
+        }
+    }
+}
+
+/// Dependencies on other jobs, by job ID
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JobDependencies {
+    /// Start after these jobs complete successfully
+    pub after_ok: Vec<String>,
+    /// Start after these jobs finish, whatever the outcome
+    pub after_any: Vec<String>,
+    /// Start after these jobs fail or time out
+    pub after_fail: Vec<String>,
+}
+
+/// Whether a job's dependencies allow it to run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencyStatus {
+    /// All upstream jobs finished as required
+    Satisfied,
+    /// Some upstream job has not finished yet
+    Waiting,
+    /// Some upstream job finished the wrong way (or does not exist)
+    Unsatisfiable(String),
+}
+
+impl JobDependencies {
+    /// No dependencies?
+    pub fn is_empty(&self) -> bool {
+        self.after_ok.is_empty() && self.after_any.is_empty() && self.after_fail.is_empty()
+    }
+    
+    /// All upstream job IDs
+    pub fn job_ids(&self) -> impl Iterator<Item = &String> {
+        self.after_ok.iter().chain(&self.after_any).chain(&self.after_fail)
+    }
+    
+    /// Evaluate against the upstream jobs returned by `lookup`
+    pub fn status<'a>(&self, lookup: impl Fn(&str) -> Option<&'a Job>) -> DependencyStatus {
+        type Accepts = fn(JobState) -> bool;
+        let conditions: [(&Vec<String>, Accepts); 3] = [
+            (&self.after_ok, |state| state == JobState::Completed),
+            (&self.after_any, |_| true),
+            (&self.after_fail, |state| matches!(state, JobState::Failed | JobState::Timeout)),
+        ];
+        
+        let mut waiting = false;
+        for (job_ids, accepts) in conditions {
+            for job_id in job_ids {
+                let Some(upstream) = lookup(job_id) else {
+                    return DependencyStatus::Unsatisfiable(format!("dependency {} not found", job_id));
+                };
+                match upstream.final_state() {
+                    None => waiting = true,
+                    Some(state) if accepts(state) => {}
+                    Some(state) => {
+                        return DependencyStatus::Unsatisfiable(format!(
+                            "dependency {} ended {:?}", job_id, state
+                        ));
+                    }
+                }
+            }
+        }
+        
+        if waiting {
+            DependencyStatus::Waiting
+        } else {
+            DependencyStatus::Satisfied
+        }
+    }
+}
+
+/// Environment variable carrying an array child's index
+pub const ARRAY_INDEX_ENV: &str = "ZENITH_ARRAY_INDEX";
+
+/// Index range and throttle of an array job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArraySpec {
+    /// First index
+    pub start: u32,
+    /// Last index (inclusive)
+    pub end: u32,
+    /// Maximum children scheduled or running at once (0 = unlimited)
+    #[serde(default)]
+    pub max_concurrent: u32,
+}
+
+/// Link from an array child to its parent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArrayTask {
+    /// Parent array job ID
+    pub parent_id: String,
+    /// This child's index
+    pub index: u32,
+    /// The parent's throttle (0 = unlimited)
+    pub max_concurrent: u32,
+}
+
+/// Parent record of an array job; its children are ordinary jobs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArrayJob {
+    /// Unique array job ID
+    pub id: Uuid,
+    /// Descriptor the children were expanded from
+    pub descriptor: JobDescriptor,
+    /// Submission time
+    pub submit_time: DateTime<Utc>,
+    /// Child job IDs in index order
+    pub children: Vec<String>,
+}
+
+impl ArrayJob {
+    /// Expand an array descriptor into the parent record and its children,
+    /// rejecting arrays of more than `max_size` children
+    pub fn expand(job: Job, max_size: u32) -> Result<(Self, Vec<Job>)> {
+        let mut descriptor = job.descriptor;
+        let spec = descriptor.array.take()
+            .ok_or_else(|| Error::Job("Not an array job".to_string()))?;
+        if spec.start > spec.end {
+            return Err(Error::Job(format!(
+                "Invalid array range {}-{}", spec.start, spec.end
+            )));
+        }
+        let size = u64::from(spec.end - spec.start) + 1;
+        if size > u64::from(max_size) {
+            return Err(Error::Job(format!(
+                "Array range {}-{} has {} children, more than the maximum of {}",
+                spec.start, spec.end, size, max_size
+            )));
+        }
+        
+        let id = job.id;
+        let children: Vec<Job> = (spec.start..=spec.end)
+            .map(|index| {
+                let mut child = descriptor.clone();
+                child.name = format!("{}[{}]", descriptor.name, index);
+                child.environment.insert(ARRAY_INDEX_ENV.to_string(), index.to_string());
+                let mut job = Job::new(child);
+                job.array_task = Some(ArrayTask {
+                    parent_id: id.to_string(),
+                    index,
+                    max_concurrent: spec.max_concurrent,
+                });
+                job
+            })
+            .collect();
+        
+        descriptor.array = Some(spec);
+        let parent = Self {
+            id,
+            descriptor,
+            submit_time: job.submit_time,
+            children: children.iter().map(|job| job.id.to_string()).collect(),
+        };
+        Ok((parent, children))
+    }
+}
+
+/// Aggregate state of an array job's children
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArrayStatus {
+    /// Overall state
+    pub state: JobState,
+    /// Number of children
+    pub total: usize,
+    /// Children per state
+    pub counts: HashMap<JobState, usize>,
+}
+
+impl ArrayStatus {
+    /// Aggregate children: finished once all are, Running while any is
+    /// scheduled or running, otherwise Queued (Blocked if all wait on
+    /// dependencies)
+    pub fn of<'a>(children: impl IntoIterator<Item = &'a Job>) -> Self {
+        let mut counts: HashMap<JobState, usize> = HashMap::new();
+        for child in children {
+            *counts.entry(child.state).or_default() += 1;
+        }
+        let total = counts.values().sum();
+        let count = |state: JobState| counts.get(&state).copied().unwrap_or(0);
+        let finished = count(JobState::Completed) + count(JobState::Failed)
+            + count(JobState::Cancelled) + count(JobState::Timeout);
+        let started = count(JobState::Scheduled) + count(JobState::Running);
+        
+        let state = if finished == total {
+            if count(JobState::Completed) == total {
+                JobState::Completed
+            } else if count(JobState::Failed) + count(JobState::Timeout) > 0 {
+                JobState::Failed
+            } else {
+                JobState::Cancelled
+            }
+        } else if started > 0 {
+            JobState::Running
+        } else if count(JobState::Blocked) == total {
+            JobState::Blocked
+        } else {
+            JobState::Queued
+        };
+        
+        Self { state, total, counts }
+    }
+}
+
+/// One step of a workflow submitted as a whole
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowStep {
+    /// Step name, unique within the workflow
+    pub name: String,
+    /// Job to run; its dependencies may name other steps as well as job IDs
+    pub descriptor: JobDescriptor,
+}
+
+/// Who caused a job state change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Actor {
+    /// The scheduler (placement, preemption, timeouts, retries)
+    Scheduler,
+    /// A node agent reporting on execution
+    Agent,
+    /// A user request through the API
+    User,
+}
+
+/// One entry in a job's state history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobEvent {
+    /// When the change happened
+    pub timestamp: DateTime<Utc>,
+    /// Previous state
+    pub from: JobState,
+    /// New state
+    pub to: JobState,
+    /// Who made the change
+    pub actor: Actor,
+    /// Why
+    pub reason: String,
+}
+
 /// Resource requirements for a job
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResourceRequirements {
-    /// Number of GPUs required
+    /// Number of GPUs required (the minimum for elastic jobs)
     pub gpu_count: u32,
+    /// Elastic jobs: GPUs the job can grow to once it is running
+    #[serde(default)]
+    pub max_gpu_count: Option<u32>,
     /// GPU memory per device in bytes
     pub gpu_memory_per_device: u64,
     /// Number of CPU cores required
@@ -59,6 +331,7 @@ impl Default for ResourceRequirements {
     fn default() -> Self {
         Self {
             gpu_count: 1,
+            max_gpu_count: None,
             gpu_memory_per_device: 0,
             cpu_cores: 1,
             cpu_memory: 1024 * 1024 * 1024, // 1GB
@@ -70,6 +343,20 @@ impl Default for ResourceRequirements {
     }
 }
 
+impl ResourceRequirements {
+    /// GPU range `(min, max)`; equal unless the job is elastic
+    pub fn gpu_range(&self) -> (u32, u32) {
+        let max = self.max_gpu_count.unwrap_or(self.gpu_count).max(self.gpu_count);
+        (self.gpu_count, max)
+    }
+    
+    /// Whether the job can run on a varying number of GPUs
+    pub fn is_elastic(&self) -> bool {
+        let (min, max) = self.gpu_range();
+        max > min
+    }
+}
+
 /// Locality preferences
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct LocalityPreferences {
@@ -83,6 +370,58 @@ pub struct LocalityPreferences {
     pub preferred_nodes: Vec<String>,
     /// Excluded node IDs
     pub excluded_nodes: Vec<String>,
+    /// Label expressions every node must match
+    #[serde(default)]
+    pub node_selector: Vec<LabelSelector>,
+}
+
+/// Label selector operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SelectorOperator {
+    /// Label present with one of the values
+    In,
+    /// Label absent or not one of the values
+    NotIn,
+    /// Label present
+    Exists,
+    /// Label absent
+    DoesNotExist,
+}
+
+/// Node label match expression (e.g. `gpu-class In [a100, h100]`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LabelSelector {
+    /// Label key
+    pub key: String,
+    /// Operator
+    pub operator: SelectorOperator,
+    /// Values for `In` / `NotIn`
+    #[serde(default)]
+    pub values: Vec<String>,
+}
+
+impl LabelSelector {
+    /// Check the expression against a node's labels
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        let value = labels.get(&self.key);
+        match self.operator {
+            SelectorOperator::In => value.is_some_and(|v| self.values.contains(v)),
+            SelectorOperator::NotIn => !value.is_some_and(|v| self.values.contains(v)),
+            SelectorOperator::Exists => value.is_some(),
+            SelectorOperator::DoesNotExist => value.is_none(),
+        }
+    }
+}
+
+impl fmt::Display for LabelSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.operator {
+            SelectorOperator::In | SelectorOperator::NotIn => {
+                write!(f, "{} {:?} [{}]", self.key, self.operator, self.values.join(", "))
+            }
+            _ => write!(f, "{} {:?}", self.key, self.operator),
+        }
+    }
 }
 
 /// Scheduling policy
@@ -94,7 +433,7 @@ pub struct SchedulingPolicy {
     pub preemptible: bool,
     /// Can this job preempt others?
     pub can_preempt_others: bool,
-    /// Maximum wait time in queue (seconds)
+    /// Maximum wait time in queue (seconds, 0 = unlimited)
     pub max_wait_time_seconds: u64,
     /// Maximum runtime (seconds, 0 = unlimited)
     pub max_runtime_seconds: u64,
@@ -104,6 +443,9 @@ pub struct SchedulingPolicy {
     pub gang_schedule: bool,
     /// Maximum retry attempts
     pub max_retries: u32,
+    /// Advance reservation to run in, by name
+    #[serde(default)]
+    pub reservation: Option<String>,
 }
 
 impl Default for SchedulingPolicy {
@@ -117,12 +459,13 @@ impl Default for SchedulingPolicy {
             queue_name: "default".to_string(),
             gang_schedule: true,
             max_retries: 3,
+            reservation: None,
         }
     }
 }
 
 /// Job descriptor - the core unit of work submission
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct JobDescriptor {
     /// User-provided job name
     pub name: String,
@@ -148,6 +491,12 @@ pub struct JobDescriptor {
     pub labels: HashMap<String, String>,
     /// Annotations for metadata
     pub annotations: HashMap<String, String>,
+    /// Jobs that must finish first
+    #[serde(default)]
+    pub dependencies: JobDependencies,
+    /// Expand into one child job per index
+    #[serde(default)]
+    pub array: Option<ArraySpec>,
 }
 
 /// A job instance with state
@@ -171,10 +520,26 @@ pub struct Job {
     pub allocated_nodes: Vec<String>,
     /// Allocated GPU device IDs per node
     pub allocated_gpus: HashMap<String, Vec<String>>,
+    /// CPU cores and memory reserved per node
+    #[serde(default)]
+    pub allocated_cpu: HashMap<String, CpuAllocation>,
     /// Retry count
     pub retry_count: u32,
     /// Last state change message
     pub message: String,
+    /// Failed job waiting to be requeued at this time
+    #[serde(default)]
+    pub retry_after: Option<DateTime<Utc>>,
+    /// When the job was last requeued by a retry or released by its
+    /// dependencies (wait time counts from here)
+    #[serde(default)]
+    pub requeue_time: Option<DateTime<Utc>>,
+    /// Parent and index if this job is an array child
+    #[serde(default)]
+    pub array_task: Option<ArrayTask>,
+    /// State change history, oldest first (append-only via `transition`)
+    #[serde(default)]
+    events: Vec<JobEvent>,
 }
 
 impl Job {
@@ -190,28 +555,75 @@ impl Job {
             end_time: None,
             allocated_nodes: vec![],
             allocated_gpus: HashMap::new(),
+            allocated_cpu: HashMap::new(),
             retry_count: 0,
             message: String::new(),
+            retry_after: None,
+            requeue_time: None,
+            array_task: None,
+            events: vec![],
         }
     }
     
-    /// Transition to a new state
-    pub fn transition(&mut self, new_state: JobState, message: &str) {
+    /// Transition to a new state, recording the change in the job's history
+    ///
+    /// Fails without changing anything if the transition is not legal.
+    pub fn transition(&mut self, new_state: JobState, actor: Actor, message: &str) -> Result<()> {
+        if !self.state.can_transition_to(new_state) {
+            return Err(Error::Job(format!(
+                "Job {} cannot move from {:?} to {:?}", self.id, self.state, new_state
+            )));
+        }
+        
+        let now = Utc::now();
+        self.events.push(JobEvent {
+            timestamp: now,
+            from: self.state,
+            to: new_state,
+            actor,
+            reason: message.to_string(),
+        });
         self.state = new_state;
         self.message = message.to_string();
         
         match new_state {
             JobState::Scheduled => {
-                self.schedule_time = Some(Utc::now());
+                self.schedule_time = Some(now);
             }
             JobState::Running => {
-                self.start_time = Some(Utc::now());
+                self.start_time = Some(now);
             }
             JobState::Completed | JobState::Failed | JobState::Cancelled | JobState::Timeout => {
-                self.end_time = Some(Utc::now());
+                self.end_time = Some(now);
             }
             _ => {}
         }
+        
+        Ok(())
+    }
+    
+    /// Drop the pending retry of a failed job, recording who did it in the
+    /// history; returns whether a retry was pending
+    pub fn cancel_retry(&mut self, actor: Actor, reason: &str) -> bool {
+        if self.state != JobState::Failed || self.retry_after.take().is_none() {
+            return false;
+        }
+        
+        let message = format!("Retry cancelled: {}", reason);
+        self.events.push(JobEvent {
+            timestamp: Utc::now(),
+            from: JobState::Failed,
+            to: JobState::Failed,
+            actor,
+            reason: message.clone(),
+        });
+        self.message = message;
+        true
+    }
+    
+    /// State change history, oldest first
+    pub fn events(&self) -> &[JobEvent] {
+        &self.events
     }
     
     /// Get job runtime in seconds
@@ -223,6 +635,39 @@ impl Job {
         }
     }
     
+    /// Latest time the job can still be running, from its start (or
+    /// schedule) time and `max_runtime_seconds`; `None` if unlimited or
+    /// not started
+    pub fn expected_end_time(&self) -> Option<DateTime<Utc>> {
+        let limit = self.descriptor.policy.max_runtime_seconds;
+        if limit == 0 {
+            return None;
+        }
+        let started = self.start_time.or(self.schedule_time)?;
+        Some(started + chrono::Duration::seconds(limit as i64))
+    }
+    
+    /// GPUs currently allocated across all nodes
+    pub fn allocated_gpu_count(&self) -> usize {
+        self.allocated_gpus.values().map(|gpus| gpus.len()).sum()
+    }
+    
+    /// Outcome of a finished job, or `None` while it may still run
+    /// (including failed jobs waiting for a retry)
+    pub fn final_state(&self) -> Option<JobState> {
+        (self.state.is_terminal() && self.retry_after.is_none()).then_some(self.state)
+    }
+    
+    /// Time after which a queued job has waited too long, if it has a limit
+    pub fn wait_deadline(&self) -> Option<DateTime<Utc>> {
+        let limit = self.descriptor.policy.max_wait_time_seconds;
+        if limit == 0 {
+            return None;
+        }
+        let queued = self.requeue_time.unwrap_or(self.submit_time);
+        Some(queued + chrono::Duration::seconds(limit as i64))
+    }
+    
     /// Get queue wait time in seconds
     pub fn wait_time_seconds(&self) -> i64 {
         match self.schedule_time {
@@ -248,13 +693,8 @@ mod tests {
             project_id: "project1".to_string(),
             command: "python".to_string(),
             arguments: vec!["train.py".to_string()],
-            environment: HashMap::new(),
             working_directory: "/app".to_string(),
-            resources: ResourceRequirements::default(),
-            locality: LocalityPreferences::default(),
-            policy: SchedulingPolicy::default(),
-            labels: HashMap::new(),
-            annotations: HashMap::new(),
+            ..Default::default()
         }
     }
     
@@ -272,11 +712,64 @@ mod tests {
         let descriptor = create_test_descriptor();
         let mut job = Job::new(descriptor);
         
-        job.transition(JobState::Queued, "Submitted to queue");
+        job.transition(JobState::Queued, Actor::Scheduler, "Submitted to queue").unwrap();
         assert_eq!(job.state, JobState::Queued);
         
-        job.transition(JobState::Running, "Started");
+        // Must be placed before it can run
+        assert!(job.transition(JobState::Running, Actor::Agent, "Started").is_err());
+        assert_eq!(job.state, JobState::Queued);
+        
+        job.transition(JobState::Scheduled, Actor::Scheduler, "Resources allocated").unwrap();
+        job.transition(JobState::Running, Actor::Agent, "Started").unwrap();
         assert_eq!(job.state, JobState::Running);
         assert!(job.start_time.is_some());
+        
+        job.transition(JobState::Completed, Actor::Agent, "Exit 0").unwrap();
+        assert!(job.transition(JobState::Running, Actor::Agent, "again").is_err());
+        assert!(job.transition(JobState::Queued, Actor::Scheduler, "retry").is_err());
+        
+        let events = job.events();
+        assert_eq!(events.len(), 4);
+        assert_eq!((events[0].from, events[0].to), (JobState::Pending, JobState::Queued));
+        assert_eq!(events[2].actor, Actor::Agent);
+        assert_eq!(events[3].reason, "Exit 0");
+        assert!(events.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+    }
+    
+    #[test]
+    fn test_array_expansion_and_status() {
+        let mut descriptor = create_test_descriptor();
+        descriptor.array = Some(ArraySpec { start: 3, end: 5, max_concurrent: 2 });
+        assert!(ArrayJob::expand(Job::new(descriptor.clone()), 2).is_err());
+        let (parent, mut children) = ArrayJob::expand(Job::new(descriptor), 3).unwrap();
+        
+        assert_eq!(parent.children.len(), 3);
+        assert_eq!(children[1].descriptor.name, "test-job[4]");
+        assert_eq!(children[1].descriptor.environment[ARRAY_INDEX_ENV], "4");
+        assert!(children[1].descriptor.array.is_none());
+        assert_eq!(children[2].array_task.as_ref().unwrap().parent_id, parent.id.to_string());
+        
+        let advance = |job: &mut Job, states: &[JobState]| {
+            for state in states {
+                job.transition(*state, Actor::Scheduler, "test").unwrap();
+            }
+        };
+        for child in &mut children {
+            advance(child, &[JobState::Queued]);
+        }
+        assert_eq!(ArrayStatus::of(&children).state, JobState::Queued);
+        
+        advance(&mut children[0], &[JobState::Scheduled, JobState::Running]);
+        let status = ArrayStatus::of(&children);
+        assert_eq!(status.state, JobState::Running);
+        assert_eq!(status.counts[&JobState::Queued], 2);
+        
+        // Finished children do not count as started
+        advance(&mut children[0], &[JobState::Completed]);
+        assert_eq!(ArrayStatus::of(&children).state, JobState::Queued);
+        
+        advance(&mut children[1], &[JobState::Scheduled, JobState::Running, JobState::Completed]);
+        advance(&mut children[2], &[JobState::Cancelled]);
+        assert_eq!(ArrayStatus::of(&children).state, JobState::Cancelled);
     }
 }
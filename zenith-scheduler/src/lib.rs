@@ -11,6 +11,7 @@
 //! - **Topology Awareness**: NVLink/NVSwitch/NUMA-aware placement
 //! - **Preemption & Backfill**: Priority-based with backfill optimization
 //! - **Quotas & Fairness**: Per-user and per-project resource limits
+//! - **Partitions**: Named queues with their own nodes, limits and priority tiers
 //!
 //! ## Architecture
 //!
@@ -40,6 +41,9 @@ pub mod api;
 pub mod config;
 pub mod job;
 pub mod node;
+pub mod partition;
+pub mod quota;
+pub mod reservation;
 pub mod scheduler;
 pub mod state;
 
@@ -1,7 +1,13 @@
 //! Zenith Job Scheduler - Main Entry Point
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::info;
+use zenith_scheduler::api::rest::{create_router, AppState};
+use zenith_scheduler::state::StateStore;
+use zenith_scheduler::{NodeRegistry, Scheduler, SchedulerConfig};
 
 #[derive(Parser)]
 #[command(name = "zenith-scheduler")]
@@ -18,6 +24,41 @@ struct Args {
     /// HTTP listen address
     #[arg(long, default_value = "0.0.0.0:8080")]
     http_address: String,
+    
+    /// Operate on a running scheduler instead of starting one
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Cordon, drain or uncordon a node
+    Node {
+        /// Scheduler HTTP API
+        #[arg(long, global = true, default_value = "http://localhost:8080")]
+        server: String,
+        #[command(subcommand)]
+        action: NodeAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum NodeAction {
+    /// Stop placing new jobs on the node
+    Cordon {
+        node_id: String,
+    },
+    /// Cordon the node and requeue its jobs once the timeout passes
+    Drain {
+        node_id: String,
+        /// Seconds to wait for running jobs (waits indefinitely if omitted)
+        #[arg(long)]
+        timeout: Option<u64>,
+    },
+    /// Let the node take new jobs again
+    Uncordon {
+        node_id: String,
+    },
 }
 
 #[tokio::main]
@@ -27,14 +68,68 @@ async fn main() -> anyhow::Result<()> {
     
     let args = Args::parse();
     
+    if let Some(Command::Node { server, action }) = args.command {
+        return node_command(&server, action).await;
+    }
+    
     info!("Starting Zenith Scheduler v{}", zenith_scheduler::VERSION);
-    info!("gRPC: {}", args.grpc_address);
-    info!("HTTP: {}", args.http_address);
     
-    // In production: start gRPC and HTTP servers
-    // For now, just wait
-    tokio::signal::ctrl_c().await?;
+    let config = if Path::new(&args.config).exists() {
+        SchedulerConfig::from_file(&args.config)?
+    } else {
+        info!("No config at {}, using defaults", args.config);
+        SchedulerConfig::default()
+    };
+    
+    let store = Arc::new(StateStore::new(config.state_store())?);
+    let node_registry = Arc::new(NodeRegistry::new(config.heartbeat_timeout_seconds));
+    let scheduler = Arc::new(Scheduler::with_store(node_registry.clone(), (&config).into(), store.clone()));
+    info!("State: {}", config.data_dir.display());
+    
+    let monitor_period = Duration::from_secs(config.monitor_interval_secs);
+    tokio::spawn(scheduler.clone().run_scheduling_loop(Duration::from_millis(config.schedule_interval_ms)));
+    tokio::spawn(scheduler.clone().run_node_monitor(monitor_period));
+    tokio::spawn(scheduler.clone().run_reaper(monitor_period));
+    tokio::spawn(store.clone().run_checkpointer(Duration::from_secs(config.checkpoint_interval_secs)));
+    
+    // Agents and clients use the HTTP API
+    info!("gRPC API not served yet, ignoring {}", args.grpc_address);
+    let router = create_router(Arc::new(AppState { scheduler, node_registry }));
+    let listener = tokio::net::TcpListener::bind(&args.http_address).await?;
+    info!("HTTP: {}", listener.local_addr()?);
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
     
     info!("Shutting down...");
+    store.save()?;
+    Ok(())
+}
+
+/// Send a node request to the scheduler and print the node's new state
+async fn node_command(server: &str, action: NodeAction) -> anyhow::Result<()> {
+    let (node_id, verb, body) = match action {
+        NodeAction::Cordon { node_id } => (node_id, "cordon", serde_json::json!({})),
+        NodeAction::Drain { node_id, timeout } => {
+            (node_id, "drain", serde_json::json!({ "timeout_secs": timeout }))
+        }
+        NodeAction::Uncordon { node_id } => (node_id, "uncordon", serde_json::json!({})),
+    };
+    
+    let url = format!("{}/api/v1/nodes/{}/{}", server.trim_end_matches('/'), node_id, verb);
+    let response = reqwest::Client::new().post(&url).json(&body).send().await?;
+    let status = response.status();
+    let body: serde_json::Value = response.json().await?;
+    if !status.is_success() {
+        anyhow::bail!("{}: {}", status, body["message"].as_str().unwrap_or("request failed"));
+    }
+    
+    let scheduling = &body["scheduling"];
+    match scheduling["deadline"].as_str() {
+        Some(deadline) => println!("{}: {} until {}", node_id, scheduling["state"].as_str().unwrap_or("?"), deadline),
+        None => println!("{}: {}", node_id, scheduling["state"].as_str().unwrap_or("?")),
+    }
     Ok(())
 }
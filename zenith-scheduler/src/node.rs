@@ -1,11 +1,18 @@
 //! Node registry and management
 
+use crate::job::{LocalityPreferences, ResourceRequirements};
 use crate::{Error, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use parking_lot::RwLock;
 use chrono::{DateTime, Utc};
 
+/// Node label naming the rack, used by `prefer_same_rack`
+pub const RACK_LABEL: &str = "rack";
+
+/// Node label naming the NVSwitch domain, used by `prefer_same_nvswitch_domain`
+pub const NVSWITCH_DOMAIN_LABEL: &str = "nvswitch-domain";
+
 /// GPU device information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GpuDevice {
@@ -27,6 +34,47 @@ pub struct GpuDevice {
     pub allocated: bool,
     /// Job ID if allocated
     pub allocated_job_id: Option<String>,
+    /// NUMA node the GPU is attached to, if known
+    #[serde(default)]
+    pub numa_node: Option<u32>,
+}
+
+/// Live readings for one GPU, sent with each agent heartbeat
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GpuStatus {
+    /// Device ID (e.g., "cuda:0")
+    pub device_id: String,
+    /// GPU utilization (0.0-1.0)
+    pub utilization: f32,
+    /// Free memory in bytes
+    pub free_memory: u64,
+    /// Temperature in Celsius
+    pub temperature: i32,
+}
+
+/// NVLink connection between two GPUs on a node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NvLinkConnection {
+    /// Source GPU device ID
+    pub source: String,
+    /// Target GPU device ID
+    pub target: String,
+    /// Number of NVLinks
+    pub link_count: u32,
+    /// Bandwidth in GB/s
+    pub bandwidth_gbps: u32,
+}
+
+impl From<&zenith_runtime_gpu::NvLinkConnection> for NvLinkConnection {
+    /// Convert from device indices to `cuda:<index>` device IDs
+    fn from(connection: &zenith_runtime_gpu::NvLinkConnection) -> Self {
+        Self {
+            source: format!("cuda:{}", connection.source),
+            target: format!("cuda:{}", connection.target),
+            link_count: connection.link_count,
+            bandwidth_gbps: connection.bandwidth_gbps,
+        }
+    }
 }
 
 /// Node topology information
@@ -44,10 +92,113 @@ pub struct NodeTopology {
     pub numa_nodes: u32,
     /// NVLink present
     pub nvlink_present: bool,
+    /// NVLink generation (0 = none or unknown)
+    #[serde(default)]
+    pub nvlink_version: u32,
     /// NVSwitch present
     pub nvswitch_present: bool,
     /// RDMA capable
     pub rdma_capable: bool,
+    /// NVLink connections between GPUs
+    #[serde(default)]
+    pub nvlink_connections: Vec<NvLinkConnection>,
+    /// CPU core IDs per NUMA node; if empty, cores are assumed to be split
+    /// evenly and contiguously across `numa_nodes`
+    #[serde(default)]
+    pub numa_cpu_cores: HashMap<u32, Vec<u32>>,
+}
+
+impl NodeTopology {
+    /// NVLink bandwidth (GB/s) between two GPUs, 0 if not connected
+    pub fn nvlink_bandwidth(&self, a: &str, b: &str) -> u32 {
+        self.nvlink_connections.iter()
+            .filter(|c| (c.source == a && c.target == b) || (c.source == b && c.target == a))
+            .map(|c| c.bandwidth_gbps)
+            .sum()
+    }
+    
+    /// NUMA node of a CPU core
+    pub fn core_numa_node(&self, core: u32) -> u32 {
+        if !self.numa_cpu_cores.is_empty() {
+            return self.numa_cpu_cores.iter()
+                .find(|(_, cores)| cores.contains(&core))
+                .map_or(0, |(numa, _)| *numa);
+        }
+        let per_node = (self.cpu_cores / self.numa_nodes.max(1)).max(1);
+        (core / per_node).min(self.numa_nodes.saturating_sub(1))
+    }
+}
+
+/// CPU cores and memory reserved for a job on one node
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CpuAllocation {
+    /// CPU cores
+    pub cores: u32,
+    /// CPU memory in bytes
+    pub memory: u64,
+    /// Core IDs to pin the job to (empty = not pinned)
+    #[serde(default)]
+    pub cpu_set: Vec<u32>,
+}
+
+impl From<&ResourceRequirements> for CpuAllocation {
+    fn from(resources: &ResourceRequirements) -> Self {
+        Self {
+            cores: resources.cpu_cores,
+            memory: resources.cpu_memory,
+            cpu_set: vec![],
+        }
+    }
+}
+
+/// Why a node cannot host (part of) a job
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FitError {
+    /// Job needs an NVSwitch fabric
+    #[error("no NVSwitch")]
+    NoNvswitch,
+    /// Job needs RDMA networking
+    #[error("not RDMA capable")]
+    NoRdma,
+    /// NVLink missing or too old
+    #[error("NVLink version {available} < required {required}")]
+    NvlinkVersion {
+        /// Node NVLink version
+        available: u32,
+        /// Required version
+        required: u32,
+    },
+    /// Not enough unreserved CPU cores
+    #[error("{available} free CPU cores < {requested} requested")]
+    CpuCores {
+        /// Unreserved cores
+        available: u32,
+        /// Requested cores
+        requested: u32,
+    },
+    /// Not enough unreserved CPU memory
+    #[error("{available} bytes free memory < {requested} requested")]
+    Memory {
+        /// Unreserved bytes
+        available: u64,
+        /// Requested bytes
+        requested: u64,
+    },
+    /// No free GPU of a requested model
+    #[error("no free GPU matching {0:?}")]
+    GpuModel(Vec<String>),
+    /// Free GPUs of the right model are too small
+    #[error("no free GPU with {0} bytes of memory")]
+    GpuMemory(u64),
+    /// All GPUs are allocated
+    #[error("no free GPUs")]
+    NoFreeGpus,
+    /// Listed in the job's `excluded_nodes`
+    #[error("excluded by job")]
+    Excluded,
+    /// Labels do not match a node selector
+    #[error("does not match selector {0}")]
+    Selector(String),
 }
 
 /// Node health status
@@ -63,6 +214,25 @@ pub enum NodeHealth {
     Unreachable,
 }
 
+/// Operator control over placing new jobs on a node
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum NodeSchedulingState {
+    /// Accepts new jobs
+    #[default]
+    Schedulable,
+    /// Takes no new jobs; running jobs are left alone
+    Cordoned,
+    /// Takes no new jobs; running jobs are suspended and requeued once
+    /// the deadline passes (never, if there is none)
+    Draining {
+        /// When to stop waiting for running jobs
+        deadline: Option<DateTime<Utc>>,
+    },
+    /// Drain finished: cordoned with no jobs left
+    Drained,
+}
+
 /// A compute node in the cluster
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Node {
@@ -86,6 +256,16 @@ pub struct Node {
     pub labels: HashMap<String, String>,
     /// Running job IDs
     pub running_jobs: Vec<String>,
+    /// CPU cores and memory reserved per job ID
+    #[serde(default)]
+    pub cpu_allocations: HashMap<String, CpuAllocation>,
+    /// Cordon / drain state set by operators
+    #[serde(default)]
+    pub scheduling: NodeSchedulingState,
+    /// Jobs the agent was told to stop; they keep their resources until
+    /// the agent confirms
+    #[serde(default)]
+    pub stopping_jobs: Vec<String>,
 }
 
 impl Node {
@@ -103,6 +283,9 @@ impl Node {
             last_heartbeat: now,
             labels: HashMap::new(),
             running_jobs: vec![],
+            cpu_allocations: HashMap::new(),
+            scheduling: NodeSchedulingState::Schedulable,
+            stopping_jobs: vec![],
         }
     }
     
@@ -118,6 +301,169 @@ impl Node {
         self.topology.gpus.len()
     }
     
+    /// CPU cores not reserved by jobs
+    pub fn available_cpu_cores(&self) -> u32 {
+        let reserved: u32 = self.cpu_allocations.values().map(|a| a.cores).sum();
+        self.topology.cpu_cores.saturating_sub(reserved)
+    }
+    
+    /// CPU memory (bytes) not reserved by jobs
+    pub fn available_memory(&self) -> u64 {
+        let reserved: u64 = self.cpu_allocations.values().map(|a| a.memory).sum();
+        self.topology.cpu_memory.saturating_sub(reserved)
+    }
+    
+    /// Check whether a GPU satisfies the per-device requirements
+    pub fn gpu_matches(gpu: &GpuDevice, resources: &ResourceRequirements) -> bool {
+        let model_ok = resources.required_gpu_models.is_empty()
+            || resources.required_gpu_models.iter()
+                .any(|m| gpu.device_name.to_lowercase().contains(&m.to_lowercase()));
+        model_ok && gpu.total_memory >= resources.gpu_memory_per_device
+    }
+    
+    /// Free GPUs that satisfy the per-device requirements
+    pub fn eligible_gpus(&self, resources: &ResourceRequirements) -> Vec<&GpuDevice> {
+        self.topology.gpus.iter()
+            .filter(|g| !g.allocated && Self::gpu_matches(g, resources))
+            .collect()
+    }
+    
+    /// Choose `count` eligible GPUs, preferring a subset on a single NUMA
+    /// node and then the one with the most NVLink bandwidth between its
+    /// members. Greedy from every seed GPU, so it stays cheap on big nodes.
+    pub fn select_gpus(&self, resources: &ResourceRequirements, count: usize) -> Option<Vec<String>> {
+        let eligible = self.eligible_gpus(resources);
+        if eligible.len() < count {
+            return None;
+        }
+        if count == 0 {
+            return Some(vec![]);
+        }
+        
+        let topology = &self.topology;
+        let bandwidth = |a: usize, chosen: &[usize]| -> u64 {
+            chosen.iter()
+                .map(|&b| topology.nvlink_bandwidth(&eligible[a].device_id, &eligible[b].device_id) as u64)
+                .sum()
+        };
+        
+        // (NUMA nodes spanned, total pairwise bandwidth, members)
+        let mut best: Option<(usize, u64, Vec<usize>)> = None;
+        for seed in 0..eligible.len() {
+            let seed_numa = eligible[seed].numa_node;
+            let mut chosen = vec![seed];
+            let mut total = 0;
+            
+            while chosen.len() < count {
+                let next = (0..eligible.len())
+                    .filter(|i| !chosen.contains(i))
+                    .max_by_key(|&i| {
+                        (eligible[i].numa_node == seed_numa, bandwidth(i, &chosen), std::cmp::Reverse(i))
+                    })?;
+                total += bandwidth(next, &chosen);
+                chosen.push(next);
+            }
+            
+            let mut numa: Vec<Option<u32>> = chosen.iter().map(|&i| eligible[i].numa_node).collect();
+            numa.sort();
+            numa.dedup();
+            
+            let better = match &best {
+                None => true,
+                Some((spans, bw, _)) => numa.len() < *spans || (numa.len() == *spans && total > *bw),
+            };
+            if better {
+                best = Some((numa.len(), total, chosen));
+            }
+        }
+        
+        best.map(|(_, _, mut chosen)| {
+            chosen.sort();
+            chosen.into_iter().map(|i| eligible[i].device_id.clone()).collect()
+        })
+    }
+    
+    /// Choose `count` unpinned CPU cores, preferring the NUMA nodes of the
+    /// given GPUs. Returns an empty set if not enough unpinned cores remain
+    /// (e.g. when some jobs hold cores without pinning).
+    pub fn select_cpu_cores(&self, count: u32, gpu_ids: &[String]) -> Vec<u32> {
+        let numa: Vec<u32> = self.topology.gpus.iter()
+            .filter(|g| gpu_ids.contains(&g.device_id))
+            .filter_map(|g| g.numa_node)
+            .collect();
+        
+        let pinned: Vec<u32> = self.cpu_allocations.values()
+            .flat_map(|a| a.cpu_set.iter().copied())
+            .collect();
+        let mut free: Vec<u32> = (0..self.topology.cpu_cores)
+            .filter(|c| !pinned.contains(c))
+            .collect();
+        if free.len() < count as usize {
+            return vec![];
+        }
+        
+        // Stable sort keeps cores ascending within each group
+        free.sort_by_key(|&c| !numa.contains(&self.topology.core_numa_node(c)));
+        free.truncate(count as usize);
+        free.sort();
+        free
+    }
+    
+    /// Check the job's hard locality constraints: excluded nodes and label
+    /// selectors
+    pub fn check_locality(&self, locality: &LocalityPreferences) -> std::result::Result<(), FitError> {
+        if locality.excluded_nodes.contains(&self.id) {
+            return Err(FitError::Excluded);
+        }
+        match locality.node_selector.iter().find(|s| !s.matches(&self.labels)) {
+            Some(selector) => Err(FitError::Selector(selector.to_string())),
+            None => Ok(()),
+        }
+    }
+    
+    /// Check the node-wide requirements (fabric, CPU cores, memory) and, for
+    /// GPU jobs, that at least one free GPU qualifies. CPU cores and memory
+    /// are requested per node.
+    pub fn check_fit(&self, resources: &ResourceRequirements) -> std::result::Result<(), FitError> {
+        let topology = &self.topology;
+        
+        if resources.require_nvswitch && !topology.nvswitch_present {
+            return Err(FitError::NoNvswitch);
+        }
+        if resources.require_rdma && !topology.rdma_capable {
+            return Err(FitError::NoRdma);
+        }
+        if resources.min_nvlink_version > 0 {
+            let available = if topology.nvlink_present { topology.nvlink_version } else { 0 };
+            if available < resources.min_nvlink_version {
+                return Err(FitError::NvlinkVersion { available, required: resources.min_nvlink_version });
+            }
+        }
+        
+        let available = self.available_cpu_cores();
+        if available < resources.cpu_cores {
+            return Err(FitError::CpuCores { available, requested: resources.cpu_cores });
+        }
+        let available = self.available_memory();
+        if available < resources.cpu_memory {
+            return Err(FitError::Memory { available, requested: resources.cpu_memory });
+        }
+        
+        if resources.gpu_count > 0 && self.eligible_gpus(resources).is_empty() {
+            let free: Vec<&GpuDevice> = self.topology.gpus.iter().filter(|g| !g.allocated).collect();
+            if free.is_empty() {
+                return Err(FitError::NoFreeGpus);
+            }
+            let model_only = ResourceRequirements { gpu_memory_per_device: 0, ..resources.clone() };
+            if !free.iter().any(|g| Self::gpu_matches(g, &model_only)) {
+                return Err(FitError::GpuModel(resources.required_gpu_models.clone()));
+            }
+            return Err(FitError::GpuMemory(resources.gpu_memory_per_device));
+        }
+        
+        Ok(())
+    }
+    
     /// Update heartbeat
     pub fn heartbeat(&mut self) {
         self.last_heartbeat = Utc::now();
@@ -154,6 +500,88 @@ impl Node {
         Ok(allocated_ids)
     }
     
+    /// Allocate specific GPUs (by device ID) to a job
+    pub fn allocate_gpu_ids(&mut self, job_id: &str, gpu_ids: &[String]) -> Result<()> {
+        for gpu_id in gpu_ids {
+            match self.topology.gpus.iter().find(|g| &g.device_id == gpu_id) {
+                Some(gpu) if gpu.allocated => {
+                    return Err(Error::Node(format!(
+                        "GPU {} on node {} already allocated to {}",
+                        gpu_id, self.id, gpu.allocated_job_id.as_deref().unwrap_or("unknown")
+                    )));
+                }
+                Some(_) => {}
+                None => {
+                    return Err(Error::Node(format!("GPU {} not found on node {}", gpu_id, self.id)));
+                }
+            }
+        }
+        
+        for gpu in self.topology.gpus.iter_mut().filter(|g| gpu_ids.contains(&g.device_id)) {
+            gpu.allocated = true;
+            gpu.allocated_job_id = Some(job_id.to_string());
+        }
+        
+        if !self.running_jobs.iter().any(|id| id == job_id) {
+            self.running_jobs.push(job_id.to_string());
+        }
+        Ok(())
+    }
+    
+    /// Reserve CPU cores and memory for a job
+    pub fn reserve_cpu(&mut self, job_id: &str, cpu: CpuAllocation) -> Result<()> {
+        let current = self.cpu_allocations.get(job_id).cloned().unwrap_or_default();
+        let cores = self.available_cpu_cores() + current.cores;
+        let memory = self.available_memory() + current.memory;
+        
+        if cpu.cores > cores || cpu.memory > memory {
+            return Err(Error::Node(format!(
+                "Not enough CPU on node {}: requested {} cores / {} bytes, available {} / {}",
+                self.id, cpu.cores, cpu.memory, cores, memory
+            )));
+        }
+        
+        let taken = self.cpu_allocations.iter()
+            .filter(|(id, _)| id.as_str() != job_id)
+            .flat_map(|(_, a)| a.cpu_set.iter())
+            .find(|c| cpu.cpu_set.contains(c));
+        if let Some(core) = taken {
+            return Err(Error::Node(format!("CPU core {} on node {} already pinned", core, self.id)));
+        }
+        
+        self.cpu_allocations.insert(job_id.to_string(), cpu);
+        Ok(())
+    }
+    
+    /// Undo one step of `NodeRegistry::commit_allocation`: free `gpu_ids` and
+    /// put back the CPU share the job held before it
+    fn undo_allocation(&mut self, job_id: &str, gpu_ids: &[String], previous_cpu: Option<CpuAllocation>) {
+        for gpu in self.topology.gpus.iter_mut().filter(|g| gpu_ids.contains(&g.device_id)) {
+            gpu.allocated = false;
+            gpu.allocated_job_id = None;
+        }
+        match previous_cpu {
+            Some(cpu) => {
+                self.cpu_allocations.insert(job_id.to_string(), cpu);
+            }
+            None => {
+                self.cpu_allocations.remove(job_id);
+            }
+        }
+        
+        let holds_gpus = self.topology.gpus.iter().any(|g| g.allocated_job_id.as_deref() == Some(job_id));
+        if !holds_gpus && !self.cpu_allocations.contains_key(job_id) {
+            self.running_jobs.retain(|id| id != job_id);
+        }
+    }
+    
+    /// Release GPUs, CPU cores and memory held by a job
+    pub fn release_job(&mut self, job_id: &str) {
+        self.release_gpus(job_id);
+        self.cpu_allocations.remove(job_id);
+        self.stopping_jobs.retain(|id| id != job_id);
+    }
+    
     /// Release GPUs from a job
     pub fn release_gpus(&mut self, job_id: &str) {
         for gpu in &mut self.topology.gpus {
@@ -207,11 +635,169 @@ impl NodeRegistry {
         }
     }
     
+    /// Record a heartbeat with the node's current GPU readings and free CPU
+    /// memory, returning the updated node
+    ///
+    /// Unlike `update`, this leaves the topology and GPU allocations alone.
+    /// Unreachable nodes must register again instead.
+    pub fn heartbeat(&self, node_id: &str, gpus: &[GpuStatus], cpu_memory_free: u64) -> Result<Node> {
+        let mut nodes = self.nodes.write();
+        let node = nodes.get_mut(node_id)
+            .ok_or_else(|| Error::Node(format!("Node not found: {}", node_id)))?;
+        if node.health == NodeHealth::Unreachable {
+            return Err(Error::Node(format!("Node {} is unreachable and must register again", node_id)));
+        }
+        
+        for status in gpus {
+            if let Some(gpu) = node.topology.gpus.iter_mut().find(|g| g.device_id == status.device_id) {
+                gpu.utilization = status.utilization;
+                gpu.free_memory = status.free_memory;
+                gpu.temperature = status.temperature;
+            }
+        }
+        node.topology.cpu_memory_free = cpu_memory_free;
+        node.heartbeat();
+        Ok(node.clone())
+    }
+    
+    /// Commit a job's allocation (node ID -> GPU device IDs) to the nodes,
+    /// reserving each node's entry in `cpu` (none if missing).
+    ///
+    /// All-or-nothing: if any node rejects its share, the nodes already
+    /// updated are rolled back and the error is returned.
+    pub fn commit_allocation(
+        &self,
+        job_id: &str,
+        allocations: &HashMap<String, Vec<String>>,
+        cpu: &HashMap<String, CpuAllocation>,
+    ) -> Result<()> {
+        let mut nodes = self.nodes.write();
+        // CPU share each node held for the job before this commit, so a
+        // failure leaves earlier allocations (e.g. before elastic growth) alone
+        let mut committed: Vec<(&String, Option<CpuAllocation>)> = vec![];
+        
+        for (node_id, gpu_ids) in allocations {
+            let result = match nodes.get_mut(node_id) {
+                Some(node) => {
+                    let previous = node.cpu_allocations.get(job_id).cloned();
+                    // `allocate_gpu_ids` takes nothing when it fails
+                    node.reserve_cpu(job_id, cpu.get(node_id).cloned().unwrap_or_default())
+                        .and_then(|_| node.allocate_gpu_ids(job_id, gpu_ids))
+                        .inspect_err(|_| node.undo_allocation(job_id, &[], previous.clone()))
+                        .map(|_| previous)
+                }
+                None => Err(Error::Node(format!("Node not found: {}", node_id))),
+            };
+            
+            match result {
+                Ok(previous) => committed.push((node_id, previous)),
+                Err(e) => {
+                    for (node_id, previous) in committed {
+                        if let Some(node) = nodes.get_mut(node_id) {
+                            node.undo_allocation(job_id, &allocations[node_id], previous);
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Release everything held by a job on all nodes
+    pub fn release_job(&self, job_id: &str) {
+        let mut nodes = self.nodes.write();
+        for node in nodes.values_mut() {
+            node.release_job(job_id);
+        }
+    }
+    
+    /// Ask the agents of every node holding a job to stop it; the job's
+    /// resources stay allocated until `confirm_stopped`
+    pub fn stop_job(&self, job_id: &str) {
+        let mut nodes = self.nodes.write();
+        for node in nodes.values_mut() {
+            if node.running_jobs.iter().any(|id| id == job_id)
+                && !node.stopping_jobs.iter().any(|id| id == job_id)
+            {
+                node.stopping_jobs.push(job_id.to_string());
+            }
+        }
+    }
+    
+    /// Release the jobs an agent reports stopped, returning those it had
+    /// been asked to stop
+    pub fn confirm_stopped(&self, node_id: &str, job_ids: &[String]) -> Result<Vec<String>> {
+        let mut nodes = self.nodes.write();
+        let node = nodes.get_mut(node_id)
+            .ok_or_else(|| Error::Node(format!("Node not found: {}", node_id)))?;
+        let confirmed: Vec<String> = node.stopping_jobs.iter()
+            .filter(|id| job_ids.contains(id))
+            .cloned()
+            .collect();
+        for job_id in &confirmed {
+            node.release_job(job_id);
+        }
+        Ok(confirmed)
+    }
+    
+    /// Release the jobs being stopped on an unreachable node, whose agent
+    /// can no longer confirm, returning them
+    pub fn forget_stopping(&self, node_id: &str) -> Vec<String> {
+        let mut nodes = self.nodes.write();
+        let Some(node) = nodes.get_mut(node_id) else {
+            return vec![];
+        };
+        let stopping = node.stopping_jobs.clone();
+        for job_id in &stopping {
+            node.release_job(job_id);
+        }
+        stopping
+    }
+    
+    /// Whether any node is still waiting for its agent to stop a job
+    pub fn is_stopping(&self, job_id: &str) -> bool {
+        self.nodes.read().values().any(|n| n.stopping_jobs.iter().any(|id| id == job_id))
+    }
+    
+    /// Every job some agent has been asked to stop but not confirmed
+    pub fn stopping_jobs(&self) -> Vec<String> {
+        let mut stopping: Vec<String> = self.nodes.read().values()
+            .flat_map(|n| n.stopping_jobs.iter().cloned())
+            .collect();
+        stopping.sort();
+        stopping.dedup();
+        stopping
+    }
+    
+    /// Mark nodes whose heartbeat is older than the timeout `Unreachable`,
+    /// returning the IDs of those newly marked
+    pub fn mark_unreachable(&self) -> Vec<String> {
+        let mut nodes = self.nodes.write();
+        let mut marked = vec![];
+        for node in nodes.values_mut() {
+            if node.health != NodeHealth::Unreachable && node.is_stale(self.heartbeat_timeout_seconds) {
+                node.health = NodeHealth::Unreachable;
+                node.health_message = format!(
+                    "No heartbeat since {}", node.last_heartbeat.format("%Y-%m-%d %H:%M:%S UTC")
+                );
+                marked.push(node.id.clone());
+            }
+        }
+        marked
+    }
+    
     /// Get a node by ID
     pub fn get(&self, node_id: &str) -> Option<Node> {
         self.nodes.read().get(node_id).cloned()
     }
     
+    /// Get all nodes, whatever their health
+    pub fn all_nodes(&self) -> Vec<Node> {
+        self.nodes.read().values().cloned().collect()
+    }
+    
     /// Get all healthy nodes
     pub fn healthy_nodes(&self) -> Vec<Node> {
         self.nodes.read()
@@ -221,6 +807,23 @@ impl NodeRegistry {
             .collect()
     }
     
+    /// Get healthy nodes that are not cordoned or draining
+    pub fn schedulable_nodes(&self) -> Vec<Node> {
+        self.healthy_nodes()
+            .into_iter()
+            .filter(|n| n.scheduling == NodeSchedulingState::Schedulable)
+            .collect()
+    }
+    
+    /// Set a node's cordon / drain state, returning the updated node
+    pub fn set_scheduling(&self, node_id: &str, scheduling: NodeSchedulingState) -> Result<Node> {
+        let mut nodes = self.nodes.write();
+        let node = nodes.get_mut(node_id)
+            .ok_or_else(|| Error::Node(format!("Node not found: {}", node_id)))?;
+        node.scheduling = scheduling;
+        Ok(node.clone())
+    }
+    
     /// Get nodes with available GPUs
     pub fn nodes_with_available_gpus(&self, count: usize) -> Vec<Node> {
         self.healthy_nodes()
@@ -276,6 +879,7 @@ mod tests {
             temperature: 40,
             allocated: false,
             allocated_job_id: None,
+            numa_node: Some(0),
         };
         
         let topology = NodeTopology {
@@ -285,8 +889,11 @@ mod tests {
             cpu_memory_free: 500 * 1024 * 1024 * 1024,
             numa_nodes: 2,
             nvlink_present: true,
+            nvlink_version: 3,
             nvswitch_present: false,
             rdma_capable: true,
+            nvlink_connections: vec![],
+            numa_cpu_cores: HashMap::new(),
         };
         
         Node::new(
@@ -330,4 +937,124 @@ mod tests {
         assert_eq!(summary.total_nodes, 1);
         assert_eq!(summary.total_gpus, 1);
     }
+    
+    #[test]
+    fn test_commit_allocation_rollback() {
+        let registry = NodeRegistry::new(60);
+        registry.register(create_test_node()).unwrap();
+        
+        // Second node share refers to a node that doesn't exist
+        let allocations = HashMap::from([
+            ("node-1".to_string(), vec!["cuda:0".to_string()]),
+            ("node-2".to_string(), vec!["cuda:0".to_string()]),
+        ]);
+        let cpu = HashMap::from([
+            ("node-1".to_string(), CpuAllocation { cores: 8, memory: 1 << 30, cpu_set: vec![] }),
+        ]);
+        assert!(registry.commit_allocation("job-1", &allocations, &cpu).is_err());
+        
+        let node = registry.get("node-1").unwrap();
+        assert_eq!(node.available_gpus(), 1);
+        assert!(node.running_jobs.is_empty());
+        assert_eq!(node.available_cpu_cores(), 64);
+        
+        // Double-booking is rejected
+        let allocations = HashMap::from([("node-1".to_string(), vec!["cuda:0".to_string()])]);
+        registry.commit_allocation("job-1", &allocations, &cpu).unwrap();
+        assert!(registry.commit_allocation("job-2", &allocations, &cpu).is_err());
+        assert_eq!(registry.get("node-1").unwrap().available_cpu_cores(), 56);
+        
+        // A failed growth step keeps what the job already held
+        let grown = HashMap::from([("node-1".to_string(), CpuAllocation { cores: 16, ..cpu["node-1"].clone() })]);
+        assert!(registry.commit_allocation("job-1", &allocations, &grown).is_err());
+        let node = registry.get("node-1").unwrap();
+        assert_eq!(node.topology.gpus[0].allocated_job_id.as_deref(), Some("job-1"));
+        assert_eq!(node.running_jobs, vec!["job-1".to_string()]);
+        assert_eq!(node.available_cpu_cores(), 56);
+        
+        registry.release_job("job-1");
+        let node = registry.get("node-1").unwrap();
+        assert_eq!(node.available_gpus(), 1);
+        assert_eq!(node.available_cpu_cores(), 64);
+    }
+    
+    #[test]
+    fn test_check_fit_reasons() {
+        let node = create_test_node();
+        let ok = ResourceRequirements {
+            gpu_count: 1,
+            gpu_memory_per_device: 40 * 1024 * 1024 * 1024,
+            required_gpu_models: vec!["a100".to_string()],
+            min_nvlink_version: 3,
+            require_rdma: true,
+            ..Default::default()
+        };
+        assert_eq!(node.check_fit(&ok), Ok(()));
+        
+        let cases = [
+            (ResourceRequirements { require_nvswitch: true, ..ok.clone() }, FitError::NoNvswitch),
+            (ResourceRequirements { min_nvlink_version: 4, ..ok.clone() },
+                FitError::NvlinkVersion { available: 3, required: 4 }),
+            (ResourceRequirements { cpu_cores: 128, ..ok.clone() },
+                FitError::CpuCores { available: 64, requested: 128 }),
+            (ResourceRequirements { required_gpu_models: vec!["H100".to_string()], ..ok.clone() },
+                FitError::GpuModel(vec!["H100".to_string()])),
+            (ResourceRequirements { gpu_memory_per_device: 96 * 1024 * 1024 * 1024, ..ok.clone() },
+                FitError::GpuMemory(96 * 1024 * 1024 * 1024)),
+        ];
+        for (resources, expected) in cases {
+            assert_eq!(node.check_fit(&resources), Err(expected));
+        }
+        
+        let mut busy = node.clone();
+        busy.allocate_gpus("job-1", 1).unwrap();
+        assert_eq!(busy.check_fit(&ok), Err(FitError::NoFreeGpus));
+        
+        busy.reserve_cpu("job-1", CpuAllocation { cores: 60, memory: 0, cpu_set: vec![] }).unwrap();
+        assert!(busy.reserve_cpu("job-2", CpuAllocation { cores: 8, memory: 0, cpu_set: vec![] }).is_err());
+        let cpu_only = ResourceRequirements { gpu_count: 0, cpu_cores: 8, ..Default::default() };
+        assert_eq!(busy.check_fit(&cpu_only), Err(FitError::CpuCores { available: 4, requested: 8 }));
+    }
+    
+    fn dgx_node() -> Node {
+        let mut node = create_test_node();
+        node.topology.gpus = (0..8)
+            .map(|i| GpuDevice {
+                device_id: format!("cuda:{}", i),
+                numa_node: Some(i / 4),
+                ..node.topology.gpus[0].clone()
+            })
+            .collect();
+        
+        // Strong links 1-2 and 2-3, a weak 1-3 link, and 3-4 across sockets
+        for (a, b, bandwidth_gbps) in [(1, 2, 600), (2, 3, 600), (1, 3, 100), (3, 4, 600), (5, 6, 50)] {
+            node.topology.nvlink_connections.push(NvLinkConnection {
+                source: format!("cuda:{}", a),
+                target: format!("cuda:{}", b),
+                link_count: 12,
+                bandwidth_gbps,
+            });
+        }
+        node
+    }
+    
+    #[test]
+    fn test_select_gpus_nvlink_and_numa() {
+        let mut node = dgx_node();
+        node.allocate_gpu_ids("busy", &["cuda:0".to_string()]).unwrap();
+        let resources = ResourceRequirements::default();
+        
+        assert_eq!(node.select_gpus(&resources, 2).unwrap(), vec!["cuda:1", "cuda:2"]);
+        // The 3-4 link is fast but crosses NUMA nodes
+        assert_eq!(node.select_gpus(&resources, 3).unwrap(), vec!["cuda:1", "cuda:2", "cuda:3"]);
+        assert_eq!(node.select_gpus(&resources, 4).unwrap(), vec!["cuda:4", "cuda:5", "cuda:6", "cuda:7"]);
+        assert!(node.select_gpus(&resources, 8).is_none());
+        
+        // Cores come from the GPUs' NUMA node (cores 32-63 on a 64-core, 2-socket node)
+        let cores = node.select_cpu_cores(4, &["cuda:5".to_string()]);
+        assert_eq!(cores, vec![32, 33, 34, 35]);
+        node.reserve_cpu("job-1", CpuAllocation { cores: 4, memory: 0, cpu_set: cores.clone() }).unwrap();
+        assert_eq!(node.select_cpu_cores(2, &["cuda:6".to_string()]), vec![36, 37]);
+        assert!(node.reserve_cpu("job-2", CpuAllocation { cores: 1, memory: 0, cpu_set: vec![33] }).is_err());
+    }
 }
@@ -0,0 +1,102 @@
+//! Partitions
+//!
+//! Named queues that own a subset of nodes (selected by label) and set
+//! runtime limits, a priority tier and who may submit to them.
+
+use crate::job::{Job, LabelSelector};
+use crate::node::Node;
+use crate::quota::ResourceUsage;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+/// Partition used when a job does not name one
+pub const DEFAULT_PARTITION: &str = "default";
+
+/// A named queue and the policy applied to its jobs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PartitionConfig {
+    /// Queue name, matched against `SchedulingPolicy::queue_name`
+    pub name: String,
+    /// Nodes belonging to the partition (empty = all nodes)
+    pub node_selector: Vec<LabelSelector>,
+    /// Runtime limit for jobs that set none (seconds, 0 = use `max_runtime_secs`)
+    pub default_runtime_secs: u64,
+    /// Longest runtime a job may request (seconds, 0 = unlimited)
+    pub max_runtime_secs: u64,
+    /// Partitions with a higher tier are scheduled first
+    pub priority_tier: i32,
+    /// Users allowed to submit (empty = everyone)
+    pub allowed_users: Vec<String>,
+    /// Projects allowed to submit (empty = every project)
+    pub allowed_projects: Vec<String>,
+}
+
+impl Default for PartitionConfig {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_PARTITION.to_string(),
+            node_selector: vec![],
+            default_runtime_secs: 0,
+            max_runtime_secs: 0,
+            priority_tier: 0,
+            allowed_users: vec![],
+            allowed_projects: vec![],
+        }
+    }
+}
+
+impl PartitionConfig {
+    /// Does the node belong to this partition?
+    pub fn contains(&self, node: &Node) -> bool {
+        self.node_selector.iter().all(|selector| selector.matches(&node.labels))
+    }
+
+    /// Check that the job may be submitted here and fill in the default
+    /// runtime limit
+    pub fn admit(&self, job: &mut Job) -> Result<()> {
+        let descriptor = &job.descriptor;
+        if !self.allowed_users.is_empty() && !self.allowed_users.contains(&descriptor.user_id) {
+            return Err(Error::Job(format!(
+                "User {} may not submit to queue {}", descriptor.user_id, self.name
+            )));
+        }
+        if !self.allowed_projects.is_empty() && !self.allowed_projects.contains(&descriptor.project_id) {
+            return Err(Error::Job(format!(
+                "Project {} may not submit to queue {}", descriptor.project_id, self.name
+            )));
+        }
+
+        let policy = &mut job.descriptor.policy;
+        if policy.max_runtime_seconds == 0 {
+            policy.max_runtime_seconds = if self.default_runtime_secs > 0 {
+                self.default_runtime_secs
+            } else {
+                self.max_runtime_secs
+            };
+        }
+        if self.max_runtime_secs > 0 && policy.max_runtime_seconds > self.max_runtime_secs {
+            return Err(Error::Job(format!(
+                "Max runtime of {}s exceeds the {}s limit of queue {}",
+                policy.max_runtime_seconds, self.max_runtime_secs, self.name
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Depth and usage of one partition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueStats {
+    /// Queue name
+    pub name: String,
+    /// Priority tier
+    pub priority_tier: i32,
+    /// Healthy nodes in the partition
+    pub nodes: usize,
+    /// Jobs waiting in the queue
+    pub depth: usize,
+    /// Resources held by the partition's scheduled and running jobs
+    pub usage: ResourceUsage,
+}
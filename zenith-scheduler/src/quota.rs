@@ -0,0 +1,292 @@
+//! Quotas and Fair Share
+//!
+//! Per-user and per-project limits on concurrently allocated resources, and
+//! a fair-share priority component computed from exponentially decayed usage.
+
+use crate::job::{Job, JobState};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Limits on resources held by running jobs (`None` = unlimited)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuotaLimits {
+    /// Maximum GPUs
+    pub max_gpus: Option<u32>,
+    /// Maximum CPU cores
+    pub max_cpu_cores: Option<u32>,
+    /// Maximum CPU memory in bytes
+    pub max_memory: Option<u64>,
+    /// Maximum scheduled or running jobs
+    pub max_running_jobs: Option<u32>,
+}
+
+/// Quota and fair-share configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuotaConfig {
+    /// Limits per user ID
+    pub users: HashMap<String, QuotaLimits>,
+    /// Limits per project ID
+    pub projects: HashMap<String, QuotaLimits>,
+    /// Limits for users without an entry
+    pub default_user: QuotaLimits,
+    /// Limits for projects without an entry
+    pub default_project: QuotaLimits,
+    /// Fair-share ordering
+    pub fair_share: FairShareConfig,
+}
+
+impl QuotaConfig {
+    /// Limits that apply to a user
+    pub fn user_limits(&self, user_id: &str) -> &QuotaLimits {
+        self.users.get(user_id).unwrap_or(&self.default_user)
+    }
+
+    /// Limits that apply to a project
+    pub fn project_limits(&self, project_id: &str) -> &QuotaLimits {
+        self.projects.get(project_id).unwrap_or(&self.default_project)
+    }
+}
+
+/// Fair-share configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FairShareConfig {
+    /// Priority points taken from a user/project holding all recent usage
+    /// (0 disables fair share)
+    pub weight: f64,
+    /// Usage half-life in seconds
+    pub half_life_secs: u64,
+    /// Charge per GPU per second
+    pub gpu_weight: f64,
+    /// Charge per CPU core per second
+    pub cpu_weight: f64,
+}
+
+impl Default for FairShareConfig {
+    fn default() -> Self {
+        Self {
+            weight: 100.0,
+            half_life_secs: 24 * 3600, // 1 day
+            gpu_weight: 1.0,
+            cpu_weight: 0.05,
+        }
+    }
+}
+
+/// Resources held by scheduled and running jobs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// GPUs
+    pub gpus: u32,
+    /// CPU cores
+    pub cpu_cores: u32,
+    /// CPU memory in bytes
+    pub memory: u64,
+    /// Scheduled or running jobs
+    pub running_jobs: u32,
+}
+
+impl ResourceUsage {
+    /// Sum the usage of active jobs matching `filter`
+    pub fn of<'a>(jobs: impl IntoIterator<Item = &'a Job>, filter: impl Fn(&Job) -> bool) -> Self {
+        jobs.into_iter()
+            .filter(|j| is_active(j) && filter(j))
+            .fold(Self::default(), |mut usage, job| {
+                usage.add(job);
+                usage
+            })
+    }
+
+    /// Add a job's resources: GPUs actually held (elastic jobs may hold
+    /// more than `gpu_count`), otherwise the request
+    pub fn add(&mut self, job: &Job) {
+        let resources = &job.descriptor.resources;
+        self.gpus += held_gpus(job);
+        self.cpu_cores += resources.cpu_cores;
+        self.memory += resources.cpu_memory;
+        self.running_jobs += 1;
+    }
+}
+
+/// GPUs a job holds, or requests while it holds none yet
+fn held_gpus(job: &Job) -> u32 {
+    match job.allocated_gpu_count() as u32 {
+        0 => job.descriptor.resources.gpu_count,
+        held => held,
+    }
+}
+
+impl QuotaLimits {
+    /// Check whether starting `job` on top of `usage` stays within limits;
+    /// the error explains which limit would be exceeded
+    pub fn check(&self, usage: &ResourceUsage, job: &Job) -> std::result::Result<(), String> {
+        let resources = &job.descriptor.resources;
+        let checks = [
+            ("GPU", self.max_gpus.map(u64::from), usage.gpus as u64, resources.gpu_count as u64),
+            ("CPU core", self.max_cpu_cores.map(u64::from), usage.cpu_cores as u64, resources.cpu_cores as u64),
+            ("memory", self.max_memory, usage.memory, resources.cpu_memory),
+            ("running job", self.max_running_jobs.map(u64::from), usage.running_jobs as u64, 1),
+        ];
+
+        for (name, limit, used, requested) in checks {
+            if let Some(limit) = limit {
+                if used + requested > limit {
+                    return Err(format!(
+                        "{} quota exceeded ({} in use + {} requested > {})",
+                        name, used, requested, limit
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Exponentially decayed usage per user and project
+#[derive(Debug, Default)]
+pub struct FairShare {
+    users: HashMap<String, f64>,
+    projects: HashMap<String, f64>,
+    last_update: Option<DateTime<Utc>>,
+}
+
+impl FairShare {
+    /// Decay past usage to `now` and charge active jobs for the elapsed time
+    pub fn accrue<'a>(
+        &mut self,
+        config: &FairShareConfig,
+        now: DateTime<Utc>,
+        jobs: impl IntoIterator<Item = &'a Job>,
+    ) {
+        let elapsed = match self.last_update {
+            Some(last) => (now - last).num_milliseconds().max(0) as f64 / 1000.0,
+            None => 0.0,
+        };
+        self.last_update = Some(now);
+        if elapsed == 0.0 {
+            return;
+        }
+
+        let decay = 0.5f64.powf(elapsed / config.half_life_secs.max(1) as f64);
+        for usage in self.users.values_mut().chain(self.projects.values_mut()) {
+            *usage *= decay;
+        }
+
+        for job in jobs.into_iter().filter(|j| is_active(j)) {
+            let charge = elapsed
+                * (held_gpus(job) as f64 * config.gpu_weight
+                    + job.descriptor.resources.cpu_cores as f64 * config.cpu_weight);
+            *self.users.entry(job.descriptor.user_id.clone()).or_default() += charge;
+            *self.projects.entry(job.descriptor.project_id.clone()).or_default() += charge;
+        }
+    }
+
+    /// Decayed usage of a user
+    pub fn user_usage(&self, user_id: &str) -> f64 {
+        self.users.get(user_id).copied().unwrap_or(0.0)
+    }
+
+    /// Decayed usage of a project
+    pub fn project_usage(&self, project_id: &str) -> f64 {
+        self.projects.get(project_id).copied().unwrap_or(0.0)
+    }
+
+    /// Priority penalty (zero or negative) for a job: `weight` times the
+    /// average of its user's and project's share of recent usage
+    pub fn priority_adjustment(&self, config: &FairShareConfig, job: &Job) -> i32 {
+        let share = |map: &HashMap<String, f64>, key: &str| {
+            let total: f64 = map.values().sum();
+            if total <= 0.0 {
+                0.0
+            } else {
+                map.get(key).copied().unwrap_or(0.0) / total
+            }
+        };
+
+        let user = share(&self.users, &job.descriptor.user_id);
+        let project = share(&self.projects, &job.descriptor.project_id);
+        -(config.weight * (user + project) / 2.0).round() as i32
+    }
+}
+
+fn is_active(job: &Job) -> bool {
+    matches!(job.state, JobState::Scheduled | JobState::Running)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::{Actor, JobDescriptor, ResourceRequirements};
+
+    fn job(user: &str, gpus: u32, state: JobState) -> Job {
+        let mut job = Job::new(JobDescriptor {
+            name: "job".to_string(),
+            user_id: user.to_string(),
+            project_id: "project".to_string(),
+            command: "true".to_string(),
+            working_directory: "/".to_string(),
+            resources: ResourceRequirements {
+                gpu_count: gpus,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let path = [JobState::Queued, JobState::Scheduled, JobState::Running];
+        for step in path.iter().take_while(|s| **s != state).chain([&state]) {
+            job.transition(*step, Actor::Scheduler, "test").unwrap();
+        }
+        job
+    }
+
+    #[test]
+    fn test_quota_check() {
+        let jobs = [
+            job("alice", 2, JobState::Running),
+            job("alice", 1, JobState::Queued),
+            job("bob", 4, JobState::Running),
+        ];
+        let usage = ResourceUsage::of(&jobs, |j| j.descriptor.user_id == "alice");
+        assert_eq!(usage.gpus, 2);
+        assert_eq!(usage.running_jobs, 1);
+
+        let limits = QuotaLimits { max_gpus: Some(3), ..Default::default() };
+        assert!(limits.check(&usage, &job("alice", 1, JobState::Queued)).is_ok());
+        let err = limits.check(&usage, &job("alice", 2, JobState::Queued)).unwrap_err();
+        assert!(err.starts_with("GPU quota exceeded"));
+
+        let limits = QuotaLimits { max_running_jobs: Some(1), ..Default::default() };
+        assert!(limits.check(&usage, &job("alice", 0, JobState::Queued)).is_err());
+    }
+
+    #[test]
+    fn test_fair_share_decay() {
+        let config = FairShareConfig { half_life_secs: 100, ..Default::default() };
+        let mut fair_share = FairShare::default();
+        let start = Utc::now();
+        let running = [job("alice", 4, JobState::Running)];
+
+        fair_share.accrue(&config, start, &running);
+        fair_share.accrue(&config, start + chrono::Duration::seconds(100), &running);
+        // 4 GPUs + 1 core at 0.05 for 100s
+        assert!((fair_share.user_usage("alice") - 405.0).abs() < 1e-6);
+
+        // One half-life with nothing running halves it
+        fair_share.accrue(&config, start + chrono::Duration::seconds(200), &[]);
+        assert!((fair_share.user_usage("alice") - 202.5).abs() < 1e-6);
+
+        assert_eq!(fair_share.priority_adjustment(&config, &running[0]), -100);
+        assert_eq!(fair_share.priority_adjustment(&config, &job("bob", 1, JobState::Queued)), -50);
+
+        // An elastic job is charged for the GPUs it grew to
+        let mut grown = job("carol", 2, JobState::Running);
+        grown.allocated_gpus.insert("node-1".to_string(), (0..6).map(|i| format!("cuda:{}", i)).collect());
+        let mut fair_share = FairShare::default();
+        fair_share.accrue(&config, start, [&grown]);
+        fair_share.accrue(&config, start + chrono::Duration::seconds(100), [&grown]);
+        assert!((fair_share.user_usage("carol") - 605.0).abs() < 1e-6);
+    }
+}
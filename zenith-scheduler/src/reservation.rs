@@ -0,0 +1,56 @@
+//! Advance Reservations
+//!
+//! Capacity set aside on a fixed set of nodes for a time window. Only
+//! jobs submitted into the reservation may use those nodes during the
+//! window; other jobs may use them beforehand if they will finish in time.
+//! Jobs inside the reservation are timed out when the window ends.
+//!
+//! Jobs already running on the nodes when the reservation is created are
+//! not evicted when the window opens: reservation jobs wait for their GPUs
+//! like any other queued job. Drain the nodes first to hand them over empty.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A named time window on a set of nodes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reservation {
+    /// Unique name jobs refer to
+    pub name: String,
+    /// Window start
+    pub start: DateTime<Utc>,
+    /// Window end
+    pub end: DateTime<Utc>,
+    /// Reserved node IDs (chosen by the scheduler from `gpu_count` if empty)
+    #[serde(default)]
+    pub nodes: Vec<String>,
+    /// GPUs to reserve when no nodes are named
+    #[serde(default)]
+    pub gpu_count: u32,
+    /// Users who may submit into the reservation (empty = everyone)
+    #[serde(default)]
+    pub allowed_users: Vec<String>,
+}
+
+impl Reservation {
+    /// Is the window open at `at`?
+    pub fn is_active(&self, at: DateTime<Utc>) -> bool {
+        self.start <= at && at < self.end
+    }
+
+    /// Would a job running from `now` until `until` (`None` = no limit)
+    /// overlap the window?
+    pub fn overlaps(&self, now: DateTime<Utc>, until: Option<DateTime<Utc>>) -> bool {
+        self.end > now && until.is_none_or(|until| until > self.start)
+    }
+
+    /// May this user submit into the reservation?
+    pub fn allows(&self, user_id: &str) -> bool {
+        self.allowed_users.is_empty() || self.allowed_users.iter().any(|u| u == user_id)
+    }
+
+    /// Does it cover this node?
+    pub fn covers(&self, node_id: &str) -> bool {
+        self.nodes.iter().any(|n| n == node_id)
+    }
+}
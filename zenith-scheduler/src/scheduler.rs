@@ -1,13 +1,24 @@
 //! Gang Scheduler Implementation
 
-use crate::job::{Job, JobState};
-use crate::node::{Node, NodeRegistry};
+use crate::job::{Actor, ArrayJob, ArrayStatus, DependencyStatus, Job, JobEvent, JobState, ResourceRequirements, WorkflowStep};
+use crate::node::{CpuAllocation, GpuStatus, Node, NodeHealth, NodeRegistry, NodeSchedulingState, NVSWITCH_DOMAIN_LABEL, RACK_LABEL};
+use crate::partition::{PartitionConfig, QueueStats, DEFAULT_PARTITION};
+use crate::quota::{FairShare, QuotaConfig, ResourceUsage};
+use crate::reservation::Reservation;
+use crate::state::{NodeState, StateStore};
 use crate::{Error, Result};
-use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use parking_lot::RwLock;
+use std::time::Duration;
+use parking_lot::{Mutex, RwLock};
 use priority_queue::PriorityQueue;
-use tracing::{debug, info};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+/// Queue ordering: higher priority first, then earlier submission
+type QueueKey = (i32, Reverse<DateTime<Utc>>);
 
 /// Scheduling decision for a job
 #[derive(Debug, Clone)]
@@ -18,6 +29,68 @@ pub struct SchedulingDecision {
     pub allocations: HashMap<String, Vec<String>>,
     /// Was this a gang allocation?
     pub gang_allocated: bool,
+    /// Extra GPUs for an already running elastic job; `allocations` holds
+    /// only the additions
+    pub grown: bool,
+    /// Jobs suspended to make room for this one, in this or earlier
+    /// cycles while their agents stopped them
+    pub preempted: Vec<PreemptionDecision>,
+    /// CPU cores, memory and pinned core IDs per node
+    pub cpu: HashMap<String, CpuAllocation>,
+}
+
+/// A running job suspended in favour of a higher-priority job
+#[derive(Debug, Clone)]
+pub struct PreemptionDecision {
+    /// Preempted job ID
+    pub job_id: String,
+    /// Job that triggered the preemption
+    pub preempted_by: String,
+    /// Node IDs with the GPUs released once the job stopped
+    pub released: HashMap<String, Vec<String>>,
+    /// Human-readable reason, also recorded on the job
+    pub reason: String,
+}
+
+/// Resources held for the highest-priority blocked job (EASY backfill)
+#[derive(Debug, Clone)]
+pub struct BackfillReservation {
+    /// Job the resources are held for
+    pub job_id: String,
+    /// Estimated start from running jobs' `max_runtime_seconds`; `None` if
+    /// it depends on a job without a runtime limit
+    pub start_estimate: Option<DateTime<Utc>>,
+    /// Node IDs with the GPUs held for the job
+    pub allocations: HashMap<String, Vec<String>>,
+    /// CPU cores and memory held for the job per node
+    pub cpu: HashMap<String, CpuAllocation>,
+}
+
+/// What one reaper pass changed
+#[derive(Debug, Clone, Default)]
+pub struct ReapReport {
+    /// Running jobs moved to `Timeout`
+    pub timed_out: Vec<String>,
+    /// Queued jobs failed for exceeding their wait deadline or outliving
+    /// their reservation
+    pub expired: Vec<String>,
+    /// Failed jobs put back in the queue
+    pub retried: Vec<String>,
+}
+
+/// What one `monitor_nodes` pass changed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeMonitorReport {
+    /// Nodes newly marked `Unreachable`
+    pub unreachable: Vec<String>,
+    /// Jobs on unreachable nodes put back in the queue
+    pub requeued: Vec<String>,
+    /// Jobs on unreachable nodes failed for good
+    pub failed: Vec<String>,
+    /// Draining nodes that are now empty
+    pub drained: Vec<String>,
+    /// Jobs suspended and requeued because their node's drain deadline passed
+    pub evicted: Vec<String>,
 }
 
 /// Gang scheduler with topology awareness
@@ -25,24 +98,69 @@ pub struct Scheduler {
     /// Node registry
     nodes: Arc<NodeRegistry>,
     /// Priority queue of pending jobs
-    pending_queue: RwLock<PriorityQueue<String, i32>>,
+    pending_queue: RwLock<PriorityQueue<String, QueueKey>>,
     /// Job storage
     jobs: RwLock<HashMap<String, Job>>,
+    /// Array job parents (their children live in `jobs`)
+    arrays: RwLock<HashMap<String, ArrayJob>>,
+    /// Advance reservations by name
+    reservations: RwLock<HashMap<String, Reservation>>,
+    /// Decayed usage for fair-share ordering
+    fair_share: RwLock<FairShare>,
+    /// Backfill reservation from the last cycle
+    reservation: RwLock<Option<BackfillReservation>>,
+    /// Durable copy of jobs, array jobs and reservations
+    store: Option<Arc<StateStore>>,
+    /// Jobs changed in memory but not yet written to `store`
+    dirty: Mutex<HashSet<String>>,
+    /// Preemptions per job still waiting for its victims to stop
+    preemptions: Mutex<HashMap<String, Vec<PreemptionDecision>>>,
     /// Scheduler configuration
     config: SchedulerConfig,
 }
 
+/// Where CPU-only jobs go among nodes with room for them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CpuPlacement {
+    /// Fullest node first, keeping whole nodes free for large jobs
+    #[default]
+    Pack,
+    /// Least-loaded node first, spreading load evenly
+    Spread,
+}
+
+/// Default for `SchedulerConfig::max_array_size`
+pub const DEFAULT_MAX_ARRAY_SIZE: u32 = 10_000;
+
 /// Scheduler configuration
 #[derive(Debug, Clone)]
 pub struct SchedulerConfig {
     /// Maximum jobs to consider in one scheduling cycle
     pub max_schedule_batch: usize,
-    /// Enable backfill scheduling
+    /// Hold resources for the highest-priority blocked job and only start
+    /// lower-priority jobs that leave them alone or finish in time. When
+    /// disabled, any queued job that fits is started.
     pub backfill_enabled: bool,
     /// Enable topology-aware placement
     pub topology_aware: bool,
     /// Prefer same-node allocation for multi-GPU jobs
     pub prefer_same_node: bool,
+    /// Allow jobs with `can_preempt_others` to suspend lower-priority jobs
+    pub preemption_enabled: bool,
+    /// Placement strategy for CPU-only jobs
+    pub cpu_placement: CpuPlacement,
+    /// First retry delay in seconds; doubles with every retry
+    pub retry_backoff_base_secs: u64,
+    /// Upper bound on the retry delay in seconds
+    pub retry_backoff_max_secs: u64,
+    /// Per-user/per-project quotas and fair share
+    pub quotas: QuotaConfig,
+    /// Named queues; a `default` partition spanning all nodes is added
+    /// unless one is configured
+    pub partitions: Vec<PartitionConfig>,
+    /// Largest array job accepted, in children
+    pub max_array_size: u32,
 }
 
 impl Default for SchedulerConfig {
@@ -52,75 +170,456 @@ impl Default for SchedulerConfig {
             backfill_enabled: true,
             topology_aware: true,
             prefer_same_node: true,
+            preemption_enabled: true,
+            cpu_placement: CpuPlacement::default(),
+            retry_backoff_base_secs: 10,
+            retry_backoff_max_secs: 3600,
+            quotas: QuotaConfig::default(),
+            partitions: vec![],
+            max_array_size: DEFAULT_MAX_ARRAY_SIZE,
+        }
+    }
+}
+
+impl From<&crate::config::SchedulerConfig> for SchedulerConfig {
+    fn from(config: &crate::config::SchedulerConfig) -> Self {
+        Self {
+            max_schedule_batch: config.max_schedule_batch,
+            backfill_enabled: config.backfill_enabled,
+            topology_aware: config.topology_aware,
+            cpu_placement: config.cpu_placement,
+            quotas: config.quotas.clone(),
+            partitions: config.partitions.clone(),
+            max_array_size: config.max_array_size,
+            ..Default::default()
         }
     }
 }
 
 impl Scheduler {
     /// Create a new scheduler
-    pub fn new(nodes: Arc<NodeRegistry>, config: SchedulerConfig) -> Self {
+    pub fn new(nodes: Arc<NodeRegistry>, mut config: SchedulerConfig) -> Self {
+        if !config.partitions.iter().any(|p| p.name == DEFAULT_PARTITION) {
+            config.partitions.push(PartitionConfig::default());
+        }
+        
         Self {
             nodes,
             pending_queue: RwLock::new(PriorityQueue::new()),
             jobs: RwLock::new(HashMap::new()),
+            arrays: RwLock::new(HashMap::new()),
+            reservations: RwLock::new(HashMap::new()),
+            fair_share: RwLock::new(FairShare::default()),
+            reservation: RwLock::new(None),
+            store: None,
+            dirty: Mutex::new(HashSet::new()),
+            preemptions: Mutex::new(HashMap::new()),
             config,
         }
     }
     
+    /// Create a scheduler that writes through to `store`, restoring the
+    /// jobs, array jobs and reservations it holds
+    ///
+    /// Queued and suspended jobs go back into the pending queue. Scheduled
+    /// and running jobs get their allocations back on nodes already in the
+    /// registry, and on the others when they register (see `register_node`).
+    /// Nodes already in the registry get their stored cordon / drain state
+    /// back unless one was set on them since, and are stored.
+    pub fn with_store(nodes: Arc<NodeRegistry>, config: SchedulerConfig, store: Arc<StateStore>) -> Self {
+        let mut scheduler = Self::new(nodes, config);
+        
+        for mut node in scheduler.nodes.all_nodes() {
+            let stored = store.get_node(&node.id).map(|state| state.scheduling);
+            if let (Some(scheduling), NodeSchedulingState::Schedulable) = (stored, node.scheduling) {
+                match scheduler.nodes.set_scheduling(&node.id, scheduling) {
+                    Ok(updated) => node = updated,
+                    Err(e) => warn!("Failed to restore scheduling state of node {}: {}", node.id, e),
+                }
+            }
+            if let Err(e) = store.store_node(NodeState::of(&node)) {
+                warn!("Failed to persist node {}: {}", node.id, e);
+            }
+        }
+        
+        let mut jobs = HashMap::new();
+        let mut queue = PriorityQueue::new();
+        for job in store.list_all_jobs() {
+            let job_id = job.id.to_string();
+            if matches!(job.state, JobState::Queued | JobState::Suspended) {
+                queue.push(job_id.clone(), Self::queue_key(&job));
+            }
+            jobs.insert(job_id, job);
+        }
+        
+        for (job_id, job) in &jobs {
+            if !matches!(job.state, JobState::Scheduled | JobState::Running) {
+                continue;
+            }
+            for node_id in &job.allocated_nodes {
+                if scheduler.nodes.get(node_id).is_some() {
+                    if let Err(e) = scheduler.restore_allocation(job_id, job, node_id) {
+                        warn!("Failed to restore allocation of job {} on {}: {}", job_id, node_id, e);
+                    }
+                }
+            }
+        }
+        
+        info!("Recovered {} jobs ({} queued) from the state store", jobs.len(), queue.len());
+        scheduler.jobs = RwLock::new(jobs);
+        scheduler.pending_queue = RwLock::new(queue);
+        scheduler.arrays = RwLock::new(
+            store.list_arrays().into_iter().map(|a| (a.id.to_string(), a)).collect()
+        );
+        scheduler.reservations = RwLock::new(
+            store.list_reservations().into_iter().map(|r| (r.name.clone(), r)).collect()
+        );
+        scheduler.store = Some(store);
+        scheduler
+    }
+    
+    /// Write a job through to the state store, if there is one; on
+    /// failure it is written again by the next `flush_dirty`
+    fn persist(&self, job: &Job) -> Result<()> {
+        match &self.store {
+            Some(store) => store.store_job(job).inspect_err(|_| self.mark_dirty(job)),
+            None => Ok(()),
+        }
+    }
+    
+    /// Note a job changed in memory, to be written by the next `flush_dirty`
+    fn mark_dirty(&self, job: &Job) {
+        if self.store.is_some() {
+            self.dirty.lock().insert(job.id.to_string());
+        }
+    }
+    
+    /// Write every job noted by `mark_dirty` with a single WAL append. Call
+    /// with the jobs lock still held, so no newer change is written first.
+    fn flush_dirty(&self, jobs: &HashMap<String, Job>) -> Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        let mut dirty = self.dirty.lock();
+        if dirty.is_empty() {
+            return Ok(());
+        }
+        store.store_jobs(dirty.iter().filter_map(|job_id| jobs.get(job_id)))?;
+        dirty.clear();
+        Ok(())
+    }
+    
+    /// `flush_dirty` at the end of a background pass; on failure the jobs
+    /// stay noted for the next one
+    fn flush_logged(&self, jobs: &HashMap<String, Job>) {
+        if let Err(e) = self.flush_dirty(jobs) {
+            warn!("Failed to persist job changes: {}", e);
+        }
+    }
+    
+    /// Commit a recovered job's allocation on one of its nodes
+    fn restore_allocation(&self, job_id: &str, job: &Job, node_id: &str) -> Result<()> {
+        let gpus = job.allocated_gpus.get(node_id).cloned().unwrap_or_default();
+        let allocations = HashMap::from([(node_id.to_string(), gpus)]);
+        let cpu: HashMap<String, CpuAllocation> = job.allocated_cpu.get(node_id)
+            .map(|cpu| HashMap::from([(node_id.to_string(), cpu.clone())]))
+            .unwrap_or_default();
+        self.nodes.commit_allocation(job_id, &allocations, &cpu)
+    }
+    
+    /// Register a node, or re-register it after either side restarted, and
+    /// reconcile it with the jobs placed there
+    ///
+    /// `running` lists the jobs the node's agent still runs. Scheduled jobs
+    /// and reported running jobs get their allocations back; running jobs
+    /// the agent no longer has are failed (and retried per their policy).
+    /// Returns the reported jobs the node should not be running, such as
+    /// those requeued elsewhere while it was unreachable; the agent should
+    /// stop them.
+    ///
+    /// Agents that do not track their jobs pass `None`: every job placed
+    /// on the node gets its allocation back and none are reported.
+    pub fn register_node(&self, mut node: Node, running: Option<&[String]>) -> Result<Vec<String>> {
+        let node_id = node.id.clone();
+        let mut jobs = self.jobs.write();
+        let mut queue = self.pending_queue.write();
+        
+        // Cordons outlive agent and scheduler restarts
+        let scheduling = self.nodes.get(&node_id).map(|known| known.scheduling)
+            .or_else(|| self.store.as_ref()?.get_node(&node_id).map(|state| state.scheduling));
+        if let Some(scheduling) = scheduling {
+            node.scheduling = scheduling;
+        }
+        self.nodes.register(node)?;
+        
+        for (job_id, job) in jobs.iter_mut() {
+            if !matches!(job.state, JobState::Scheduled | JobState::Running)
+                || !job.allocated_nodes.contains(&node_id)
+            {
+                continue;
+            }
+            
+            if job.state == JobState::Running && running.is_some_and(|running| !running.contains(job_id)) {
+                let message = format!("Lost when node {} re-registered", node_id);
+                job.transition(JobState::Failed, Actor::Agent, &message)?;
+                warn!("Job {} failed: {}", job_id, message);
+                self.finished(job_id, job, &mut queue);
+                self.mark_dirty(job);
+                continue;
+            }
+            
+            if let Err(e) = self.restore_allocation(job_id, job, &node_id) {
+                warn!("Failed to restore allocation of job {} on {}: {}", job_id, node_id, e);
+            }
+        }
+        
+        let orphans: Vec<String> = running.unwrap_or_default().iter()
+            .filter(|job_id| !jobs.get(*job_id).is_some_and(|job| {
+                matches!(job.state, JobState::Scheduled | JobState::Running)
+                    && job.allocated_nodes.contains(&node_id)
+            }))
+            .cloned()
+            .collect();
+        for job_id in &orphans {
+            warn!("Node {} runs job {} which the scheduler does not expect there", node_id, job_id);
+        }
+        // A fresh registration holds no jobs being stopped
+        self.requeue_stopped(&jobs, &mut queue);
+        self.flush_dirty(&jobs)?;
+        
+        info!("Node {} registered", node_id);
+        Ok(orphans)
+    }
+    
     /// Submit a job
-    pub fn submit(&self, mut job: Job) -> Result<String> {
-        let job_id = job.id.to_string();
+    ///
+    /// Jobs with dependencies are held as Blocked until the scheduling
+    /// cycle finds them satisfied. Array jobs are expanded into one child
+    /// per index; the returned ID is then the parent's.
+    pub fn submit(&self, job: Job) -> Result<String> {
+        let mut jobs = self.jobs.write();
+        let mut queue = self.pending_queue.write();
         
-        job.transition(JobState::Queued, "Submitted to scheduler");
+        let known = |job_id: &str| jobs.contains_key(job_id);
         
-        let priority = job.descriptor.policy.priority;
+        if job.descriptor.array.is_none() {
+            let job = self.admit(known, job)?;
+            let job_id = job.id.to_string();
+            self.enqueue(&mut jobs, &mut queue, vec![job])?;
+            return Ok(job_id);
+        }
         
-        {
-            let mut jobs = self.jobs.write();
-            jobs.insert(job_id.clone(), job);
+        let (parent, children) = ArrayJob::expand(job, self.config.max_array_size)?;
+        let parent_id = parent.id.to_string();
+        let children = children.into_iter()
+            .map(|child| self.admit(known, child))
+            .collect::<Result<Vec<Job>>>()?;
+        self.enqueue(&mut jobs, &mut queue, children)?;
+        if let Some(store) = &self.store {
+            store.store_array(&parent)?;
         }
+        info!("Array job {} submitted with {} children", parent_id, parent.children.len());
+        self.arrays.write().insert(parent_id.clone(), parent);
+        Ok(parent_id)
+    }
+    
+    /// Submit a DAG of jobs at once
+    ///
+    /// Step dependencies may refer to other steps by name or to existing
+    /// jobs by ID. Nothing is submitted if a step is duplicated, refers to
+    /// an unknown job, or the steps form a cycle. Returns step name -> job ID.
+    pub fn submit_workflow(&self, steps: Vec<WorkflowStep>) -> Result<HashMap<String, String>> {
+        let mut jobs = self.jobs.write();
+        let mut queue = self.pending_queue.write();
         
-        {
-            let mut queue = self.pending_queue.write();
-            queue.push(job_id.clone(), priority);
+        let mut graph = petgraph::graphmap::DiGraphMap::<&str, ()>::new();
+        for step in &steps {
+            if step.descriptor.array.is_some() {
+                return Err(Error::Job(format!("Workflow step {} cannot be an array job", step.name)));
+            }
+            if graph.contains_node(&step.name) {
+                return Err(Error::Job(format!("Duplicate workflow step: {}", step.name)));
+            }
+            graph.add_node(&step.name);
+        }
+        for step in &steps {
+            for upstream in step.descriptor.dependencies.job_ids() {
+                if graph.contains_node(upstream) {
+                    graph.add_edge(upstream, &step.name, ());
+                } else if !jobs.contains_key(upstream) {
+                    return Err(Error::Job(format!(
+                        "Workflow step {} depends on unknown job {}", step.name, upstream
+                    )));
+                }
+            }
+        }
+        let order: Vec<String> = petgraph::algo::toposort(&graph, None)
+            .map_err(|cycle| Error::Job(format!("Workflow has a cycle through step {}", cycle.node_id())))?
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        
+        let mut pending: HashMap<String, Job> = steps.into_iter()
+            .map(|step| (step.name, Job::new(step.descriptor)))
+            .collect();
+        let job_ids: HashMap<String, String> = pending.iter()
+            .map(|(name, job)| (name.clone(), job.id.to_string()))
+            .collect();
+        
+        // Upstream steps are admitted first, so every reference resolves
+        let known = |job_id: &str| jobs.contains_key(job_id) || job_ids.values().any(|id| id == job_id);
+        let mut admitted = vec![];
+        for name in order {
+            let mut job = pending.remove(&name).expect("every step is in the graph");
+            let dependencies = &mut job.descriptor.dependencies;
+            for upstream in dependencies.after_ok.iter_mut()
+                .chain(dependencies.after_any.iter_mut())
+                .chain(dependencies.after_fail.iter_mut())
+            {
+                if let Some(job_id) = job_ids.get(upstream.as_str()) {
+                    *upstream = job_id.clone();
+                }
+            }
+            admitted.push(self.admit(known, job)?);
+        }
+        self.enqueue(&mut jobs, &mut queue, admitted)?;
+        
+        info!("Workflow of {} jobs submitted", job_ids.len());
+        Ok(job_ids)
+    }
+    
+    /// Check a new job against its queue and reservation and move it to
+    /// Queued, or Blocked if it has dependencies (which `known` must resolve)
+    fn admit(&self, known: impl Fn(&str) -> bool, mut job: Job) -> Result<Job> {
+        let queue_name = &job.descriptor.policy.queue_name;
+        self.partition(queue_name)
+            .ok_or_else(|| Error::Job(format!("Unknown queue: {}", queue_name)))?
+            .admit(&mut job)?;
+        
+        if let Some(name) = &job.descriptor.policy.reservation {
+            let reservations = self.reservations.read();
+            let reservation = reservations.get(name)
+                .ok_or_else(|| Error::Job(format!("Unknown reservation: {}", name)))?;
+            if !reservation.allows(&job.descriptor.user_id) {
+                return Err(Error::Job(format!(
+                    "User {} may not submit into reservation {}", job.descriptor.user_id, name
+                )));
+            }
+            if reservation.end <= Utc::now() {
+                return Err(Error::Job(format!("Reservation {} has ended", name)));
+            }
         }
         
-        info!("Job {} submitted with priority {}", job_id, priority);
-        Ok(job_id)
+        if job.descriptor.dependencies.is_empty() {
+            job.transition(JobState::Queued, Actor::User, "Submitted to scheduler")?;
+        } else {
+            if let Some(missing) = job.descriptor.dependencies.job_ids().find(|id| !known(id)) {
+                return Err(Error::Job(format!("Unknown dependency: {}", missing)));
+            }
+            job.transition(JobState::Blocked, Actor::User, "Waiting for dependencies")?;
+        }
+        Ok(job)
+    }
+    
+    /// Store admitted jobs in one batch, then add them to the jobs and the
+    /// queue; nothing is added if storing fails
+    fn enqueue(
+        &self,
+        jobs: &mut HashMap<String, Job>,
+        queue: &mut PriorityQueue<String, QueueKey>,
+        batch: Vec<Job>,
+    ) -> Result<()> {
+        if let Some(store) = &self.store {
+            store.store_jobs(&batch)?;
+        }
+        for job in batch {
+            let job_id = job.id.to_string();
+            if job.state == JobState::Queued {
+                queue.push(job_id.clone(), Self::queue_key(&job));
+            }
+            info!("Job {} submitted with priority {}", job_id, job.descriptor.policy.priority);
+            jobs.insert(job_id, job);
+        }
+        Ok(())
+    }
+    
+    /// Queue blocked jobs whose dependencies are met and cancel those whose
+    /// dependencies never can be, repeating so cancellations cascade
+    fn resolve_dependencies(
+        &self,
+        jobs: &mut HashMap<String, Job>,
+        queue: &mut PriorityQueue<String, QueueKey>,
+    ) {
+        let now = Utc::now();
+        loop {
+            let resolved: Vec<(String, DependencyStatus)> = jobs.values()
+                .filter(|job| job.state == JobState::Blocked)
+                .map(|job| (job.id.to_string(), job.descriptor.dependencies.status(|id| jobs.get(id))))
+                .filter(|(_, status)| *status != DependencyStatus::Waiting)
+                .collect();
+            if resolved.is_empty() {
+                break;
+            }
+            
+            for (job_id, status) in resolved {
+                let job = jobs.get_mut(&job_id).expect("collected above");
+                if let DependencyStatus::Unsatisfiable(reason) = status {
+                    let message = format!("Dependency never satisfied: {}", reason);
+                    job.transition(JobState::Cancelled, Actor::Scheduler, &message)
+                        .expect("blocked jobs can be cancelled");
+                    info!("Job {} cancelled: {}", job_id, message);
+                    self.mark_dirty(job);
+                } else {
+                    job.transition(JobState::Queued, Actor::Scheduler, "Dependencies satisfied")
+                        .expect("blocked jobs can be queued");
+                    job.requeue_time = Some(now);
+                    queue.push(job_id.clone(), Self::queue_key(job));
+                    info!("Job {} released by its dependencies", job_id);
+                    self.mark_dirty(job);
+                }
+            }
+        }
     }
     
-    /// Cancel a job
-    pub fn cancel(&self, job_id: &str, reason: &str) -> Result<()> {
+    /// Cancel a job, or every unfinished child of an array job
+    pub fn cancel(&self, job_id: &str, actor: Actor, reason: &str) -> Result<()> {
+        let children = self.arrays.read().get(job_id).map(|array| array.children.clone());
+        if let Some(children) = children {
+            for child in children {
+                if self.get_job(&child).is_some_and(|job| job.final_state().is_none()) {
+                    self.cancel(&child, actor, reason)?;
+                }
+            }
+            return Ok(());
+        }
+        
         let mut jobs = self.jobs.write();
         
         if let Some(job) = jobs.get_mut(job_id) {
-            match job.state {
-                JobState::Pending | JobState::Queued | JobState::Scheduled => {
-                    job.transition(JobState::Cancelled, reason);
-                    
+            // A failed job waiting to be retried stays failed, but for good
+            if job.cancel_retry(actor, reason) {
+                info!("Job {} will not be retried: {}", job_id, reason);
+                return self.persist(job);
+            }
+            
+            let previous = job.state;
+            job.transition(JobState::Cancelled, actor, reason)?;
+            
+            match previous {
+                JobState::Scheduled | JobState::Running => {
+                    // Resources are released once the agent stopped it
+                    self.nodes.stop_job(job_id);
+                }
+                _ => {
                     // Remove from queue
                     let mut queue = self.pending_queue.write();
                     queue.remove(job_id);
-                }
-                JobState::Running => {
-                    job.transition(JobState::Cancelled, reason);
-                    
-                    // Release resources
-                    for node_id in &job.allocated_nodes {
-                        if let Some(_node) = self.nodes.get(node_id) {
-                            // In production: send cancel signal to node agent
-                        }
-                    }
-                }
-                _ => {
-                    return Err(Error::Job(format!(
-                        "Cannot cancel job in state {:?}", job.state
-                    )));
+                    self.preemptions.lock().remove(job_id);
                 }
             }
             
             info!("Job {} cancelled: {}", job_id, reason);
-            Ok(())
+            self.persist(job)
         } else {
             Err(Error::Job(format!("Job not found: {}", job_id)))
         }
@@ -129,270 +628,2573 @@ impl Scheduler {
     /// Run one scheduling cycle
     pub fn schedule_cycle(&self) -> Vec<SchedulingDecision> {
         let mut decisions = vec![];
-        let mut queue = self.pending_queue.write();
+        // Same lock order as `cancel` and `update_job_state`
         let mut jobs = self.jobs.write();
+        let mut queue = self.pending_queue.write();
+        self.resolve_dependencies(&mut jobs, &mut queue);
         
-        let mut processed = 0;
-        let mut to_remove = vec![];
+        let now = Utc::now();
+        let fair_share_config = &self.config.quotas.fair_share;
+        let mut fair_share = self.fair_share.write();
+        fair_share.accrue(fair_share_config, now, jobs.values());
         
-        // Process jobs in priority order
-        for (job_id, _priority) in queue.iter() {
-            if processed >= self.config.max_schedule_batch {
-                break;
+        // Process jobs by partition tier, then priority (`PriorityQueue::iter`
+        // is unordered) lowered by each job's fair-share penalty
+        let mut ordered: Vec<(String, (i32, QueueKey))> = queue.iter()
+            .map(|(id, &(priority, submitted))| {
+                let (tier, adjustment) = jobs.get(id).map_or((0, 0), |job| (
+                    self.partition(&job.descriptor.policy.queue_name).map_or(0, |p| p.priority_tier),
+                    fair_share.priority_adjustment(fair_share_config, job),
+                ));
+                (id.clone(), (tier, (priority.saturating_add(adjustment), submitted)))
+            })
+            .collect();
+        drop(fair_share);
+        ordered.sort_by_key(|(_, key)| Reverse(*key));
+        
+        let mut reservation: Option<BackfillReservation> = None;
+        let mut blocked = false;
+        
+        // Scheduled or running children per array, for throttling
+        let mut array_active: HashMap<String, u32> = HashMap::new();
+        for job in jobs.values().filter(|j| matches!(j.state, JobState::Scheduled | JobState::Running)) {
+            if let Some(task) = &job.array_task {
+                *array_active.entry(task.parent_id.clone()).or_default() += 1;
             }
+        }
+        
+        for (job_id, _key) in ordered.into_iter().take(self.config.max_schedule_batch) {
+            let Some(job) = jobs.get(&job_id) else {
+                continue;
+            };
             
-            if let Some(job) = jobs.get_mut(job_id) {
-                if let Some(decision) = self.try_schedule_job(job) {
-                    // Apply allocation
-                    job.transition(JobState::Scheduled, "Resources allocated");
-                    job.allocated_nodes = decision.allocations.keys().cloned().collect();
-                    job.allocated_gpus = decision.allocations.clone();
-                    
-                    decisions.push(decision.clone());
-                    to_remove.push(job_id.clone());
-                    
-                    info!(
-                        "Job {} scheduled: {} nodes, {} GPUs",
-                        job_id,
-                        job.allocated_nodes.len(),
-                        job.allocated_gpus.values().map(|v| v.len()).sum::<usize>()
+            // Array children beyond the throttle wait for a sibling to finish
+            if let Some(task) = &job.array_task {
+                let active = array_active.get(&task.parent_id).copied().unwrap_or(0);
+                if task.max_concurrent > 0 && active >= task.max_concurrent {
+                    let message = format!(
+                        "Waiting for array throttle ({} of {} running)", active, task.max_concurrent
                     );
+                    if let Some(job) = jobs.get_mut(&job_id) {
+                        job.message = message;
+                    }
+                    continue;
+                }
+            }
+            
+            // Jobs in a reservation wait for its window
+            if let Some(reason) = self.reservation_wait(job, now) {
+                debug!("Job {} {}", job_id, reason);
+                if let Some(job) = jobs.get_mut(&job_id) {
+                    job.message = reason;
+                }
+                continue;
+            }
+            
+            // Over-quota jobs stay queued and never hold a reservation
+            if let Err(reason) = self.check_quota(job, &jobs) {
+                let message = format!("Waiting for quota: {}", reason);
+                debug!("Job {} {}", job_id, message);
+                if let Some(job) = jobs.get_mut(&job_id) {
+                    job.message = message;
+                }
+                continue;
+            }
+            
+            let nodes = self.placeable_nodes(job);
+            let decision = match &reservation {
+                Some(reservation) => self.try_backfill(job, &nodes, reservation, now),
+                None => self.try_schedule_job(job, &nodes),
+            };
+            
+            // Lower-priority jobs must not preempt ahead of a reserved one.
+            // The victims' resources are held until their agents confirm
+            // they stopped, so the job waits for them behind a reservation.
+            if decision.is_none()
+                && reservation.is_none()
+                && self.config.preemption_enabled
+                && job.descriptor.policy.can_preempt_others
+            {
+                if let Some(held) = self.preempt_for(&job_id, &mut jobs) {
+                    if let Some(job) = jobs.get_mut(&job_id) {
+                        job.message = "Waiting for preempted jobs to stop".to_string();
+                    }
+                    blocked = true;
+                    reservation = Some(held);
+                    continue;
+                }
+            }
+            
+            let Some(mut decision) = decision else {
+                let reason = Self::unplaceable_reason(&jobs[&job_id], &nodes);
+                debug!("Job {} not placed: {}", job_id, reason);
+                if let Some(job) = jobs.get_mut(&job_id) {
+                    job.message = format!("Waiting for resources: {}", reason);
+                }
+                blocked = true;
+                if reservation.is_none() && self.config.backfill_enabled {
+                    let job = &jobs[&job_id];
+                    reservation = self.reserve_for(job, &jobs, &nodes, now);
+                    if let Some(r) = &reservation {
+                        debug!("Reserved resources for job {} from {:?}", job_id, r.start_estimate);
+                    }
                 }
+                continue;
+            };
+            
+            // Commit to the nodes so later jobs in this cycle see it
+            if let Err(e) = self.nodes.commit_allocation(&job_id, &decision.allocations, &decision.cpu) {
+                warn!("Failed to commit allocation for job {}: {}", job_id, e);
+                continue;
+            }
+            
+            // Apply allocation
+            let job = jobs.get_mut(&job_id).expect("job checked above");
+            if let Err(e) = job.transition(JobState::Scheduled, Actor::Scheduler, "Resources allocated") {
+                warn!("Failed to schedule job {}: {}", job_id, e);
+                self.nodes.release_job(&job_id);
+                queue.remove(&job_id);
+                continue;
+            }
+            job.allocated_nodes = decision.allocations.keys().cloned().collect();
+            job.allocated_gpus = decision.allocations.clone();
+            job.allocated_cpu = decision.cpu.clone();
+            queue.remove(&job_id);
+            self.mark_dirty(job);
+            decision.preempted = self.preemptions.lock().remove(&job_id).unwrap_or_default();
+            if let Some(task) = &job.array_task {
+                *array_active.entry(task.parent_id.clone()).or_default() += 1;
             }
             
-            processed += 1;
+            info!(
+                "Job {} scheduled: {} nodes, {} GPUs, {} preempted",
+                job_id,
+                job.allocated_nodes.len(),
+                job.allocated_gpus.values().map(|v| v.len()).sum::<usize>(),
+                decision.preempted.len()
+            );
+            
+            decisions.push(decision);
         }
         
-        // Remove scheduled jobs from queue
-        for job_id in to_remove {
-            queue.remove(&job_id);
+        // Hand leftover capacity to elastic jobs only if no queued job needs it
+        if !blocked {
+            decisions.extend(self.grow_elastic_jobs(&mut jobs));
         }
         
+        self.flush_logged(&jobs);
+        *self.reservation.write() = reservation;
         decisions
     }
     
-    /// Try to schedule a single job
-    fn try_schedule_job(&self, job: &Job) -> Option<SchedulingDecision> {
-        let required_gpus = job.descriptor.resources.gpu_count as usize;
-        
-        if required_gpus == 0 {
-            // CPU-only job
-            return self.schedule_cpu_job(job);
+    /// Give running elastic jobs more GPUs, up to their maximum and quota,
+    /// preferring the nodes they already occupy
+    fn grow_elastic_jobs(&self, jobs: &mut HashMap<String, Job>) -> Vec<SchedulingDecision> {
+        let mut growable: Vec<String> = jobs.values()
+            .filter(|j| matches!(j.state, JobState::Scheduled | JobState::Running))
+            .filter(|j| j.descriptor.resources.is_elastic())
+            .filter(|j| j.allocated_gpu_count() < j.descriptor.resources.gpu_range().1 as usize)
+            .map(|j| j.id.to_string())
+            .collect();
+        if growable.is_empty() {
+            return vec![];
         }
         
-        // Get candidate nodes
-        let candidates = self.nodes.nodes_with_available_gpus(1);
+        // Highest priority first
+        growable.sort_by_key(|id| Reverse((jobs[id].descriptor.policy.priority, Reverse(jobs[id].submit_time))));
         
-        if candidates.is_empty() {
-            debug!("No nodes with available GPUs for job {}", job.id);
-            return None;
-        }
-        
-        // Gang scheduling: try to allocate all GPUs together
-        if job.descriptor.policy.gang_schedule {
-            return self.gang_schedule(job, &candidates, required_gpus);
+        let mut decisions = vec![];
+        for job_id in growable {
+            let Some(decision) = self.plan_growth(&jobs[&job_id], jobs) else {
+                continue;
+            };
+            
+            if let Err(e) = self.nodes.commit_allocation(&job_id, &decision.allocations, &decision.cpu) {
+                warn!("Failed to grow elastic job {}: {}", job_id, e);
+                continue;
+            }
+            
+            let job = jobs.get_mut(&job_id).expect("job listed above");
+            for (node_id, gpu_ids) in &decision.allocations {
+                job.allocated_gpus.entry(node_id.clone()).or_default().extend(gpu_ids.iter().cloned());
+                if !job.allocated_nodes.contains(node_id) {
+                    job.allocated_nodes.push(node_id.clone());
+                }
+            }
+            for (node_id, cpu) in &decision.cpu {
+                job.allocated_cpu.entry(node_id.clone()).or_insert_with(|| cpu.clone());
+            }
+            job.message = format!("Grew to {} GPUs", job.allocated_gpu_count());
+            info!("Elastic job {} {}", job_id, job.message);
+            self.mark_dirty(job);
+            
+            decisions.push(decision);
         }
         
-        // Non-gang: allocate wherever possible
-        self.spread_schedule(job, &candidates, required_gpus)
+        decisions
     }
     
-    /// Gang scheduling: all or nothing allocation
-    fn gang_schedule(
-        &self,
-        job: &Job,
-        candidates: &[Node],
-        required_gpus: usize,
-    ) -> Option<SchedulingDecision> {
-        // First try: single node with enough GPUs
-        if self.config.prefer_same_node {
-            for node in candidates {
-                if node.available_gpus() >= required_gpus {
-                    // Allocate all GPUs from this node
-                    let mut allocations = HashMap::new();
-                    let gpu_ids: Vec<String> = node.topology.gpus.iter()
-                        .filter(|g| !g.allocated)
-                        .take(required_gpus)
-                        .map(|g| g.device_id.clone())
-                        .collect();
-                    
-                    allocations.insert(node.id.clone(), gpu_ids);
-                    
-                    return Some(SchedulingDecision {
-                        job_id: job.id.to_string(),
-                        allocations,
-                        gang_allocated: true,
-                    });
-                }
+    /// Extra GPUs an elastic job can take now, or `None` if it cannot grow
+    fn plan_growth(&self, job: &Job, jobs: &HashMap<String, Job>) -> Option<SchedulingDecision> {
+        let resources = &job.descriptor.resources;
+        let job_id = job.id.to_string();
+        let (_, max) = resources.gpu_range();
+        let mut remaining = (max as usize).saturating_sub(job.allocated_gpu_count());
+        
+        // Stay within the GPU quota of the user and the project
+        let quotas = &self.config.quotas;
+        let user = &job.descriptor.user_id;
+        let project = &job.descriptor.project_id;
+        let headroom = [
+            (quotas.user_limits(user).max_gpus, ResourceUsage::of(jobs.values(), |j| &j.descriptor.user_id == user)),
+            (quotas.project_limits(project).max_gpus, ResourceUsage::of(jobs.values(), |j| &j.descriptor.project_id == project)),
+        ];
+        for (limit, usage) in headroom {
+            if let Some(limit) = limit {
+                remaining = remaining.min(limit.saturating_sub(usage.gpus) as usize);
             }
         }
-        
-        // Second try: spread across multiple nodes
-        let total_available: usize = candidates.iter()
-            .map(|n| n.available_gpus())
-            .sum();
-        
-        if total_available < required_gpus {
-            debug!(
-                "Not enough GPUs for gang job {}: need {}, have {}",
-                job.id, required_gpus, total_available
-            );
+        if remaining == 0 {
             return None;
         }
         
-        // Greedy allocation across nodes
-        let mut allocations = HashMap::new();
-        let mut remaining = required_gpus;
+        // Nodes the job already runs on first (no new CPU share needed),
+        // then other candidates in rank order
+        let nodes = self.placeable_nodes(job);
+        let mut pool: Vec<Node> = nodes.iter()
+            .filter(|n| job.allocated_gpus.contains_key(&n.id))
+            .cloned()
+            .collect();
+        pool.sort_by(|a, b| a.id.cmp(&b.id));
+        pool.extend(Self::rank_candidates(job, &nodes).into_iter().filter(|n| !job.allocated_gpus.contains_key(&n.id)));
         
-        for node in candidates {
+        let mut decision = SchedulingDecision {
+            job_id: job_id.clone(),
+            allocations: HashMap::new(),
+            gang_allocated: false,
+            grown: true,
+            preempted: vec![],
+            cpu: HashMap::new(),
+        };
+        
+        for node in &pool {
             if remaining == 0 {
                 break;
             }
+            let take = remaining.min(node.eligible_gpus(resources).len());
+            if take == 0 {
+                continue;
+            }
+            let Some(gpu_ids) = self.pick_gpus(node, resources, take) else {
+                continue;
+            };
             
-            let available = node.available_gpus();
-            let to_allocate = remaining.min(available);
-            
-            let gpu_ids: Vec<String> = node.topology.gpus.iter()
-                .filter(|g| !g.allocated)
-                .take(to_allocate)
-                .map(|g| g.device_id.clone())
-                .collect();
+            let cpu = match node.cpu_allocations.get(&job_id) {
+                Some(existing) => existing.clone(),
+                None => {
+                    let mut cpu = CpuAllocation::from(resources);
+                    if self.config.topology_aware {
+                        cpu.cpu_set = node.select_cpu_cores(cpu.cores, &gpu_ids);
+                    }
+                    cpu
+                }
+            };
             
-            allocations.insert(node.id.clone(), gpu_ids);
-            remaining -= to_allocate;
+            remaining -= gpu_ids.len();
+            decision.cpu.insert(node.id.clone(), cpu);
+            decision.allocations.insert(node.id.clone(), gpu_ids);
         }
         
-        if remaining > 0 {
-            None
-        } else {
-            Some(SchedulingDecision {
-                job_id: job.id.to_string(),
-                allocations,
-                gang_allocated: true,
-            })
-        }
+        (!decision.allocations.is_empty()).then_some(decision)
     }
     
-    /// Spread scheduling: allocate what's available
-    fn spread_schedule(
-        &self,
-        job: &Job,
-        candidates: &[Node],
-        required_gpus: usize,
-    ) -> Option<SchedulingDecision> {
-        // Same as gang but with partial allocation allowed
-        self.gang_schedule(job, candidates, required_gpus)
+    /// Explain why a job fits none of the nodes: per-node rejections, or a
+    /// shortfall of eligible GPUs across the nodes that do fit
+    fn unplaceable_reason(job: &Job, nodes: &[Node]) -> String {
+        if nodes.is_empty() {
+            return format!(
+                "no healthy unreserved nodes in queue {}", job.descriptor.policy.queue_name
+            );
+        }
+        
+        let resources = &job.descriptor.resources;
+        let mut rejected = vec![];
+        let mut eligible = 0;
+        for node in nodes {
+            match node.check_locality(&job.descriptor.locality).and_then(|_| node.check_fit(resources)) {
+                Ok(()) => eligible += node.eligible_gpus(resources).len(),
+                Err(e) => rejected.push(format!("{}: {}", node.id, e)),
+            }
+        }
+        rejected.sort();
+        
+        if rejected.len() < nodes.len() && resources.gpu_count > 0 {
+            rejected.insert(0, format!(
+                "{} eligible GPUs < {} requested", eligible, resources.gpu_count
+            ));
+        }
+        rejected.join("; ")
     }
     
-    /// Schedule CPU-only job
-    fn schedule_cpu_job(&self, job: &Job) -> Option<SchedulingDecision> {
-        let nodes = self.nodes.healthy_nodes();
+    /// Check the job's user and project quotas against their active jobs
+    fn check_quota(&self, job: &Job, jobs: &HashMap<String, Job>) -> std::result::Result<(), String> {
+        let quotas = &self.config.quotas;
+        let user_id = &job.descriptor.user_id;
+        let project_id = &job.descriptor.project_id;
         
-        if let Some(node) = nodes.first() {
-            Some(SchedulingDecision {
-                job_id: job.id.to_string(),
-                allocations: HashMap::from([(node.id.clone(), vec![])]),
-                gang_allocated: false,
-            })
+        let usage = ResourceUsage::of(jobs.values(), |j| &j.descriptor.user_id == user_id);
+        quotas.user_limits(user_id).check(&usage, job)
+            .map_err(|e| format!("user {}: {}", user_id, e))?;
+        
+        let usage = ResourceUsage::of(jobs.values(), |j| &j.descriptor.project_id == project_id);
+        quotas.project_limits(project_id).check(&usage, job)
+            .map_err(|e| format!("project {}: {}", project_id, e))
+    }
+    
+    /// Find where and when `job` can start once running jobs reach their
+    /// `max_runtime_seconds`, releasing the earliest-ending jobs first
+    fn reserve_for(
+        &self,
+        job: &Job,
+        jobs: &HashMap<String, Job>,
+        nodes: &[Node],
+        now: DateTime<Utc>,
+    ) -> Option<BackfillReservation> {
+        // Jobs without a runtime limit are assumed to end last
+        let mut running: Vec<&Job> = jobs.values()
+            .filter(|j| matches!(j.state, JobState::Scheduled | JobState::Running))
+            .collect();
+        let end = |j: &Job| self.runtime_deadline(j).map(|(end, _)| end);
+        running.sort_by_key(|j| (end(j).is_none(), end(j)));
+        
+        let mut released = vec![];
+        for running_job in running {
+            released.push(running_job.id.to_string());
+            if let Some(decision) = self.try_schedule_job(job, &Self::without_jobs(nodes, &released)) {
+                return Some(BackfillReservation {
+                    job_id: job.id.to_string(),
+                    start_estimate: end(running_job).map(|end| end.max(now)),
+                    allocations: decision.allocations,
+                    cpu: decision.cpu,
+                });
+            }
+        }
+        
+        debug!("Job {} does not fit even on an idle cluster", job.id);
+        None
+    }
+    
+    /// Place a job behind a reservation: either on resources the reservation
+    /// does not need, or anywhere if its runtime limit ends before the
+    /// reserved job can start
+    fn try_backfill(
+        &self,
+        job: &Job,
+        nodes: &[Node],
+        reservation: &BackfillReservation,
+        now: DateTime<Utc>,
+    ) -> Option<SchedulingDecision> {
+        let mut masked = nodes.to_vec();
+        for node in &mut masked {
+            if let Some(reserved) = reservation.allocations.get(&node.id) {
+                for gpu in &mut node.topology.gpus {
+                    if !gpu.allocated && reserved.contains(&gpu.device_id) {
+                        gpu.allocated = true;
+                        gpu.allocated_job_id = Some(reservation.job_id.clone());
+                    }
+                }
+            }
+            // Free cores and memory beyond what the reserved job needs
+            // stay available
+            if let Some(cpu) = reservation.cpu.get(&node.id) {
+                node.cpu_allocations.insert(reservation.job_id.clone(), cpu.clone());
+            }
+        }
+        
+        if let Some(decision) = self.try_schedule_job(job, &masked) {
+            return Some(decision);
+        }
+        
+        let limit = job.descriptor.policy.max_runtime_seconds;
+        let finishes_in_time = match reservation.start_estimate {
+            Some(start) if limit > 0 => now + chrono::Duration::seconds(limit as i64) <= start,
+            _ => false,
+        };
+        
+        if finishes_in_time {
+            self.try_schedule_job(job, nodes)
         } else {
             None
         }
     }
     
-    /// Get job status
-    pub fn get_job(&self, job_id: &str) -> Option<Job> {
-        self.jobs.read().get(job_id).cloned()
+    /// Suspend the cheapest set of lower-priority preemptible jobs that
+    /// frees enough room for `job_id`, unless jobs already being stopped
+    /// do, and return the resources to hold for it until they stop.
+    fn preempt_for(
+        &self,
+        job_id: &str,
+        jobs: &mut HashMap<String, Job>,
+    ) -> Option<BackfillReservation> {
+        let job = jobs.get(job_id)?.clone();
+        let priority = job.descriptor.policy.priority;
+        
+        // Cheapest first: lowest priority, then the most recently started
+        // (least work lost)
+        let mut candidates: Vec<&Job> = jobs.values()
+            .filter(|j| matches!(j.state, JobState::Scheduled | JobState::Running))
+            .filter(|j| j.descriptor.policy.preemptible && j.descriptor.policy.priority < priority)
+            .collect();
+        candidates.sort_by(|a, b| {
+            a.descriptor.policy.priority.cmp(&b.descriptor.policy.priority)
+                .then_with(|| b.start_time.or(b.schedule_time).cmp(&a.start_time.or(a.schedule_time)))
+        });
+        
+        // Resources of jobs already being stopped count as free
+        let snapshot = Self::without_jobs(&self.placeable_nodes(&job), &self.nodes.stopping_jobs());
+        let place = |victims: &[String]| self.try_schedule_job(&job, &Self::without_jobs(&snapshot, victims));
+        let fits = |victims: &[String]| place(victims).is_some();
+        let hold = |decision: SchedulingDecision| BackfillReservation {
+            job_id: job_id.to_string(),
+            start_estimate: None,
+            allocations: decision.allocations,
+            cpu: decision.cpu,
+        };
+        
+        if let Some(decision) = place(&[]) {
+            debug!("Job {} waits for preempted jobs to stop", job_id);
+            return Some(hold(decision));
+        }
+        
+        let mut victims = vec![];
+        for candidate in candidates {
+            victims.push(candidate.id.to_string());
+            if fits(&victims) {
+                break;
+            }
+        }
+        if victims.is_empty() || !fits(&victims) {
+            debug!("Preemption cannot make room for job {}", job_id);
+            return None;
+        }
+        
+        // Drop victims that turned out to be unnecessary
+        let mut i = 0;
+        while i < victims.len() {
+            let mut trial = victims.clone();
+            trial.remove(i);
+            if fits(&trial) {
+                victims = trial;
+            } else {
+                i += 1;
+            }
+        }
+        
+        let held = place(&victims).map(hold);
+        let mut preemptions = self.preemptions.lock();
+        let preempted = preemptions.entry(job_id.to_string()).or_default();
+        for victim_id in victims {
+            let victim = jobs.get_mut(&victim_id)?;
+            let reason = format!(
+                "Preempted by job {} (priority {} > {})",
+                job_id, priority, victim.descriptor.policy.priority
+            );
+            let released = self.suspend(&victim_id, victim, &reason);
+            
+            preempted.push(PreemptionDecision {
+                job_id: victim_id,
+                preempted_by: job_id.to_string(),
+                released,
+                reason,
+            });
+        }
+        held
+    }
+    
+    /// Copy of `nodes` with the given jobs' GPUs released
+    fn without_jobs(nodes: &[Node], job_ids: &[String]) -> Vec<Node> {
+        let mut nodes = nodes.to_vec();
+        for node in &mut nodes {
+            for job_id in job_ids {
+                node.release_job(job_id);
+            }
+        }
+        nodes
+    }
+    
+    fn queue_key(job: &Job) -> QueueKey {
+        (job.descriptor.policy.priority, Reverse(job.submit_time))
+    }
+    
+    /// Try to place a single job on a snapshot of healthy nodes
+    fn try_schedule_job(&self, job: &Job, nodes: &[Node]) -> Option<SchedulingDecision> {
+        // Elastic jobs start at their minimum; `grow_elastic_jobs` adds more
+        let required_gpus = job.descriptor.resources.gpu_count as usize;
+        
+        // Candidate nodes, best first
+        let candidates = Self::rank_candidates(job, nodes);
+        
+        if candidates.is_empty() {
+            debug!("No nodes satisfy the requirements of job {}", job.id);
+            return None;
+        }
+        
+        let mut decision = if required_gpus == 0 {
+            // CPU-only job
+            self.schedule_cpu_job(job, &candidates)
+        } else if job.descriptor.policy.gang_schedule {
+            // Gang scheduling: try to allocate all GPUs together
+            self.gang_schedule(job, &candidates, required_gpus)
+        } else {
+            // Non-gang: spread across nodes
+            self.spread_schedule(job, &candidates, required_gpus)
+        }?;
+        
+        // CPU share on every node, pinned near the chosen GPUs
+        for (node_id, gpu_ids) in &decision.allocations {
+            let node = candidates.iter().find(|n| &n.id == node_id)?;
+            let mut cpu = CpuAllocation::from(&job.descriptor.resources);
+            if self.config.topology_aware {
+                cpu.cpu_set = node.select_cpu_cores(cpu.cores, gpu_ids);
+            }
+            decision.cpu.insert(node_id.clone(), cpu);
+        }
+        
+        Some(decision)
+    }
+    
+    /// Pick GPUs on one node: NVLink/NUMA-aware when `topology_aware`,
+    /// otherwise the first eligible ones
+    fn pick_gpus(&self, node: &Node, resources: &ResourceRequirements, count: usize) -> Option<Vec<String>> {
+        if self.config.topology_aware {
+            return node.select_gpus(resources, count);
+        }
+        
+        let eligible = node.eligible_gpus(resources);
+        (eligible.len() >= count).then(|| {
+            eligible.into_iter().take(count).map(|g| g.device_id.clone()).collect()
+        })
+    }
+    
+    /// Nodes that pass the job's hard constraints (locality and resources),
+    /// best `locality_score` first and by node ID among equals
+    fn rank_candidates(job: &Job, nodes: &[Node]) -> Vec<Node> {
+        let resources = &job.descriptor.resources;
+        let locality = &job.descriptor.locality;
+        
+        let candidates: Vec<&Node> = nodes.iter()
+            .filter(|n| n.check_locality(locality).is_ok() && n.check_fit(resources).is_ok())
+            .collect();
+        
+        let mut scored: Vec<(i64, &Node)> = candidates.iter()
+            .map(|node| (Self::locality_score(job, node, &candidates), *node))
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.id.cmp(&b.1.id)));
+        
+        scored.into_iter().map(|(_, node)| node.clone()).collect()
+    }
+    
+    /// Soft preferences: preferred nodes in the order listed, then (when
+    /// asked for) nodes whose NVSwitch domain or rack has the most eligible
+    /// GPUs, so multi-node jobs fill one group before spilling over
+    fn locality_score(job: &Job, node: &Node, candidates: &[&Node]) -> i64 {
+        let resources = &job.descriptor.resources;
+        let locality = &job.descriptor.locality;
+        let mut score = 0;
+        
+        if let Some(position) = locality.preferred_nodes.iter().position(|id| id == &node.id) {
+            score += 1_000_000 - position as i64;
+        }
+        
+        let group_gpus = |label: &str| -> i64 {
+            let Some(group) = node.labels.get(label) else {
+                return 0;
+            };
+            candidates.iter()
+                .filter(|n| n.labels.get(label) == Some(group))
+                .map(|n| n.eligible_gpus(resources).len() as i64)
+                .sum()
+        };
+        if locality.prefer_same_nvswitch_domain {
+            score += group_gpus(NVSWITCH_DOMAIN_LABEL);
+        }
+        if locality.prefer_same_rack {
+            score += group_gpus(RACK_LABEL);
+        }
+        
+        score
+    }
+    
+    /// Node pools to try for a multi-node job, in order: each NVSwitch
+    /// domain, then each rack (when preferred), then all candidates
+    fn node_pools<'a>(job: &Job, candidates: &'a [Node]) -> Vec<Vec<&'a Node>> {
+        let locality = &job.descriptor.locality;
+        let mut pools = vec![];
+        
+        for (preferred, label) in [
+            (locality.prefer_same_nvswitch_domain, NVSWITCH_DOMAIN_LABEL),
+            (locality.prefer_same_rack, RACK_LABEL),
+        ] {
+            if !preferred {
+                continue;
+            }
+            
+            // Groups in the order their best-ranked node appears
+            let mut groups: Vec<(&String, Vec<&Node>)> = vec![];
+            for node in candidates {
+                let Some(group) = node.labels.get(label) else {
+                    continue;
+                };
+                match groups.iter_mut().find(|(g, _)| *g == group) {
+                    Some((_, members)) => members.push(node),
+                    None => groups.push((group, vec![node])),
+                }
+            }
+            pools.extend(groups.into_iter().map(|(_, members)| members));
+        }
+        
+        pools.push(candidates.iter().collect());
+        pools
+    }
+    
+    /// Gang scheduling: all or nothing allocation
+    fn gang_schedule(
+        &self,
+        job: &Job,
+        candidates: &[Node],
+        required_gpus: usize,
+    ) -> Option<SchedulingDecision> {
+        let resources = &job.descriptor.resources;
+        
+        // First try: single node with enough GPUs
+        if self.config.prefer_same_node || job.descriptor.locality.prefer_same_node {
+            for node in candidates {
+                if let Some(gpu_ids) = self.pick_gpus(node, resources, required_gpus) {
+                    // Allocate all GPUs from this node
+                    let mut allocations = HashMap::new();
+                    allocations.insert(node.id.clone(), gpu_ids);
+                    
+                    return Some(SchedulingDecision {
+                        job_id: job.id.to_string(),
+                        allocations,
+                        gang_allocated: true,
+                        grown: false,
+                        preempted: vec![],
+                        cpu: HashMap::new(),
+                    });
+                }
+            }
+        }
+        
+        // Second try: spread across multiple nodes, staying within one
+        // NVSwitch domain or rack if the job prefers it and one has room
+        for pool in Self::node_pools(job, candidates) {
+            if let Some(allocations) = self.fill_nodes(resources, &pool, required_gpus) {
+                return Some(SchedulingDecision {
+                    job_id: job.id.to_string(),
+                    allocations,
+                    gang_allocated: true,
+                    grown: false,
+                    preempted: vec![],
+                    cpu: HashMap::new(),
+                });
+            }
+        }
+        
+        debug!("Not enough eligible GPUs for gang job {}: need {}", job.id, required_gpus);
+        None
+    }
+    
+    /// Greedy allocation across nodes in order; `None` if the pool is short
+    fn fill_nodes(
+        &self,
+        resources: &ResourceRequirements,
+        pool: &[&Node],
+        required_gpus: usize,
+    ) -> Option<HashMap<String, Vec<String>>> {
+        let total_available: usize = pool.iter()
+            .map(|n| n.eligible_gpus(resources).len())
+            .sum();
+        
+        if total_available < required_gpus {
+            return None;
+        }
+        
+        let mut allocations = HashMap::new();
+        let mut remaining = required_gpus;
+        
+        for node in pool {
+            if remaining == 0 {
+                break;
+            }
+            
+            let to_allocate = remaining.min(node.eligible_gpus(resources).len());
+            if to_allocate == 0 {
+                continue;
+            }
+            
+            let gpu_ids = self.pick_gpus(node, resources, to_allocate)?;
+            allocations.insert(node.id.clone(), gpu_ids);
+            remaining -= to_allocate;
+        }
+        
+        Some(allocations)
+    }
+    
+    /// Spread scheduling: deal GPUs one at a time across the ranked nodes so
+    /// a single node failure takes out as few of the job's GPUs as possible
+    fn spread_schedule(
+        &self,
+        job: &Job,
+        candidates: &[Node],
+        required_gpus: usize,
+    ) -> Option<SchedulingDecision> {
+        let resources = &job.descriptor.resources;
+        let capacity: Vec<usize> = candidates.iter()
+            .map(|n| n.eligible_gpus(resources).len())
+            .collect();
+        
+        if capacity.iter().sum::<usize>() < required_gpus {
+            debug!("Not enough eligible GPUs for spread job {}: need {}", job.id, required_gpus);
+            return None;
+        }
+        
+        let mut counts = vec![0; candidates.len()];
+        let mut remaining = required_gpus;
+        while remaining > 0 {
+            for (count, available) in counts.iter_mut().zip(&capacity) {
+                if remaining > 0 && *count < *available {
+                    *count += 1;
+                    remaining -= 1;
+                }
+            }
+        }
+        
+        let mut allocations = HashMap::new();
+        for (node, count) in candidates.iter().zip(counts) {
+            if count > 0 {
+                allocations.insert(node.id.clone(), self.pick_gpus(node, resources, count)?);
+            }
+        }
+        
+        Some(SchedulingDecision {
+            job_id: job.id.to_string(),
+            allocations,
+            gang_allocated: false,
+            grown: false,
+            preempted: vec![],
+            cpu: HashMap::new(),
+        })
+    }
+    
+    /// Schedule CPU-only job: locality preferences first, then the node
+    /// the `cpu_placement` strategy favours
+    fn schedule_cpu_job(&self, job: &Job, candidates: &[Node]) -> Option<SchedulingDecision> {
+        let refs: Vec<&Node> = candidates.iter().collect();
+        let resources = &job.descriptor.resources;
+        
+        // Load after placing the job: the busier of cores and memory, in
+        // millionths so it orders as an integer
+        let load = |node: &Node| -> i64 {
+            let topology = &node.topology;
+            let cores = (topology.cpu_cores - node.available_cpu_cores() + resources.cpu_cores) as f64
+                / topology.cpu_cores.max(1) as f64;
+            let memory = (topology.cpu_memory - node.available_memory() + resources.cpu_memory) as f64
+                / topology.cpu_memory.max(1) as f64;
+            (cores.max(memory) * 1e6) as i64
+        };
+        
+        // Ties keep rank order (node ID)
+        let best = candidates.iter().rev().max_by_key(|node| {
+            let fullness = match self.config.cpu_placement {
+                CpuPlacement::Pack => load(node),
+                CpuPlacement::Spread => -load(node),
+            };
+            (Self::locality_score(job, node, &refs), fullness)
+        });
+        
+        best.map(|node| SchedulingDecision {
+            job_id: job.id.to_string(),
+            allocations: HashMap::from([(node.id.clone(), vec![])]),
+            gang_allocated: false,
+            grown: false,
+            preempted: vec![],
+            cpu: HashMap::new(),
+        })
+    }
+    
+    /// Update a job's state (e.g. reported by the node agent).
+    ///
+    /// Terminal states release the job's resources on its nodes.
+    pub fn update_job_state(&self, job_id: &str, state: JobState, actor: Actor, message: &str) -> Result<()> {
+        let mut jobs = self.jobs.write();
+        let mut queue = self.pending_queue.write();
+        let job = jobs.get_mut(job_id)
+            .ok_or_else(|| Error::Job(format!("Job not found: {}", job_id)))?;
+        
+        if job.state.is_terminal() {
+            return Err(Error::Job(format!(
+                "Job {} already finished in state {:?}", job_id, job.state
+            )));
+        }
+        
+        job.transition(state, actor, message)?;
+        
+        if state.is_terminal() {
+            self.finished(job_id, job, &mut queue);
+        }
+        self.persist(job)
+    }
+    
+    /// Bookkeeping for a job that just reached a terminal state: dequeue
+    /// it, release its resources, and schedule a retry if it failed and
+    /// has retries left (the reaper requeues it)
+    fn finished(&self, job_id: &str, job: &mut Job, queue: &mut PriorityQueue<String, QueueKey>) {
+        queue.remove(job_id);
+        self.nodes.release_job(job_id);
+        info!("Job {} finished as {:?}, resources released", job_id, job.state);
+        
+        if job.state == JobState::Failed && job.can_retry() {
+            let delay = self.retry_backoff(job.retry_count);
+            job.retry_after = Some(Utc::now() + delay);
+            info!("Job {} will be retried in {}s", job_id, delay.num_seconds());
+        }
+    }
+    
+    /// Delay before retry number `retry_count + 1`: base * 2^retry_count, capped
+    fn retry_backoff(&self, retry_count: u32) -> chrono::Duration {
+        let factor = 1u64.checked_shl(retry_count).unwrap_or(u64::MAX);
+        let secs = self.config.retry_backoff_base_secs
+            .saturating_mul(factor)
+            .min(self.config.retry_backoff_max_secs);
+        chrono::Duration::seconds(secs as i64)
+    }
+    
+    /// Enforce runtime limits, wait deadlines and retries as of now
+    pub fn reap(&self) -> ReapReport {
+        self.reap_at(Utc::now())
+    }
+    
+    /// Enforce runtime limits, wait deadlines and retries as of `now`
+    pub fn reap_at(&self, now: DateTime<Utc>) -> ReapReport {
+        let mut report = ReapReport::default();
+        let mut jobs = self.jobs.write();
+        let mut queue = self.pending_queue.write();
+        
+        for (job_id, job) in jobs.iter_mut() {
+            let deadline = matches!(job.state, JobState::Scheduled | JobState::Running)
+                .then(|| self.runtime_deadline(job))
+                .flatten();
+            let waiting = matches!(
+                job.state, JobState::Pending | JobState::Queued | JobState::Suspended
+            );
+            let orphaned = waiting.then(|| self.reservation_gone(job, now)).flatten();
+            match job.state {
+                JobState::Scheduled | JobState::Running
+                    if deadline.as_ref().is_some_and(|(end, _)| *end <= now) =>
+                {
+                    let (_, message) = deadline.expect("checked above");
+                    if let Err(e) = job.transition(JobState::Timeout, Actor::Scheduler, &message) {
+                        warn!("Failed to time out job {}: {}", job_id, e);
+                        continue;
+                    }
+                    // Resources are held until the agent has killed it
+                    self.nodes.stop_job(job_id);
+                    warn!("Job {} timed out", job_id);
+                    self.mark_dirty(job);
+                    report.timed_out.push(job_id.clone());
+                }
+                JobState::Pending | JobState::Queued | JobState::Suspended
+                    if orphaned.is_some()
+                        || self.wait_deadline(job).is_some_and(|deadline| deadline <= now) =>
+                {
+                    let message = orphaned.unwrap_or_else(|| format!(
+                        "Exceeded max wait time of {}s", job.descriptor.policy.max_wait_time_seconds
+                    ));
+                    if let Err(e) = job.transition(JobState::Failed, Actor::Scheduler, &message) {
+                        warn!("Failed to expire job {}: {}", job_id, e);
+                        continue;
+                    }
+                    queue.remove(job_id);
+                    warn!("Job {} failed: {}", job_id, message);
+                    self.mark_dirty(job);
+                    report.expired.push(job_id.clone());
+                }
+                JobState::Failed if job.retry_after.is_some_and(|at| at <= now) => {
+                    let retried = self.requeue(job_id, job, &mut queue, now);
+                    if retried {
+                        report.retried.push(job_id.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+        
+        // Ended reservations have timed out or failed all their jobs above
+        let mut reservations = self.reservations.write();
+        let ended: Vec<String> = reservations.values()
+            .filter(|reservation| reservation.end <= now)
+            .map(|reservation| reservation.name.clone())
+            .collect();
+        for name in ended {
+            if let Some(store) = &self.store {
+                if let Err(e) = store.delete_reservation(&name) {
+                    warn!("Failed to delete ended reservation {}: {}", name, e);
+                    continue;
+                }
+            }
+            reservations.remove(&name);
+            info!("Reservation {} ended and was removed", name);
+        }
+        drop(reservations);
+        
+        self.flush_logged(&jobs);
+        report
+    }
+    
+    /// Suspend a scheduled or running job and tell its agents to stop it,
+    /// returning the GPUs it holds until they confirm. `requeue_stopped`
+    /// puts it back in the queue then.
+    fn suspend(&self, job_id: &str, job: &mut Job, reason: &str) -> HashMap<String, Vec<String>> {
+        self.nodes.stop_job(job_id);
+        let released = std::mem::take(&mut job.allocated_gpus);
+        job.allocated_nodes.clear();
+        job.allocated_cpu.clear();
+        job.transition(JobState::Suspended, Actor::Scheduler, reason)
+            .expect("suspended jobs are scheduled or running");
+        self.mark_dirty(job);
+        info!("Job {} suspended: {}", job_id, reason);
+        released
+    }
+    
+    /// Put suspended jobs back in the queue once no agent is still
+    /// stopping them, with their original submit time so they keep their
+    /// place
+    fn requeue_stopped(&self, jobs: &HashMap<String, Job>, queue: &mut PriorityQueue<String, QueueKey>) {
+        let stopping = self.nodes.stopping_jobs();
+        for (job_id, job) in jobs {
+            if job.state == JobState::Suspended
+                && queue.get(job_id).is_none()
+                && stopping.binary_search(job_id).is_err()
+            {
+                queue.push(job_id.clone(), Self::queue_key(job));
+                info!("Job {} stopped and requeued", job_id);
+            }
+        }
+    }
+    
+    /// Record a heartbeat, releasing the jobs the agent reports stopped,
+    /// and return the updated node; its `stopping_jobs` are still to stop
+    pub fn heartbeat(&self, node_id: &str, gpus: &[GpuStatus], cpu_memory_free: u64, stopped: &[String]) -> Result<Node> {
+        if stopped.is_empty() {
+            return self.nodes.heartbeat(node_id, gpus, cpu_memory_free);
+        }
+        
+        let jobs = self.jobs.read();
+        let mut queue = self.pending_queue.write();
+        self.nodes.heartbeat(node_id, gpus, cpu_memory_free)?;
+        let confirmed = self.nodes.confirm_stopped(node_id, stopped)?;
+        if !confirmed.is_empty() {
+            debug!("Node {} stopped jobs {:?}", node_id, confirmed);
+            self.requeue_stopped(&jobs, &mut queue);
+        }
+        self.nodes.get(node_id).ok_or_else(|| Error::Node(format!("Node not found: {}", node_id)))
+    }
+    
+    /// Put a failed job with retries left back in the queue, consuming one
+    /// retry; returns whether it was requeued
+    fn requeue(
+        &self,
+        job_id: &str,
+        job: &mut Job,
+        queue: &mut PriorityQueue<String, QueueKey>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let message = format!(
+            "Retry {}/{} after failure: {}",
+            job.retry_count + 1, job.descriptor.policy.max_retries, job.message
+        );
+        if let Err(e) = job.transition(JobState::Queued, Actor::Scheduler, &message) {
+            warn!("Failed to retry job {}: {}", job_id, e);
+            return false;
+        }
+        job.retry_count += 1;
+        job.retry_after = None;
+        job.requeue_time = Some(now);
+        job.schedule_time = None;
+        job.start_time = None;
+        job.end_time = None;
+        job.allocated_nodes.clear();
+        job.allocated_gpus.clear();
+        job.allocated_cpu.clear();
+        queue.push(job_id.to_string(), Self::queue_key(job));
+        info!("Job {} requeued: {}", job_id, message);
+        self.mark_dirty(job);
+        true
+    }
+    
+    /// Mark nodes that missed their heartbeat deadline `Unreachable` and
+    /// deal with the jobs placed on unreachable nodes
+    ///
+    /// Each such job is failed and its resources released; jobs with
+    /// retries left go straight back into the queue. An unreachable node
+    /// gets no new work until its agent registers again (see
+    /// `register_node`). Node drains are advanced too (see `drain`).
+    pub fn monitor_nodes(&self) -> NodeMonitorReport {
+        let mut report = NodeMonitorReport {
+            unreachable: self.nodes.mark_unreachable(),
+            ..Default::default()
+        };
+        for node_id in &report.unreachable {
+            warn!("Node {} is unreachable", node_id);
+        }
+        
+        let mut jobs = self.jobs.write();
+        let mut queue = self.pending_queue.write();
+        let now = Utc::now();
+        
+        // Agents that stopped responding cannot confirm stops
+        for node_id in &report.unreachable {
+            self.nodes.forget_stopping(node_id);
+        }
+        self.requeue_stopped(&jobs, &mut queue);
+        
+        for (job_id, job) in jobs.iter_mut() {
+            if !matches!(job.state, JobState::Scheduled | JobState::Running) {
+                continue;
+            }
+            let Some(lost) = job.allocated_nodes.iter().find(|node_id| {
+                self.nodes.get(node_id).is_some_and(|node| node.health == NodeHealth::Unreachable)
+            }) else {
+                continue;
+            };
+            
+            let message = format!("Node {} unreachable", lost);
+            self.lose_job(job_id, job, &mut queue, now, &message, &mut report);
+        }
+        
+        self.progress_drains(&mut jobs, now, &mut report);
+        self.flush_logged(&jobs);
+        report
+    }
+    
+    /// Remove a node whose agent is shutting down; its scheduled and
+    /// running jobs are lost and failed or requeued as in `monitor_nodes`
+    pub fn deregister_node(&self, node_id: &str) -> Result<NodeMonitorReport> {
+        let mut jobs = self.jobs.write();
+        let mut queue = self.pending_queue.write();
+        if self.nodes.get(node_id).is_none() {
+            return Err(Error::Node(format!("Node not found: {}", node_id)));
+        }
+        
+        let mut report = NodeMonitorReport::default();
+        let message = format!("Node {} deregistered", node_id);
+        let now = Utc::now();
+        for (job_id, job) in jobs.iter_mut() {
+            if matches!(job.state, JobState::Scheduled | JobState::Running)
+                && job.allocated_nodes.iter().any(|n| n == node_id)
+            {
+                self.lose_job(job_id, job, &mut queue, now, &message, &mut report);
+            }
+        }
+        
+        self.nodes.deregister(node_id)?;
+        info!("Node {} deregistered", node_id);
+        self.requeue_stopped(&jobs, &mut queue);
+        self.flush_dirty(&jobs)?;
+        Ok(report)
+    }
+    
+    /// Fail a scheduled or running job whose node is gone and release its
+    /// resources, requeueing it right away if it has retries left
+    fn lose_job(
+        &self,
+        job_id: &str,
+        job: &mut Job,
+        queue: &mut PriorityQueue<String, QueueKey>,
+        now: DateTime<Utc>,
+        message: &str,
+        report: &mut NodeMonitorReport,
+    ) {
+        if let Err(e) = job.transition(JobState::Failed, Actor::Scheduler, message) {
+            warn!("Failed to fail job {}: {}", job_id, e);
+            return;
+        }
+        self.nodes.release_job(job_id);
+        warn!("Job {} failed: {}", job_id, message);
+        
+        if job.can_retry() && self.requeue(job_id, job, queue, now) {
+            report.requeued.push(job_id.to_string());
+        } else {
+            self.mark_dirty(job);
+            report.failed.push(job_id.to_string());
+        }
+    }
+    
+    /// Finish drains whose nodes are empty, first suspending the jobs
+    /// still on nodes past their drain deadline (requeued once their agent
+    /// stops them)
+    fn progress_drains(
+        &self,
+        jobs: &mut HashMap<String, Job>,
+        now: DateTime<Utc>,
+        report: &mut NodeMonitorReport,
+    ) {
+        for node in self.nodes.healthy_nodes() {
+            let NodeSchedulingState::Draining { deadline } = node.scheduling else {
+                continue;
+            };
+            let remaining: Vec<String> = jobs.iter()
+                .filter(|(_, job)| {
+                    matches!(job.state, JobState::Scheduled | JobState::Running)
+                        && job.allocated_nodes.contains(&node.id)
+                })
+                .map(|(job_id, _)| job_id.clone())
+                .collect();
+            
+            if !remaining.is_empty() {
+                if deadline.is_none_or(|deadline| deadline > now) {
+                    continue;
+                }
+                let reason = format!("Node {} drained", node.id);
+                for job_id in remaining {
+                    let job = jobs.get_mut(&job_id).expect("collected from jobs");
+                    self.suspend(&job_id, job, &reason);
+                    report.evicted.push(job_id);
+                }
+                continue;
+            }
+            
+            // Drained once the agent confirms the evicted jobs stopped
+            if !node.stopping_jobs.is_empty() {
+                continue;
+            }
+            
+            if let Err(e) = self.set_node_scheduling(&node.id, NodeSchedulingState::Drained) {
+                warn!("Failed to finish draining node {}: {}", node.id, e);
+                continue;
+            }
+            report.drained.push(node.id);
+        }
+    }
+    
+    /// Stop placing new jobs on a node
+    pub fn cordon(&self, node_id: &str) -> Result<Node> {
+        self.set_node_scheduling(node_id, NodeSchedulingState::Cordoned)
+    }
+    
+    /// Cordon a node and empty it: running jobs may finish until the
+    /// deadline, after which `monitor_nodes` suspends them for requeueing
+    pub fn drain(&self, node_id: &str, deadline: Option<DateTime<Utc>>) -> Result<Node> {
+        self.set_node_scheduling(node_id, NodeSchedulingState::Draining { deadline })
+    }
+    
+    /// Let a cordoned or drained node take new jobs again
+    pub fn uncordon(&self, node_id: &str) -> Result<Node> {
+        self.set_node_scheduling(node_id, NodeSchedulingState::Schedulable)
+    }
+    
+    /// Change a node's cordon / drain state and persist it
+    fn set_node_scheduling(&self, node_id: &str, scheduling: NodeSchedulingState) -> Result<Node> {
+        let node = self.nodes.set_scheduling(node_id, scheduling)?;
+        if let Some(store) = &self.store {
+            store.store_node(NodeState::of(&node))?;
+        }
+        info!("Node {} is now {:?}", node_id, scheduling);
+        Ok(node)
+    }
+    
+    /// Run `schedule_cycle` every `period` until the task is dropped
+    pub async fn run_scheduling_loop(self: Arc<Self>, period: Duration) {
+        let mut ticker = tokio::time::interval(period);
+        loop {
+            ticker.tick().await;
+            let decisions = self.schedule_cycle();
+            if !decisions.is_empty() {
+                debug!("Scheduled {} jobs", decisions.len());
+            }
+        }
+    }
+    
+    /// Run `monitor_nodes` every `period` until the task is dropped
+    pub async fn run_node_monitor(self: Arc<Self>, period: Duration) {
+        let mut ticker = tokio::time::interval(period);
+        loop {
+            ticker.tick().await;
+            let report = self.monitor_nodes();
+            if report != NodeMonitorReport::default() {
+                debug!("Node monitor: {:?}", report);
+            }
+        }
+    }
+    
+    /// Run `reap` every `period` until the task is dropped
+    pub async fn run_reaper(self: Arc<Self>, period: Duration) {
+        let mut ticker = tokio::time::interval(period);
+        loop {
+            ticker.tick().await;
+            let report = self.reap();
+            if !report.timed_out.is_empty() || !report.expired.is_empty() || !report.retried.is_empty() {
+                debug!("Reaper: {:?}", report);
+            }
+        }
+    }
+    
+    /// Get job status
+    pub fn get_job(&self, job_id: &str) -> Option<Job> {
+        self.jobs.read().get(job_id).cloned()
+    }
+    
+    /// Configured partition by name
+    pub fn partition(&self, name: &str) -> Option<&PartitionConfig> {
+        self.config.partitions.iter().find(|p| p.name == name)
+    }
+    
+    /// Healthy nodes in the job's partition that reservations leave to it:
+    /// a job in a reservation gets only its nodes, any other job only nodes
+    /// no reservation claims before the job's runtime limit runs out
+    fn placeable_nodes(&self, job: &Job) -> Vec<Node> {
+        let Some(partition) = self.partition(&job.descriptor.policy.queue_name) else {
+            return vec![];
+        };
+        
+        let now = Utc::now();
+        let policy = &job.descriptor.policy;
+        let until = (policy.max_runtime_seconds > 0)
+            .then(|| now + chrono::Duration::seconds(policy.max_runtime_seconds as i64));
+        let reservations = self.reservations.read();
+        let own = policy.reservation.as_ref().and_then(|name| reservations.get(name));
+        
+        self.nodes.schedulable_nodes()
+            .into_iter()
+            .filter(|node| partition.contains(node))
+            .filter(|node| match own {
+                Some(reservation) => reservation.covers(&node.id),
+                None => !reservations.values()
+                    .any(|r| r.covers(&node.id) && r.overlaps(now, until)),
+            })
+            .collect()
+    }
+    
+    /// Wait deadline of a queued job; jobs in a reservation only start
+    /// waiting once its window opens
+    fn wait_deadline(&self, job: &Job) -> Option<DateTime<Utc>> {
+        let deadline = job.wait_deadline()?;
+        let opens = job.descriptor.policy.reservation.as_ref()
+            .and_then(|name| self.reservations.read().get(name).map(|r| r.start));
+        match opens {
+            Some(start) if start > job.requeue_time.unwrap_or(job.submit_time) => {
+                let limit = job.descriptor.policy.max_wait_time_seconds as i64;
+                Some(start + chrono::Duration::seconds(limit))
+            }
+            _ => Some(deadline),
+        }
+    }
+    
+    /// When a scheduled or running job must stop, and the reason to record
+    /// then: its runtime limit, or the end of its reservation if sooner
+    fn runtime_deadline(&self, job: &Job) -> Option<(DateTime<Utc>, String)> {
+        let limit = job.expected_end_time().map(|end| {
+            (end, format!("Exceeded max runtime of {}s", job.descriptor.policy.max_runtime_seconds))
+        });
+        let window = job.descriptor.policy.reservation.as_ref().and_then(|name| {
+            let end = self.reservations.read().get(name)?.end;
+            Some((end, format!("Reservation {} ended at {}", name, end.to_rfc3339())))
+        });
+        match (limit, window) {
+            (Some(limit), Some(window)) => Some(if window.0 < limit.0 { window } else { limit }),
+            (limit, window) => limit.or(window),
+        }
+    }
+    
+    /// Why a job in a reservation cannot start yet, if it cannot
+    fn reservation_wait(&self, job: &Job, now: DateTime<Utc>) -> Option<String> {
+        if let Some(reason) = self.reservation_gone(job, now) {
+            return Some(reason);
+        }
+        let name = job.descriptor.policy.reservation.as_ref()?;
+        let start = self.reservations.read().get(name)?.start;
+        (now < start).then(|| format!(
+            "Waiting for reservation {} to start at {}", name, start.to_rfc3339()
+        ))
+    }
+    
+    /// Why a job's reservation can no longer run it, if it was deleted or
+    /// has ended; the reaper fails such jobs
+    fn reservation_gone(&self, job: &Job, now: DateTime<Utc>) -> Option<String> {
+        let name = job.descriptor.policy.reservation.as_ref()?;
+        match self.reservations.read().get(name) {
+            None => Some(format!("Reservation {} no longer exists", name)),
+            Some(reservation) if now >= reservation.end => {
+                Some(format!("Reservation {} has ended", name))
+            }
+            Some(_) => None,
+        }
+    }
+    
+    /// Create an advance reservation
+    ///
+    /// Without named nodes, the fewest healthy nodes with `gpu_count` GPUs
+    /// are chosen. Reserved nodes may not overlap another reservation's
+    /// window. Jobs already running there are not evicted, not even once
+    /// the window opens (see the `reservation` module).
+    pub fn create_reservation(&self, mut reservation: Reservation) -> Result<Reservation> {
+        let mut reservations = self.reservations.write();
+        if reservations.contains_key(&reservation.name) {
+            return Err(Error::Scheduling(format!("Reservation {} already exists", reservation.name)));
+        }
+        if reservation.start >= reservation.end {
+            return Err(Error::Scheduling(format!("Reservation {} ends before it starts", reservation.name)));
+        }
+        
+        let clashes = |node_id: &str| reservations.values().any(|other| {
+            other.covers(node_id) && other.start < reservation.end && reservation.start < other.end
+        });
+        
+        if reservation.nodes.is_empty() {
+            if reservation.gpu_count == 0 {
+                return Err(Error::Scheduling("Reservation needs nodes or a GPU count".to_string()));
+            }
+            let mut nodes: Vec<Node> = self.nodes.schedulable_nodes()
+                .into_iter()
+                .filter(|node| !clashes(&node.id))
+                .collect();
+            nodes.sort_by(|a, b| b.total_gpus().cmp(&a.total_gpus()).then_with(|| a.id.cmp(&b.id)));
+            
+            let mut gpus = 0;
+            let mut chosen = vec![];
+            for node in nodes {
+                if gpus >= reservation.gpu_count as usize {
+                    break;
+                }
+                gpus += node.total_gpus();
+                chosen.push(node.id);
+            }
+            if gpus < reservation.gpu_count as usize {
+                return Err(Error::Scheduling(format!(
+                    "Only {} of {} GPUs are free to reserve", gpus, reservation.gpu_count
+                )));
+            }
+            reservation.nodes = chosen;
+        } else {
+            for node_id in &reservation.nodes {
+                if self.nodes.get(node_id).is_none() {
+                    return Err(Error::Node(format!("Node not found: {}", node_id)));
+                }
+                if clashes(node_id) {
+                    return Err(Error::Scheduling(format!(
+                        "Node {} is already reserved during that window", node_id
+                    )));
+                }
+            }
+        }
+        
+        if let Some(store) = &self.store {
+            store.store_reservation(&reservation)?;
+        }
+        info!(
+            "Reservation {} created on {:?} from {} to {}",
+            reservation.name, reservation.nodes, reservation.start, reservation.end
+        );
+        reservations.insert(reservation.name.clone(), reservation.clone());
+        Ok(reservation)
+    }
+    
+    /// Delete an advance reservation; jobs submitted into it stay queued
+    pub fn delete_reservation(&self, name: &str) -> Result<()> {
+        let mut reservations = self.reservations.write();
+        if let (Some(store), true) = (&self.store, reservations.contains_key(name)) {
+            store.delete_reservation(name)?;
+        }
+        reservations.remove(name)
+            .map(|_| info!("Reservation {} deleted", name))
+            .ok_or_else(|| Error::Scheduling(format!("Reservation not found: {}", name)))
+    }
+    
+    /// All advance reservations, by start time
+    pub fn reservations(&self) -> Vec<Reservation> {
+        let mut reservations: Vec<Reservation> = self.reservations.read().values().cloned().collect();
+        reservations.sort_by(|a, b| a.start.cmp(&b.start).then_with(|| a.name.cmp(&b.name)));
+        reservations
+    }
+    
+    /// Depth and usage of every partition
+    pub fn queue_stats(&self) -> Vec<QueueStats> {
+        let jobs = self.jobs.read();
+        let queue = self.pending_queue.read();
+        let nodes = self.nodes.healthy_nodes();
+        let in_partition = |job: &Job, partition: &PartitionConfig| {
+            job.descriptor.policy.queue_name == partition.name
+        };
+        
+        self.config.partitions.iter()
+            .map(|partition| QueueStats {
+                name: partition.name.clone(),
+                priority_tier: partition.priority_tier,
+                nodes: nodes.iter().filter(|node| partition.contains(node)).count(),
+                depth: queue.iter()
+                    .filter(|(id, _)| jobs.get(*id).is_some_and(|job| in_partition(job, partition)))
+                    .count(),
+                usage: ResourceUsage::of(jobs.values(), |job| in_partition(job, partition)),
+            })
+            .collect()
+    }
+    
+    /// Get an array job's parent record and the aggregate state of its children
+    pub fn get_array(&self, array_id: &str) -> Option<(ArrayJob, ArrayStatus)> {
+        let array = self.arrays.read().get(array_id).cloned()?;
+        let jobs = self.jobs.read();
+        let status = ArrayStatus::of(array.children.iter().filter_map(|id| jobs.get(id)));
+        Some((array, status))
+    }
+    
+    /// Get a job's state change history, oldest first
+    pub fn job_events(&self, job_id: &str) -> Option<Vec<JobEvent>> {
+        self.jobs.read().get(job_id).map(|job| job.events().to_vec())
+    }
+    
+    /// Get all jobs with a specific state
+    pub fn jobs_with_state(&self, state: JobState) -> Vec<Job> {
+        self.jobs.read()
+            .values()
+            .filter(|j| j.state == state)
+            .cloned()
+            .collect()
+    }
+    
+    /// Resources currently held for the highest-priority blocked job
+    pub fn backfill_reservation(&self) -> Option<BackfillReservation> {
+        self.reservation.read().clone()
+    }
+    
+    /// Get queue size
+    pub fn queue_size(&self) -> usize {
+        self.pending_queue.read().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::{ArraySpec, JobDescriptor, ARRAY_INDEX_ENV};
+    use crate::node::{GpuDevice, NodeTopology};
+    use crate::quota::FairShareConfig;
+    
+    fn create_test_node(id: &str, gpu_count: usize) -> Node {
+        let gpus: Vec<GpuDevice> = (0..gpu_count)
+            .map(|i| GpuDevice {
+                device_id: format!("cuda:{}", i),
+                device_name: "NVIDIA A100".to_string(),
+                uuid: format!("GPU-{}", i),
+                total_memory: 80 * 1024 * 1024 * 1024,
+                free_memory: 80 * 1024 * 1024 * 1024,
+                utilization: 0.0,
+                temperature: 40,
+                allocated: false,
+                allocated_job_id: None,
+                numa_node: Some(if i < gpu_count / 2 { 0 } else { 1 }),
+            })
+            .collect();
+        
+        let topology = NodeTopology {
+            gpus,
+            cpu_cores: 64,
+            cpu_memory: 512 * 1024 * 1024 * 1024,
+            cpu_memory_free: 500 * 1024 * 1024 * 1024,
+            numa_nodes: 2,
+            nvlink_present: true,
+            nvlink_version: 3,
+            nvswitch_present: false,
+            rdma_capable: true,
+            nvlink_connections: vec![],
+            numa_cpu_cores: HashMap::new(),
+        };
+        
+        Node::new(
+            id.to_string(),
+            format!("{}.local", id),
+            "192.168.1.1".to_string(),
+            topology,
+        )
+    }
+    
+    #[test]
+    fn test_scheduler_submit() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 4)).unwrap();
+        
+        let scheduler = Scheduler::new(registry, SchedulerConfig::default());
+        
+        let job_id = scheduler.submit(gpu_job(2)).unwrap();
+        
+        assert_eq!(scheduler.queue_size(), 1);
+        
+        // Run scheduling
+        let decisions = scheduler.schedule_cycle();
+        
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(scheduler.queue_size(), 0);
+    }
+    
+    fn gpu_job(gpu_count: u32) -> Job {
+        Job::new(JobDescriptor {
+            name: "gpu-job".to_string(),
+            user_id: "user1".to_string(),
+            project_id: "project1".to_string(),
+            command: "python".to_string(),
+            working_directory: "/app".to_string(),
+            resources: crate::job::ResourceRequirements {
+                gpu_count,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+    
+    #[test]
+    fn test_no_double_booking_in_one_cycle() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 2)).unwrap();
+        let scheduler = Scheduler::new(registry.clone(), SchedulerConfig::default());
+        
+        for _ in 0..3 {
+            scheduler.submit(gpu_job(1)).unwrap();
+        }
+        
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 2);
+        assert_eq!(scheduler.queue_size(), 1);
+        
+        let gpus: Vec<&String> = decisions.iter()
+            .flat_map(|d| d.allocations.values().flatten())
+            .collect();
+        assert_ne!(gpus[0], gpus[1]);
+        assert_eq!(registry.get("node-1").unwrap().available_gpus(), 0);
+    }
+    
+    #[test]
+    fn test_resources_released_on_finish_and_cancel() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 2)).unwrap();
+        let scheduler = Scheduler::new(registry.clone(), SchedulerConfig::default());
+        
+        let first = scheduler.submit(gpu_job(1)).unwrap();
+        let second = scheduler.submit(gpu_job(1)).unwrap();
+        scheduler.schedule_cycle();
+        assert_eq!(registry.get("node-1").unwrap().available_gpus(), 0);
+        
+        scheduler.update_job_state(&first, JobState::Running, Actor::Agent, "Started").unwrap();
+        scheduler.update_job_state(&first, JobState::Completed, Actor::Agent, "Exit 0").unwrap();
+        assert_eq!(registry.get("node-1").unwrap().available_gpus(), 1);
+        assert!(scheduler.update_job_state(&first, JobState::Running, Actor::Agent, "again").is_err());
+        
+        scheduler.update_job_state(&second, JobState::Running, Actor::Agent, "Started").unwrap();
+        scheduler.cancel(&second, Actor::User, "user").unwrap();
+        let node = registry.get("node-1").unwrap();
+        assert_eq!(node.stopping_jobs, vec![second.clone()]);
+        assert_eq!(node.available_gpus(), 1);
+        
+        let node = scheduler.heartbeat("node-1", &[], 0, std::slice::from_ref(&second)).unwrap();
+        assert_eq!(node.available_gpus(), 2);
+        assert!(node.running_jobs.is_empty());
+        assert_eq!(scheduler.get_job(&second).unwrap().state, JobState::Cancelled);
+    }
+    
+    fn policy_job(gpu_count: u32, priority: i32, preemptible: bool, can_preempt: bool) -> Job {
+        let mut job = gpu_job(gpu_count);
+        job.descriptor.policy.priority = priority;
+        job.descriptor.policy.preemptible = preemptible;
+        job.descriptor.policy.can_preempt_others = can_preempt;
+        job
+    }
+    
+    #[test]
+    fn test_queue_orders_by_priority_then_submit_time() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 1)).unwrap();
+        let scheduler = Scheduler::new(registry, SchedulerConfig::default());
+        
+        let early = scheduler.submit(policy_job(1, 10, false, false)).unwrap();
+        let _late = scheduler.submit(policy_job(1, 10, false, false)).unwrap();
+        let _low = scheduler.submit(policy_job(1, 5, false, false)).unwrap();
+        
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].job_id, early);
+    }
+    
+    #[test]
+    fn test_preemption_picks_minimal_victims() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 4)).unwrap();
+        let scheduler = Scheduler::new(registry.clone(), SchedulerConfig::default());
+        
+        let low = scheduler.submit(policy_job(2, 10, true, false)).unwrap();
+        let mid = scheduler.submit(policy_job(1, 20, true, false)).unwrap();
+        let pinned = scheduler.submit(policy_job(1, 1, false, false)).unwrap();
+        assert_eq!(scheduler.schedule_cycle().len(), 3);
+        for id in [&low, &mid, &pinned] {
+            scheduler.update_job_state(id, JobState::Running, Actor::Agent, "Started").unwrap();
+        }
+        
+        // Needs 2 GPUs: suspending `low` alone is enough
+        let urgent = scheduler.submit(policy_job(2, 100, false, true)).unwrap();
+        assert!(scheduler.schedule_cycle().is_empty());
+        let victim = scheduler.get_job(&low).unwrap();
+        assert_eq!(victim.state, JobState::Suspended);
+        assert!(victim.allocated_gpus.is_empty());
+        assert!(victim.message.contains(&urgent));
+        assert_eq!(scheduler.get_job(&mid).unwrap().state, JobState::Running);
+        
+        // Its GPUs stay held, without preempting more, until the agent
+        // confirms it stopped
+        let node = registry.get("node-1").unwrap();
+        assert_eq!(node.stopping_jobs, vec![low.clone()]);
+        assert_eq!(node.available_gpus(), 0);
+        assert!(scheduler.schedule_cycle().is_empty());
+        assert_eq!(scheduler.get_job(&mid).unwrap().state, JobState::Running);
+        assert_eq!(scheduler.queue_size(), 1);
+        
+        scheduler.heartbeat("node-1", &[], 0, std::slice::from_ref(&low)).unwrap();
+        assert!(registry.get("node-1").unwrap().stopping_jobs.is_empty());
+        assert_eq!(scheduler.queue_size(), 2);
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].job_id, urgent);
+        
+        let preempted = &decisions[0].preempted;
+        assert_eq!(preempted.len(), 1);
+        assert_eq!(preempted[0].job_id, low);
+        assert_eq!(preempted[0].preempted_by, urgent);
+        assert_eq!(preempted[0].released["node-1"].len(), 2);
+        assert_eq!(registry.get("node-1").unwrap().available_gpus(), 0);
+        
+        // The victim resumes once capacity frees up
+        scheduler.update_job_state(&urgent, JobState::Completed, Actor::Agent, "Exit 0").unwrap();
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions[0].job_id, low);
+        assert!(decisions[0].preempted.is_empty());
+    }
+    
+    #[test]
+    fn test_no_preemption_without_eligible_victims() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 2)).unwrap();
+        let scheduler = Scheduler::new(registry, SchedulerConfig::default());
+        
+        // Preemptible but higher priority, and non-preemptible
+        let higher = scheduler.submit(policy_job(1, 200, true, false)).unwrap();
+        let pinned = scheduler.submit(policy_job(1, 1, false, false)).unwrap();
+        scheduler.schedule_cycle();
+        
+        scheduler.submit(policy_job(1, 100, false, true)).unwrap();
+        assert!(scheduler.schedule_cycle().is_empty());
+        assert_eq!(scheduler.get_job(&higher).unwrap().state, JobState::Scheduled);
+        assert_eq!(scheduler.get_job(&pinned).unwrap().state, JobState::Scheduled);
+    }
+    
+    fn timed_job(gpu_count: u32, priority: i32, max_runtime_seconds: u64) -> Job {
+        let mut job = policy_job(gpu_count, priority, false, false);
+        job.descriptor.policy.max_runtime_seconds = max_runtime_seconds;
+        job
+    }
+    
+    #[test]
+    fn test_backfill_respects_reservation() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 4)).unwrap();
+        let scheduler = Scheduler::new(registry.clone(), SchedulerConfig::default());
+        
+        let running = scheduler.submit(timed_job(2, 50, 3600)).unwrap();
+        scheduler.schedule_cycle();
+        scheduler.update_job_state(&running, JobState::Running, Actor::Agent, "Started").unwrap();
+        
+        // Needs the whole node: blocked for up to an hour
+        let big = scheduler.submit(timed_job(4, 100, 0)).unwrap();
+        let unlimited = scheduler.submit(timed_job(1, 20, 0)).unwrap();
+        let too_long = scheduler.submit(timed_job(1, 15, 7200)).unwrap();
+        let short = scheduler.submit(timed_job(2, 10, 600)).unwrap();
+        
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].job_id, short);
+        
+        let reservation = scheduler.backfill_reservation().unwrap();
+        assert_eq!(reservation.job_id, big);
+        assert_eq!(reservation.allocations["node-1"].len(), 4);
+        let start = reservation.start_estimate.unwrap();
+        assert!(start > Utc::now() + chrono::Duration::minutes(59));
+        
+        for id in [&big, &unlimited, &too_long] {
+            assert_eq!(scheduler.get_job(id).unwrap().state, JobState::Queued);
+        }
+    }
+    
+    #[test]
+    fn test_backfill_reserves_cpu_for_cpu_only_job() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 0)).unwrap();
+        let scheduler = Scheduler::new(registry.clone(), SchedulerConfig::default());
+        let cpu_timed_job = |cores: u32, priority: i32, max_runtime_seconds: u64| {
+            let mut job = cpu_job(cores);
+            job.descriptor.policy.priority = priority;
+            job.descriptor.policy.max_runtime_seconds = max_runtime_seconds;
+            job
+        };
+        
+        let running = scheduler.submit(cpu_timed_job(40, 50, 3600)).unwrap();
+        scheduler.schedule_cycle();
+        scheduler.update_job_state(&running, JobState::Running, Actor::Agent, "Started").unwrap();
+        
+        // Needs 48 of 64 cores with 24 free: the free ones are held for it
+        let head = scheduler.submit(cpu_timed_job(48, 100, 0)).unwrap();
+        let unlimited = scheduler.submit(cpu_timed_job(8, 20, 0)).unwrap();
+        let short = scheduler.submit(cpu_timed_job(8, 10, 600)).unwrap();
+        
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].job_id, short);
+        assert_eq!(scheduler.backfill_reservation().unwrap().cpu["node-1"].cores, 48);
+        for id in [&head, &unlimited] {
+            assert_eq!(scheduler.get_job(id).unwrap().state, JobState::Queued);
+        }
+    }
+    
+    #[test]
+    fn test_backfill_disabled_starts_anything_that_fits() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 4)).unwrap();
+        let config = SchedulerConfig { backfill_enabled: false, ..Default::default() };
+        let scheduler = Scheduler::new(registry, config);
+        
+        let running = scheduler.submit(timed_job(2, 50, 3600)).unwrap();
+        scheduler.schedule_cycle();
+        scheduler.update_job_state(&running, JobState::Running, Actor::Agent, "Started").unwrap();
+        
+        scheduler.submit(timed_job(4, 100, 0)).unwrap();
+        let unlimited = scheduler.submit(timed_job(1, 20, 0)).unwrap();
+        
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].job_id, unlimited);
+        assert!(scheduler.backfill_reservation().is_none());
+    }
+    
+    fn owned_job(user: &str, project: &str, gpu_count: u32) -> Job {
+        let mut job = gpu_job(gpu_count);
+        job.descriptor.user_id = user.to_string();
+        job.descriptor.project_id = project.to_string();
+        job
+    }
+    
+    #[test]
+    fn test_quota_keeps_jobs_queued() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 8)).unwrap();
+        
+        let mut quotas = QuotaConfig::default();
+        quotas.users.insert("alice".to_string(), crate::quota::QuotaLimits {
+            max_gpus: Some(2),
+            ..Default::default()
+        });
+        quotas.default_project.max_running_jobs = Some(3);
+        let config = SchedulerConfig { quotas, ..Default::default() };
+        let scheduler = Scheduler::new(registry, config);
+        
+        let alice: Vec<String> = (0..3)
+            .map(|_| scheduler.submit(owned_job("alice", "vision", 1)).unwrap())
+            .collect();
+        let bob: Vec<String> = (0..4)
+            .map(|_| scheduler.submit(owned_job("bob", "nlp", 1)).unwrap())
+            .collect();
+        
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 5);
+        
+        let waiting = scheduler.get_job(&alice[2]).unwrap();
+        assert_eq!(waiting.state, JobState::Queued);
+        assert!(waiting.message.contains("user alice: GPU quota exceeded"), "{}", waiting.message);
+        
+        let waiting = scheduler.get_job(&bob[3]).unwrap();
+        assert_eq!(waiting.state, JobState::Queued);
+        assert!(waiting.message.contains("project nlp: running job quota"), "{}", waiting.message);
+        
+        // Finishing a job frees quota
+        scheduler.update_job_state(&alice[0], JobState::Completed, Actor::Agent, "Exit 0").unwrap();
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].job_id, alice[2]);
+    }
+    
+    #[test]
+    fn test_fair_share_reorders_equal_priority() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 1)).unwrap();
+        let scheduler = Scheduler::new(registry, SchedulerConfig::default());
+        
+        // Alice has recently used far more than bob
+        {
+            let config = FairShareConfig::default();
+            let mut history = owned_job("alice", "vision", 8);
+            for state in [JobState::Queued, JobState::Scheduled, JobState::Running] {
+                history.transition(state, Actor::Scheduler, "history").unwrap();
+            }
+            let start = Utc::now() - chrono::Duration::hours(2);
+            let mut fair_share = scheduler.fair_share.write();
+            fair_share.accrue(&config, start, [&history]);
+            fair_share.accrue(&config, start + chrono::Duration::hours(1), [&history]);
+        }
+        
+        let _alice = scheduler.submit(owned_job("alice", "vision", 1)).unwrap();
+        let bob = scheduler.submit(owned_job("bob", "nlp", 1)).unwrap();
+        
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].job_id, bob);
+    }
+    
+    #[test]
+    fn test_requirements_filter_nodes_with_reasons() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        let mut plain = create_test_node("plain", 4);
+        plain.topology.rdma_capable = false;
+        registry.register(plain).unwrap();
+        registry.register(create_test_node("rdma", 2)).unwrap();
+        let scheduler = Scheduler::new(registry.clone(), SchedulerConfig::default());
+        
+        let mut job = gpu_job(2);
+        job.descriptor.resources.require_rdma = true;
+        job.descriptor.resources.required_gpu_models = vec!["A100".to_string()];
+        job.descriptor.resources.cpu_cores = 40;
+        let placed = scheduler.submit(job).unwrap();
+        
+        // Same again: the RDMA node's cores are now reserved
+        let mut job = gpu_job(0);
+        job.descriptor.resources.require_rdma = true;
+        job.descriptor.resources.cpu_cores = 40;
+        let waiting = scheduler.submit(job).unwrap();
+        
+        let mut job = gpu_job(1);
+        job.descriptor.resources.required_gpu_models = vec!["H100".to_string()];
+        let wrong_model = scheduler.submit(job).unwrap();
+        
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].job_id, placed);
+        assert!(decisions[0].allocations.contains_key("rdma"));
+        assert_eq!(registry.get("rdma").unwrap().available_cpu_cores(), 24);
+        
+        let message = scheduler.get_job(&waiting).unwrap().message;
+        assert!(message.contains("plain: not RDMA capable"), "{}", message);
+        assert!(message.contains("rdma: 24 free CPU cores < 40 requested"), "{}", message);
+        
+        let message = scheduler.get_job(&wrong_model).unwrap().message;
+        assert!(message.contains("no free GPU matching [\"H100\"]"), "{}", message);
+        
+        scheduler.update_job_state(&placed, JobState::Completed, Actor::Agent, "Exit 0").unwrap();
+        assert_eq!(registry.get("rdma").unwrap().available_cpu_cores(), 64);
+    }
+    
+    #[test]
+    fn test_locality_constraints_and_ranking() {
+        use crate::job::{LabelSelector, SelectorOperator};
+        
+        let registry = Arc::new(NodeRegistry::new(60));
+        for (id, rack) in [("a1", "a"), ("a2", "a"), ("b1", "b"), ("b2", "b"), ("b3", "b")] {
+            let mut node = create_test_node(id, 2);
+            node.labels.insert(RACK_LABEL.to_string(), rack.to_string());
+            node.labels.insert("tier".to_string(), if id == "b3" { "spot" } else { "prod" }.to_string());
+            registry.register(node).unwrap();
+        }
+        let scheduler = Scheduler::new(registry, SchedulerConfig::default());
+        
+        // Preferred node wins over ID order
+        let mut job = gpu_job(1);
+        job.descriptor.locality.preferred_nodes = vec!["b2".to_string()];
+        let preferred = scheduler.submit(job).unwrap();
+        
+        // Hard constraints leave only rack a, minus a1
+        let mut job = gpu_job(1);
+        job.descriptor.locality.excluded_nodes = vec!["a1".to_string()];
+        job.descriptor.locality.node_selector = vec![LabelSelector {
+            key: RACK_LABEL.to_string(),
+            operator: SelectorOperator::In,
+            values: vec!["a".to_string()],
+        }];
+        let constrained = scheduler.submit(job).unwrap();
+        
+        let decisions: HashMap<String, SchedulingDecision> = scheduler.schedule_cycle()
+            .into_iter()
+            .map(|d| (d.job_id.clone(), d))
+            .collect();
+        assert!(decisions[&preferred].allocations.contains_key("b2"));
+        assert!(decisions[&constrained].allocations.contains_key("a2"));
+        
+        // 4 GPUs within one rack: b has 5 free, a only 3
+        let mut job = gpu_job(4);
+        job.descriptor.locality.prefer_same_rack = true;
+        let racked = scheduler.submit(job).unwrap();
+        
+        let mut job = gpu_job(1);
+        job.descriptor.locality.node_selector = vec![LabelSelector {
+            key: "tier".to_string(),
+            operator: SelectorOperator::NotIn,
+            values: vec!["prod".to_string(), "spot".to_string()],
+        }];
+        let unmatched = scheduler.submit(job).unwrap();
+        
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 1);
+        let mut nodes: Vec<&String> = decisions[0].allocations.keys().collect();
+        nodes.sort();
+        assert_eq!(decisions[0].job_id, racked);
+        assert_eq!(nodes, vec!["b1", "b2", "b3"]);
+        
+        let message = scheduler.get_job(&unmatched).unwrap().message;
+        assert!(message.contains("a1: does not match selector tier NotIn [prod, spot]"), "{}", message);
+    }
+    
+    #[test]
+    fn test_decision_reports_pinned_cores() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 8)).unwrap();
+        let scheduler = Scheduler::new(registry.clone(), SchedulerConfig::default());
+        
+        // Take one GPU on NUMA 0 so the 4-GPU job fits only on NUMA 1
+        scheduler.submit(gpu_job(1)).unwrap();
+        scheduler.schedule_cycle();
+        
+        let mut job = gpu_job(4);
+        job.descriptor.resources.cpu_cores = 8;
+        let job_id = scheduler.submit(job).unwrap();
+        let decisions = scheduler.schedule_cycle();
+        
+        assert_eq!(decisions[0].job_id, job_id);
+        assert_eq!(decisions[0].allocations["node-1"], vec!["cuda:4", "cuda:5", "cuda:6", "cuda:7"]);
+        assert_eq!(decisions[0].cpu["node-1"].cpu_set, (32..40).collect::<Vec<u32>>());
+        assert_eq!(registry.get("node-1").unwrap().cpu_allocations[&job_id].cpu_set.len(), 8);
     }
     
-    /// Get all jobs with a specific state
-    pub fn jobs_with_state(&self, state: JobState) -> Vec<Job> {
-        self.jobs.read()
-            .values()
-            .filter(|j| j.state == state)
-            .cloned()
-            .collect()
+    #[test]
+    fn test_spread_placement_uses_many_nodes() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        for id in ["n1", "n2", "n3"] {
+            registry.register(create_test_node(id, 4)).unwrap();
+        }
+        let scheduler = Scheduler::new(registry, SchedulerConfig::default());
+        
+        let mut job = gpu_job(4);
+        job.descriptor.policy.gang_schedule = false;
+        scheduler.submit(job).unwrap();
+        
+        let decisions = scheduler.schedule_cycle();
+        assert!(!decisions[0].gang_allocated);
+        let mut per_node: Vec<usize> = decisions[0].allocations.values().map(|g| g.len()).collect();
+        per_node.sort();
+        assert_eq!(per_node, vec![1, 1, 2]);
     }
     
-    /// Get queue size
-    pub fn queue_size(&self) -> usize {
-        self.pending_queue.read().len()
+    #[test]
+    fn test_elastic_job_starts_at_minimum_and_grows() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 4)).unwrap();
+        let scheduler = Scheduler::new(registry.clone(), SchedulerConfig::default());
+        
+        let blocker = scheduler.submit(gpu_job(2)).unwrap();
+        scheduler.schedule_cycle();
+        
+        let mut job = gpu_job(2);
+        job.descriptor.resources.max_gpu_count = Some(6);
+        let elastic = scheduler.submit(job).unwrap();
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 1);
+        assert!(!decisions[0].grown);
+        assert_eq!(scheduler.get_job(&elastic).unwrap().allocated_gpu_count(), 2);
+        
+        // A queued job that cannot fit keeps freed capacity away from growth
+        scheduler.update_job_state(&blocker, JobState::Completed, Actor::Agent, "Exit 0").unwrap();
+        let waiting = scheduler.submit(gpu_job(3)).unwrap();
+        assert!(scheduler.schedule_cycle().is_empty());
+        scheduler.cancel(&waiting, Actor::User, "user").unwrap();
+        
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 1);
+        assert!(decisions[0].grown);
+        assert_eq!(decisions[0].allocations["node-1"].len(), 2);
+        
+        let job = scheduler.get_job(&elastic).unwrap();
+        assert_eq!(job.allocated_gpu_count(), 4);
+        assert_eq!(job.allocated_nodes, vec!["node-1".to_string()]);
+        assert_eq!(registry.get("node-1").unwrap().available_gpus(), 0);
+        
+        scheduler.update_job_state(&elastic, JobState::Completed, Actor::Agent, "Exit 0").unwrap();
+        assert_eq!(registry.get("node-1").unwrap().available_gpus(), 4);
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::job::JobDescriptor;
-    use crate::node::{GpuDevice, NodeTopology};
     
-    fn create_test_node(id: &str, gpu_count: usize) -> Node {
-        let gpus: Vec<GpuDevice> = (0..gpu_count)
-            .map(|i| GpuDevice {
-                device_id: format!("cuda:{}", i),
-                device_name: "NVIDIA A100".to_string(),
-                uuid: format!("GPU-{}", i),
-                total_memory: 80 * 1024 * 1024 * 1024,
-                free_memory: 80 * 1024 * 1024 * 1024,
-                utilization: 0.0,
-                temperature: 40,
-                allocated: false,
-                allocated_job_id: None,
-            })
-            .collect();
+    fn cpu_job(cores: u32) -> Job {
+        let mut job = gpu_job(0);
+        job.descriptor.resources.cpu_cores = cores;
+        job.descriptor.resources.cpu_memory = 1024 * 1024 * 1024;
+        job
+    }
+    
+    #[test]
+    fn test_cpu_placement_strategies() {
+        for (strategy, expected) in [(CpuPlacement::Pack, "n1"), (CpuPlacement::Spread, "n2")] {
+            let registry = Arc::new(NodeRegistry::new(60));
+            registry.register(create_test_node("n1", 0)).unwrap();
+            registry.register(create_test_node("n2", 0)).unwrap();
+            let config = SchedulerConfig { cpu_placement: strategy, ..Default::default() };
+            let scheduler = Scheduler::new(registry.clone(), config);
+            
+            // Load n1 with 32 of 64 cores
+            let mut first = cpu_job(32);
+            first.descriptor.locality.preferred_nodes = vec!["n1".to_string()];
+            scheduler.submit(first).unwrap();
+            scheduler.schedule_cycle();
+            
+            let job_id = scheduler.submit(cpu_job(16)).unwrap();
+            let decisions = scheduler.schedule_cycle();
+            assert_eq!(decisions[0].job_id, job_id);
+            assert!(decisions[0].allocations.contains_key(expected), "{:?}", strategy);
+        }
         
-        let topology = NodeTopology {
-            gpus,
-            cpu_cores: 64,
-            cpu_memory: 512 * 1024 * 1024 * 1024,
-            cpu_memory_free: 500 * 1024 * 1024 * 1024,
-            numa_nodes: 2,
-            nvlink_present: true,
-            nvswitch_present: false,
-            rdma_capable: true,
-        };
+        // Jobs that fit nowhere stay queued
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("n1", 0)).unwrap();
+        let scheduler = Scheduler::new(registry.clone(), SchedulerConfig::default());
+        let too_big = scheduler.submit(cpu_job(65)).unwrap();
+        assert!(scheduler.schedule_cycle().is_empty());
+        assert!(scheduler.get_job(&too_big).unwrap().message.contains("64 free CPU cores < 65"));
+    }
+    
+    #[test]
+    fn test_reaper_timeouts_and_wait_deadlines() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 1)).unwrap();
+        let scheduler = Scheduler::new(registry.clone(), SchedulerConfig::default());
         
-        Node::new(
-            id.to_string(),
-            format!("{}.local", id),
-            "192.168.1.1".to_string(),
-            topology,
-        )
+        let mut job = gpu_job(1);
+        job.descriptor.policy.max_runtime_seconds = 60;
+        let running = scheduler.submit(job).unwrap();
+        let mut job = gpu_job(1);
+        job.descriptor.policy.max_wait_time_seconds = 120;
+        let waiting = scheduler.submit(job).unwrap();
+        scheduler.schedule_cycle();
+        scheduler.update_job_state(&running, JobState::Running, Actor::Agent, "Started").unwrap();
+        
+        let now = Utc::now();
+        let report = scheduler.reap_at(now + chrono::Duration::seconds(30));
+        assert!(report.timed_out.is_empty() && report.expired.is_empty());
+        
+        let report = scheduler.reap_at(now + chrono::Duration::seconds(90));
+        assert_eq!(report.timed_out, vec![running.clone()]);
+        assert_eq!(scheduler.get_job(&running).unwrap().state, JobState::Timeout);
+        
+        // Its GPU stays held, so the waiting job cannot start on it, until
+        // the agent confirms the job stopped
+        let node = scheduler.heartbeat("node-1", &[], 0, &[]).unwrap();
+        assert_eq!(node.stopping_jobs, vec![running.clone()]);
+        assert_eq!(node.available_gpus(), 0);
+        assert!(scheduler.schedule_cycle().is_empty());
+        let node = scheduler.heartbeat("node-1", &[], 0, std::slice::from_ref(&running)).unwrap();
+        assert_eq!(node.available_gpus(), 1);
+        assert_eq!(scheduler.get_job(&running).unwrap().state, JobState::Timeout);
+        
+        let report = scheduler.reap_at(now + chrono::Duration::seconds(150));
+        assert_eq!(report.expired, vec![waiting.clone()]);
+        let job = scheduler.get_job(&waiting).unwrap();
+        assert_eq!(job.state, JobState::Failed);
+        assert!(job.retry_after.is_none());
+        assert_eq!(scheduler.queue_size(), 0);
     }
     
     #[test]
-    fn test_scheduler_submit() {
+    fn test_reaper_retries_with_backoff() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 1)).unwrap();
+        let scheduler = Scheduler::new(registry, SchedulerConfig::default());
+        
+        let mut job = gpu_job(1);
+        job.descriptor.policy.max_retries = 2;
+        let job_id = scheduler.submit(job).unwrap();
+        
+        let mut delays = vec![];
+        for attempt in 0..3 {
+            scheduler.schedule_cycle();
+            scheduler.update_job_state(&job_id, JobState::Running, Actor::Agent, "Started").unwrap();
+            scheduler.update_job_state(&job_id, JobState::Failed, Actor::Agent, "Exit 1").unwrap();
+            
+            let job = scheduler.get_job(&job_id).unwrap();
+            assert_eq!(job.retry_count, attempt);
+            let Some(retry_after) = job.retry_after else {
+                // Out of retries
+                assert_eq!(attempt, 2);
+                break;
+            };
+            let delay = (retry_after - Utc::now()).num_milliseconds() as f64 / 1000.0;
+            delays.push(delay.round() as i64);
+            
+            assert!(scheduler.reap_at(retry_after - chrono::Duration::seconds(1)).retried.is_empty());
+            assert_eq!(scheduler.reap_at(retry_after).retried, vec![job_id.clone()]);
+            
+            let job = scheduler.get_job(&job_id).unwrap();
+            assert_eq!(job.state, JobState::Queued);
+            assert!(job.message.starts_with(&format!("Retry {}/2", attempt + 1)));
+        }
+        assert_eq!(delays, vec![10, 20]);
+        assert_eq!(scheduler.get_job(&job_id).unwrap().state, JobState::Failed);
+    }
+    
+    #[test]
+    fn test_cancel_pending_retry() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 1)).unwrap();
+        let scheduler = Scheduler::new(registry, SchedulerConfig::default());
+        
+        let mut job = gpu_job(1);
+        job.descriptor.policy.max_retries = 1;
+        let job_id = scheduler.submit(job).unwrap();
+        scheduler.schedule_cycle();
+        scheduler.update_job_state(&job_id, JobState::Failed, Actor::Agent, "Exit 1").unwrap();
+        let retry_after = scheduler.get_job(&job_id).unwrap().retry_after.unwrap();
+        
+        scheduler.cancel(&job_id, Actor::User, "giving up").unwrap();
+        let job = scheduler.get_job(&job_id).unwrap();
+        assert_eq!(job.final_state(), Some(JobState::Failed));
+        assert_eq!(job.message, "Retry cancelled: giving up");
+        let event = job.events().last().unwrap();
+        assert_eq!((event.from, event.to, event.actor), (JobState::Failed, JobState::Failed, Actor::User));
+        assert_eq!(event.reason, job.message);
+        assert!(scheduler.reap_at(retry_after).retried.is_empty());
+        
+        // Nothing left to cancel
+        assert!(scheduler.cancel(&job_id, Actor::User, "again").is_err());
+    }
+    
+    fn step(name: &str, after_ok: &[&str], after_fail: &[&str]) -> WorkflowStep {
+        let mut job = gpu_job(1);
+        job.descriptor.policy.max_retries = 0;
+        job.descriptor.dependencies.after_ok = after_ok.iter().map(|s| s.to_string()).collect();
+        job.descriptor.dependencies.after_fail = after_fail.iter().map(|s| s.to_string()).collect();
+        WorkflowStep { name: name.to_string(), descriptor: job.descriptor }
+    }
+    
+    #[test]
+    fn test_workflow_dependencies() {
         let registry = Arc::new(NodeRegistry::new(60));
         registry.register(create_test_node("node-1", 4)).unwrap();
+        let scheduler = Scheduler::new(registry, SchedulerConfig::default());
+        
+        let ids = scheduler.submit_workflow(vec![
+            step("evaluate", &["train"], &[]),
+            step("train", &["preprocess"], &[]),
+            step("preprocess", &[], &[]),
+            step("report-failure", &[], &["train"]),
+        ]).unwrap();
+        let state = |step: &str| scheduler.get_job(&ids[step]).unwrap().state;
+        assert_eq!(state("train"), JobState::Blocked);
+        assert_eq!(scheduler.get_job(&ids["train"]).unwrap().descriptor.dependencies.after_ok, vec![ids["preprocess"].clone()]);
+        
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].job_id, ids["preprocess"]);
+        
+        scheduler.update_job_state(&ids["preprocess"], JobState::Running, Actor::Agent, "Started").unwrap();
+        scheduler.update_job_state(&ids["preprocess"], JobState::Completed, Actor::Agent, "Exit 0").unwrap();
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].job_id, ids["train"]);
+        
+        // A failed upstream cancels after_ok dependents and releases after_fail ones
+        scheduler.update_job_state(&ids["train"], JobState::Running, Actor::Agent, "Started").unwrap();
+        scheduler.update_job_state(&ids["train"], JobState::Failed, Actor::Agent, "Exit 1").unwrap();
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(state("evaluate"), JobState::Cancelled);
+        let evaluate = scheduler.get_job(&ids["evaluate"]).unwrap();
+        assert_eq!(evaluate.events().last().unwrap().actor, Actor::Scheduler);
+        assert!(evaluate.message.contains("ended Failed"));
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].job_id, ids["report-failure"]);
+    }
+    
+    #[test]
+    fn test_workflow_rejects_cycles_and_unknown_jobs() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        let scheduler = Scheduler::new(registry, SchedulerConfig::default());
         
+        let cycle = scheduler.submit_workflow(vec![
+            step("a", &["b"], &[]),
+            step("b", &["a"], &[]),
+            step("c", &[], &[]),
+        ]);
+        assert!(cycle.is_err());
+        assert!(scheduler.submit_workflow(vec![step("a", &["missing"], &[])]).is_err());
+        assert!(scheduler.submit(Job::new(step("a", &["missing"], &[]).descriptor)).is_err());
+        assert_eq!(scheduler.jobs.read().len(), 0);
+    }
+    
+    #[test]
+    fn test_array_job_throttle() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 4)).unwrap();
         let scheduler = Scheduler::new(registry, SchedulerConfig::default());
         
-        let descriptor = JobDescriptor {
-            name: "test-job".to_string(),
-            user_id: "user1".to_string(),
-            project_id: "project1".to_string(),
-            command: "python".to_string(),
-            arguments: vec!["train.py".to_string()],
-            environment: HashMap::new(),
-            working_directory: "/app".to_string(),
-            resources: crate::job::ResourceRequirements {
-                gpu_count: 2,
-                ..Default::default()
-            },
-            locality: Default::default(),
-            policy: Default::default(),
-            labels: HashMap::new(),
-            annotations: HashMap::new(),
+        let mut job = gpu_job(1);
+        job.descriptor.array = Some(ArraySpec { start: 0, end: 4, max_concurrent: 2 });
+        let array_id = scheduler.submit(job).unwrap();
+        assert!(scheduler.get_job(&array_id).is_none());
+        let (array, status) = scheduler.get_array(&array_id).unwrap();
+        assert_eq!(array.children.len(), 5);
+        assert_eq!(status.state, JobState::Queued);
+        
+        // Room for four, but only two at a time
+        let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 2);
+        let waiting = scheduler.get_job(&array.children[4]).unwrap();
+        assert_eq!(waiting.descriptor.environment[ARRAY_INDEX_ENV], "4");
+        assert!(waiting.message.starts_with("Waiting for array throttle"));
+        
+        let first = &decisions[0].job_id;
+        scheduler.update_job_state(first, JobState::Running, Actor::Agent, "Started").unwrap();
+        scheduler.update_job_state(first, JobState::Completed, Actor::Agent, "Exit 0").unwrap();
+        assert_eq!(scheduler.schedule_cycle().len(), 1);
+        
+        let (_, status) = scheduler.get_array(&array_id).unwrap();
+        assert_eq!(status.state, JobState::Running);
+        assert_eq!(status.counts[&JobState::Scheduled], 2);
+        assert_eq!(status.counts[&JobState::Completed], 1);
+        
+        // Cancelling the parent cancels every unfinished child
+        scheduler.cancel(&array_id, Actor::User, "sweep done").unwrap();
+        let (_, status) = scheduler.get_array(&array_id).unwrap();
+        assert_eq!(status.counts[&JobState::Cancelled], 4);
+        assert_eq!(status.state, JobState::Cancelled);
+    }
+    
+    #[test]
+    fn test_partitions() {
+        use crate::job::{LabelSelector, SelectorOperator};
+        
+        let registry = Arc::new(NodeRegistry::new(60));
+        let mut gpu_node = create_test_node("gpu-node", 1);
+        gpu_node.labels.insert("partition".to_string(), "gpu".to_string());
+        registry.register(gpu_node).unwrap();
+        registry.register(create_test_node("shared-node", 1)).unwrap();
+        
+        let config = SchedulerConfig {
+            partitions: vec![
+                PartitionConfig {
+                    name: "gpu".to_string(),
+                    node_selector: vec![LabelSelector {
+                        key: "partition".to_string(),
+                        operator: SelectorOperator::In,
+                        values: vec!["gpu".to_string()],
+                    }],
+                    priority_tier: 10,
+                    ..Default::default()
+                },
+                PartitionConfig {
+                    name: "debug".to_string(),
+                    default_runtime_secs: 300,
+                    max_runtime_secs: 600,
+                    allowed_users: vec!["alice".to_string()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
         };
+        let scheduler = Scheduler::new(registry, config);
         
-        let job = Job::new(descriptor);
-        let job_id = scheduler.submit(job).unwrap();
+        let in_queue = |user: &str, queue: &str, priority: i32| {
+            let mut job = owned_job(user, "project", 1);
+            job.descriptor.policy.queue_name = queue.to_string();
+            job.descriptor.policy.priority = priority;
+            job.descriptor.policy.max_runtime_seconds = 0;
+            job
+        };
+        
+        // Admission
+        assert!(scheduler.submit(in_queue("alice", "missing", 0)).is_err());
+        assert!(scheduler.submit(in_queue("bob", "debug", 0)).is_err());
+        let mut too_long = in_queue("alice", "debug", 0);
+        too_long.descriptor.policy.max_runtime_seconds = 3600;
+        assert!(scheduler.submit(too_long).is_err());
+        
+        // Debug outranks by priority but the gpu partition has the higher tier
+        let debug_a = scheduler.submit(in_queue("alice", "debug", 100)).unwrap();
+        let debug_b = scheduler.submit(in_queue("alice", "debug", 100)).unwrap();
+        let gpu = scheduler.submit(in_queue("bob", "gpu", 0)).unwrap();
+        assert_eq!(scheduler.get_job(&debug_a).unwrap().descriptor.policy.max_runtime_seconds, 300);
+        
+        let decisions = scheduler.schedule_cycle();
+        let placed: HashMap<_, _> = decisions.iter()
+            .map(|d| (d.job_id.clone(), d.allocations.keys().cloned().collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(placed[&gpu], vec!["gpu-node".to_string()]);
+        assert_eq!(placed.len(), 2);
+        assert!(placed.contains_key(&debug_a) ^ placed.contains_key(&debug_b));
+        
+        let stats = scheduler.queue_stats();
+        let stats: HashMap<_, _> = stats.iter().map(|s| (s.name.as_str(), s)).collect();
+        assert_eq!(stats["gpu"].nodes, 1);
+        assert_eq!(stats["gpu"].usage.gpus, 1);
+        assert_eq!(stats["debug"].depth, 1);
+        assert_eq!(stats["debug"].usage.running_jobs, 1);
+        assert_eq!(stats["default"].nodes, 2);
+    }
+    
+    #[test]
+    fn test_advance_reservations() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 4)).unwrap();
+        registry.register(create_test_node("node-2", 4)).unwrap();
+        let config = SchedulerConfig { backfill_enabled: false, ..Default::default() };
+        let scheduler = Scheduler::new(registry, config);
+        
+        let now = Utc::now();
+        let window = |name: &str, node: &str, start_mins: i64| Reservation {
+            name: name.to_string(),
+            start: now + chrono::Duration::minutes(start_mins),
+            end: now + chrono::Duration::minutes(start_mins + 120),
+            nodes: vec![node.to_string()],
+            gpu_count: 0,
+            allowed_users: vec!["alice".to_string()],
+        };
+        scheduler.create_reservation(window("demo", "node-2", -1)).unwrap();
+        scheduler.create_reservation(window("maintenance", "node-1", 60)).unwrap();
+        assert!(scheduler.create_reservation(window("clash", "node-2", 30)).is_err());
+        let by_count = Reservation { nodes: vec![], gpu_count: 4, ..window("count", "", 600) };
+        assert_eq!(scheduler.create_reservation(by_count).unwrap().nodes.len(), 1);
+        scheduler.delete_reservation("count").unwrap();
+        
+        let reserved = |user: &str, reservation: &str| {
+            let mut job = owned_job(user, "project", 4);
+            job.descriptor.policy.reservation = Some(reservation.to_string());
+            job
+        };
+        assert!(scheduler.submit(reserved("bob", "demo")).is_err());
+        assert!(scheduler.submit(reserved("alice", "missing")).is_err());
+        
+        let in_demo = scheduler.submit(reserved("alice", "demo")).unwrap();
+        let later = scheduler.submit(reserved("alice", "maintenance")).unwrap();
+        let unlimited = scheduler.submit(gpu_job(4)).unwrap();
+        let short = scheduler.submit(timed_job(4, 0, 1800)).unwrap();
+        
+        let placed: HashMap<_, _> = scheduler.schedule_cycle().into_iter()
+            .map(|d| (d.job_id, d.allocations.keys().next().unwrap().clone()))
+            .collect();
+        assert_eq!(placed.len(), 2);
+        assert_eq!(placed[&in_demo], "node-2");
+        // Finishes before the maintenance window opens
+        assert_eq!(placed[&short], "node-1");
+        
+        let waiting = scheduler.get_job(&later).unwrap();
+        assert!(waiting.message.starts_with("Waiting for reservation maintenance"));
+        let blocked = scheduler.get_job(&unlimited).unwrap();
+        assert_eq!(blocked.state, JobState::Queued);
+        assert!(blocked.message.contains("unreserved"));
         
+        // Waiting for a reservation counts from its window opening
+        let report = scheduler.reap_at(now + chrono::Duration::minutes(90));
+        assert_eq!(report.expired, vec![unlimited]);
+        
+        // The window's end is the runtime limit of jobs inside it
+        assert!(!scheduler.reap_at(now + chrono::Duration::minutes(118)).timed_out.contains(&in_demo));
+        let report = scheduler.reap_at(now + chrono::Duration::minutes(119));
+        assert!(report.timed_out.contains(&in_demo));
+        let job = scheduler.get_job(&in_demo).unwrap();
+        assert_eq!(job.state, JobState::Timeout);
+        assert!(job.message.starts_with("Reservation demo ended"));
+        // An ended reservation is dropped and no longer accepts jobs
+        let names: Vec<_> = scheduler.reservations().into_iter().map(|r| r.name).collect();
+        assert_eq!(names, vec!["maintenance".to_string()]);
+        
+        // Jobs left waiting on a deleted reservation are failed, not kept queued
+        scheduler.delete_reservation("maintenance").unwrap();
+        let report = scheduler.reap_at(now + chrono::Duration::minutes(120));
+        assert_eq!(report.expired, vec![later.clone()]);
+        let job = scheduler.get_job(&later).unwrap();
+        assert_eq!(job.state, JobState::Failed);
+        assert_eq!(job.message, "Reservation maintenance no longer exists");
+    }
+    
+    #[test]
+    fn test_recovery_from_state_store() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let store_config = crate::state::StateStoreConfig {
+            data_dir: temp_dir.path().to_path_buf(),
+            enable_wal: true,
+            sync_writes: true,
+            checkpoint_interval_secs: u64::MAX,
+        };
+        let store = Arc::new(StateStore::new(store_config.clone()).unwrap());
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 4)).unwrap();
+        registry.register(create_test_node("node-2", 4)).unwrap();
+        let scheduler = Scheduler::with_store(registry, SchedulerConfig::default(), store);
+        
+        let running = scheduler.submit(gpu_job(4)).unwrap();
+        let scheduled = scheduler.submit(gpu_job(4)).unwrap();
+        let placed: HashMap<_, _> = scheduler.schedule_cycle().into_iter()
+            .map(|d| (d.job_id, d.allocations.keys().next().unwrap().clone()))
+            .collect();
+        let queued = scheduler.submit(gpu_job(4)).unwrap();
+        scheduler.update_job_state(&running, JobState::Running, Actor::Agent, "Started").unwrap();
+        drop(scheduler);
+        
+        // Restart with an empty registry; nodes come back as agents register
+        let store = Arc::new(StateStore::new(store_config).unwrap());
+        let registry = Arc::new(NodeRegistry::new(60));
+        let scheduler = Scheduler::with_store(registry.clone(), SchedulerConfig::default(), store);
         assert_eq!(scheduler.queue_size(), 1);
+        assert_eq!(scheduler.get_job(&running).unwrap().state, JobState::Running);
+        assert!(scheduler.get_job(&running).unwrap().events().len() >= 3);
         
-        // Run scheduling
+        // The agent restarted too and lost the running job
+        scheduler.register_node(create_test_node("node-1", 4), Some(&[])).unwrap();
+        scheduler.register_node(create_test_node("node-2", 4), Some(&[])).unwrap();
+        let lost = scheduler.get_job(&running).unwrap();
+        assert_eq!(lost.state, JobState::Failed);
+        assert!(lost.message.starts_with("Lost when node"));
+        
+        // The scheduled job kept its node, so the queued one takes the other
         let decisions = scheduler.schedule_cycle();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].job_id, queued);
+        assert_eq!(decisions[0].allocations.keys().next(), Some(&placed[&running]));
+        assert_eq!(registry.get(&placed[&scheduled]).unwrap().available_gpus(), 0);
+    }
+    
+    #[test]
+    fn test_register_without_job_report() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 4)).unwrap();
+        let scheduler = Scheduler::new(registry.clone(), SchedulerConfig::default());
+        let running = scheduler.submit(gpu_job(4)).unwrap();
+        assert_eq!(scheduler.schedule_cycle().len(), 1);
+        scheduler.update_job_state(&running, JobState::Running, Actor::Agent, "Started").unwrap();
+        
+        // An agent that cannot tell which jobs it runs keeps them all
+        let orphans = scheduler.register_node(create_test_node("node-1", 4), None).unwrap();
+        assert!(orphans.is_empty());
+        assert_eq!(scheduler.get_job(&running).unwrap().state, JobState::Running);
+        assert_eq!(registry.get("node-1").unwrap().available_gpus(), 0);
+    }
+    
+    #[test]
+    fn test_unreachable_nodes_requeue_or_fail_jobs() {
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 4)).unwrap();
+        registry.register(create_test_node("node-2", 4)).unwrap();
+        let scheduler = Scheduler::new(registry.clone(), SchedulerConfig::default());
+        
+        let mut retryable = gpu_job(4);
+        retryable.descriptor.policy.max_retries = 1;
+        let retryable = scheduler.submit(retryable).unwrap();
+        let mut final_job = gpu_job(4);
+        final_job.descriptor.policy.max_retries = 0;
+        let final_job = scheduler.submit(final_job).unwrap();
+        assert_eq!(scheduler.schedule_cycle().len(), 2);
+        scheduler.update_job_state(&retryable, JobState::Running, Actor::Agent, "Started").unwrap();
+        assert_eq!(scheduler.monitor_nodes(), NodeMonitorReport::default());
+        
+        for node_id in ["node-1", "node-2"] {
+            let mut node = registry.get(node_id).unwrap();
+            node.last_heartbeat -= chrono::Duration::minutes(5);
+            registry.register(node).unwrap();
+        }
+        let mut report = scheduler.monitor_nodes();
+        report.unreachable.sort();
+        assert_eq!(report.unreachable, vec!["node-1", "node-2"]);
+        assert_eq!(report.requeued, vec![retryable.clone()]);
+        assert_eq!(report.failed, vec![final_job.clone()]);
+        assert_eq!(registry.get("node-1").unwrap().health, NodeHealth::Unreachable);
+        assert_eq!(scheduler.get_job(&final_job).unwrap().final_state(), Some(JobState::Failed));
+        let requeued = scheduler.get_job(&retryable).unwrap();
+        assert_eq!(requeued.state, JobState::Queued);
+        assert_eq!(requeued.retry_count, 1);
+        
+        // Fenced until the agent registers again
+        assert!(scheduler.schedule_cycle().is_empty());
+        assert_eq!(scheduler.monitor_nodes(), NodeMonitorReport::default());
         
+        // The returning agent still runs the requeued job, which it must stop
+        let orphans = scheduler.register_node(create_test_node("node-1", 4), Some(std::slice::from_ref(&retryable))).unwrap();
+        assert_eq!(orphans, vec![retryable.clone()]);
+        let decisions = scheduler.schedule_cycle();
         assert_eq!(decisions.len(), 1);
+        assert!(decisions[0].allocations.contains_key("node-1"));
+    }
+    
+    #[test]
+    fn test_cordon_and_drain() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let store_config = crate::state::StateStoreConfig {
+            data_dir: temp_dir.path().to_path_buf(),
+            enable_wal: true,
+            sync_writes: true,
+            checkpoint_interval_secs: u64::MAX,
+        };
+        let store = Arc::new(StateStore::new(store_config.clone()).unwrap());
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 4)).unwrap();
+        let scheduler = Scheduler::with_store(registry.clone(), SchedulerConfig::default(), store);
+        
+        scheduler.cordon("node-1").unwrap();
+        let waiting = scheduler.submit(gpu_job(4)).unwrap();
+        assert!(scheduler.schedule_cycle().is_empty());
+        scheduler.uncordon("node-1").unwrap();
+        assert_eq!(scheduler.schedule_cycle()[0].job_id, waiting);
+        
+        // Without a deadline the drain waits for the job
+        scheduler.drain("node-1", None).unwrap();
+        assert_eq!(scheduler.monitor_nodes(), NodeMonitorReport::default());
+        assert_eq!(scheduler.get_job(&waiting).unwrap().state, JobState::Scheduled);
+        
+        // Past the deadline the job is suspended, then requeued and the
+        // node drained once the agent stops it
+        scheduler.drain("node-1", Some(Utc::now())).unwrap();
+        let report = scheduler.monitor_nodes();
+        assert_eq!(report.evicted, vec![waiting.clone()]);
+        assert!(report.drained.is_empty());
+        let evicted = scheduler.get_job(&waiting).unwrap();
+        assert_eq!(evicted.state, JobState::Suspended);
+        assert_eq!(evicted.message, "Node node-1 drained");
+        assert_eq!(registry.get("node-1").unwrap().available_gpus(), 0);
         assert_eq!(scheduler.queue_size(), 0);
+        assert!(scheduler.monitor_nodes().drained.is_empty());
+        
+        let node = scheduler.heartbeat("node-1", &[], 0, std::slice::from_ref(&waiting)).unwrap();
+        assert_eq!(node.available_gpus(), 4);
+        assert_eq!(scheduler.queue_size(), 1);
+        assert_eq!(scheduler.monitor_nodes().drained, vec!["node-1"]);
+        assert!(scheduler.schedule_cycle().is_empty());
+        drop(scheduler);
+        
+        // The drain outlives a restart and the agent re-registering
+        let store = Arc::new(StateStore::new(store_config.clone()).unwrap());
+        let registry = Arc::new(NodeRegistry::new(60));
+        let scheduler = Scheduler::with_store(registry.clone(), SchedulerConfig::default(), store);
+        scheduler.register_node(create_test_node("node-1", 4), None).unwrap();
+        assert_eq!(registry.get("node-1").unwrap().scheduling, NodeSchedulingState::Drained);
+        assert!(scheduler.schedule_cycle().is_empty());
+        drop(scheduler);
+        
+        // And nodes registered before the scheduler starts
+        let store = Arc::new(StateStore::new(store_config.clone()).unwrap());
+        let registry = Arc::new(NodeRegistry::new(60));
+        registry.register(create_test_node("node-1", 4)).unwrap();
+        registry.register(create_test_node("node-2", 4)).unwrap();
+        registry.set_scheduling("node-2", NodeSchedulingState::Cordoned).unwrap();
+        let scheduler = Scheduler::with_store(registry.clone(), SchedulerConfig::default(), store);
+        assert_eq!(registry.get("node-1").unwrap().scheduling, NodeSchedulingState::Drained);
+        scheduler.uncordon("node-1").unwrap();
+        assert_eq!(scheduler.schedule_cycle()[0].job_id, waiting);
+        drop(scheduler);
+        
+        let store = StateStore::new(store_config).unwrap();
+        assert_eq!(store.get_node("node-1").unwrap().scheduling, NodeSchedulingState::Schedulable);
+        assert_eq!(store.get_node("node-2").unwrap().scheduling, NodeSchedulingState::Cordoned);
     }
 }
@@ -1,27 +1,46 @@
 //! State Persistence for Scheduler
 //!
 //! Provides durable storage for job and node state.
+//!
+//! With the WAL enabled, every mutation is appended to `wal.log` (fsynced
+//! when `sync_writes` is set) before it is acknowledged. Checkpoints write
+//! one JSON file per collection via temp file + rename and then truncate
+//! the WAL; `load()` reads the checkpoint and replays the WAL on top,
+//! dropping a torn final record left by a crash. Any other unreadable
+//! record fails the load rather than losing the records after it.
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::fs;
-use parking_lot::RwLock;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use parking_lot::{Mutex, RwLock};
+use tracing::{info, warn};
 
-use crate::job::{Job, JobState};
-use crate::node::Node;
+use crate::job::{Actor, ArrayJob, Job, JobState};
+use crate::node::{Node, NodeSchedulingState};
+use crate::reservation::Reservation;
 use crate::{Error, Result};
 
+const JOBS_FILE: &str = "jobs.json";
+const NODES_FILE: &str = "nodes.json";
+const ARRAYS_FILE: &str = "arrays.json";
+const RESERVATIONS_FILE: &str = "reservations.json";
+const WAL_FILE: &str = "wal.log";
+
 /// State store configuration
 #[derive(Debug, Clone)]
 pub struct StateStoreConfig {
     /// Data directory
     pub data_dir: PathBuf,
-    /// Enable WAL
+    /// Log mutations to a write-ahead log and checkpoint periodically;
+    /// otherwise rewrite the checkpoint on every mutation
     pub enable_wal: bool,
     /// Sync writes to disk
     pub sync_writes: bool,
-    /// Checkpoint interval in seconds
+    /// Checkpoint interval in seconds (WAL mode)
     pub checkpoint_interval_secs: u64,
 }
 
@@ -36,11 +55,29 @@ impl Default for StateStoreConfig {
     }
 }
 
+/// One logged mutation; replaying a record twice has no further effect
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum WalRecord {
+    PutJob { job: Box<Job> },
+    DeleteJob { job_id: String },
+    PutNode { node: NodeState },
+    PutArray { array: Box<ArrayJob> },
+    PutReservation { reservation: Reservation },
+    DeleteReservation { name: String },
+}
+
 /// Persistent state store
 pub struct StateStore {
     config: StateStoreConfig,
     jobs: RwLock<HashMap<String, Job>>,
     nodes: RwLock<HashMap<String, NodeState>>,
+    arrays: RwLock<HashMap<String, ArrayJob>>,
+    reservations: RwLock<HashMap<String, Reservation>>,
+    /// Open WAL (WAL mode only); lock order: jobs, nodes, arrays,
+    /// reservations, wal
+    wal: Mutex<Option<File>>,
+    last_checkpoint: Mutex<Instant>,
 }
 
 /// Persisted node state
@@ -54,6 +91,22 @@ pub struct NodeState {
     pub registered_at: i64,
     /// Allocated jobs
     pub allocated_jobs: Vec<String>,
+    /// Cordon / drain state, kept across restarts
+    #[serde(default)]
+    pub scheduling: NodeSchedulingState,
+}
+
+impl NodeState {
+    /// Snapshot of a registered node
+    pub fn of(node: &Node) -> Self {
+        Self {
+            id: node.id.clone(),
+            last_heartbeat: node.last_heartbeat.timestamp(),
+            registered_at: node.registered_at.timestamp(),
+            allocated_jobs: node.running_jobs.clone(),
+            scheduling: node.scheduling,
+        }
+    }
 }
 
 impl StateStore {
@@ -62,78 +115,257 @@ impl StateStore {
         // Create data directory if needed
         if !config.data_dir.exists() {
             fs::create_dir_all(&config.data_dir)
-                .map_err(|e| Error::Io(e))?;
+                .map_err(Error::Io)?;
         }
         
         let store = Self {
             config,
             jobs: RwLock::new(HashMap::new()),
             nodes: RwLock::new(HashMap::new()),
+            arrays: RwLock::new(HashMap::new()),
+            reservations: RwLock::new(HashMap::new()),
+            wal: Mutex::new(None),
+            last_checkpoint: Mutex::new(Instant::now()),
         };
         
         // Load existing state
         store.load()?;
         
+        if store.config.enable_wal {
+            let wal = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(store.path(WAL_FILE))
+                .map_err(Error::Io)?;
+            *store.wal.lock() = Some(wal);
+        }
+        
         Ok(store)
     }
     
-    /// Load state from disk
+    fn path(&self, name: &str) -> PathBuf {
+        self.config.data_dir.join(name)
+    }
+    
+    /// Load the checkpoint, then replay the WAL on top of it
     fn load(&self) -> Result<()> {
-        let jobs_path = self.config.data_dir.join("jobs.json");
-        let nodes_path = self.config.data_dir.join("nodes.json");
-        
-        // Load jobs
-        if jobs_path.exists() {
-            let data = fs::read_to_string(&jobs_path)
-                .map_err(|e| Error::Io(e))?;
-            let jobs: HashMap<String, Job> = serde_json::from_str(&data)
-                .map_err(|e| Error::Serialization(e.to_string()))?;
-            *self.jobs.write() = jobs;
+        *self.jobs.write() = self.read_checkpoint(JOBS_FILE)?;
+        *self.nodes.write() = self.read_checkpoint(NODES_FILE)?;
+        *self.arrays.write() = self.read_checkpoint(ARRAYS_FILE)?;
+        *self.reservations.write() = self.read_checkpoint(RESERVATIONS_FILE)?;
+        
+        self.replay_wal()
+    }
+    
+    fn read_checkpoint<T: serde::de::DeserializeOwned + Default>(&self, name: &str) -> Result<T> {
+        let path = self.path(name);
+        if !path.exists() {
+            return Ok(T::default());
         }
         
-        // Load nodes
-        if nodes_path.exists() {
-            let data = fs::read_to_string(&nodes_path)
-                .map_err(|e| Error::Io(e))?;
-            let nodes: HashMap<String, NodeState> = serde_json::from_str(&data)
+        let data = fs::read_to_string(&path)
+            .map_err(Error::Io)?;
+        serde_json::from_str(&data)
+            .map_err(|e| Error::Serialization(e.to_string()))
+    }
+    
+    /// Apply every complete WAL record and cut off a torn tail, so later
+    /// appends do not follow garbage. A complete record that does not parse
+    /// is an error: records after it would be lost.
+    fn replay_wal(&self) -> Result<()> {
+        let wal_path = self.path(WAL_FILE);
+        if !wal_path.exists() {
+            return Ok(());
+        }
+        
+        let data = fs::read(&wal_path).map_err(Error::Io)?;
+        let mut valid_len = 0;
+        let mut replayed = 0;
+        for line in data.split_inclusive(|&b| b == b'\n') {
+            // Records are written with their newline, so one without it is torn
+            if !line.ends_with(b"\n") {
+                break;
+            }
+            let record = serde_json::from_slice::<WalRecord>(line).map_err(|e| Error::Serialization(format!(
+                "corrupt WAL record {} at byte {} of {}: {}", replayed + 1, valid_len, wal_path.display(), e
+            )))?;
+            self.apply(record);
+            valid_len += line.len();
+            replayed += 1;
+        }
+        
+        if valid_len < data.len() {
+            warn!(
+                "Discarding {} bytes of torn WAL after {} records",
+                data.len() - valid_len, replayed
+            );
+            let wal = OpenOptions::new().write(true).open(&wal_path).map_err(Error::Io)?;
+            wal.set_len(valid_len as u64).map_err(Error::Io)?;
+            wal.sync_all().map_err(Error::Io)?;
+        }
+        if replayed > 0 {
+            info!("Replayed {} WAL records", replayed);
+        }
+        
+        Ok(())
+    }
+    
+    fn apply(&self, record: WalRecord) {
+        match record {
+            WalRecord::PutJob { job } => {
+                self.jobs.write().insert(job.id.to_string(), *job);
+            }
+            WalRecord::DeleteJob { job_id } => {
+                self.jobs.write().remove(&job_id);
+            }
+            WalRecord::PutNode { node } => {
+                self.nodes.write().insert(node.id.clone(), node);
+            }
+            WalRecord::PutArray { array } => {
+                self.arrays.write().insert(array.id.to_string(), *array);
+            }
+            WalRecord::PutReservation { reservation } => {
+                self.reservations.write().insert(reservation.name.clone(), reservation);
+            }
+            WalRecord::DeleteReservation { name } => {
+                self.reservations.write().remove(&name);
+            }
+        }
+    }
+    
+    /// Make a mutation durable: append it to the WAL, or rewrite the
+    /// checkpoint when the WAL is disabled. Call with the map lock held so
+    /// the log order matches the in-memory order.
+    fn log(&self, records: &[WalRecord]) -> Result<()> {
+        let mut wal = self.wal.lock();
+        let Some(file) = wal.as_mut() else {
+            return Ok(());
+        };
+        
+        let mut buf = vec![];
+        for record in records {
+            serde_json::to_writer(&mut buf, record)
                 .map_err(|e| Error::Serialization(e.to_string()))?;
-            *self.nodes.write() = nodes;
+            buf.push(b'\n');
+        }
+        file.write_all(&buf).map_err(Error::Io)?;
+        if self.config.sync_writes {
+            file.sync_data().map_err(Error::Io)?;
         }
         
         Ok(())
     }
     
-    /// Save state to disk
+    /// Persist after a mutation once the map lock is released
+    fn persist(&self) -> Result<()> {
+        if self.config.enable_wal {
+            self.checkpoint_if_due()?;
+        } else if self.config.sync_writes {
+            self.save()?;
+        }
+        Ok(())
+    }
+    
+    /// Checkpoint if the WAL holds records and `checkpoint_interval_secs`
+    /// have passed since the last checkpoint; returns whether it did
+    pub fn checkpoint_if_due(&self) -> Result<bool> {
+        let due = self.last_checkpoint.lock().elapsed().as_secs() >= self.config.checkpoint_interval_secs;
+        if !due {
+            return Ok(false);
+        }
+        let logged = match self.wal.lock().as_ref() {
+            Some(file) => file.metadata().map_err(Error::Io)?.len() > 0,
+            None => false,
+        };
+        if logged {
+            self.checkpoint()?;
+        }
+        Ok(logged)
+    }
+    
+    /// Run `checkpoint_if_due` every `period` until the task is dropped, so
+    /// the WAL is folded into the checkpoint while no mutations arrive
+    pub async fn run_checkpointer(self: Arc<Self>, period: Duration) {
+        let mut ticker = tokio::time::interval(period);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.checkpoint_if_due() {
+                warn!("Checkpoint failed: {}", e);
+            }
+        }
+    }
+    
+    /// Save state to disk (a checkpoint)
     pub fn save(&self) -> Result<()> {
-        let jobs_path = self.config.data_dir.join("jobs.json");
-        let nodes_path = self.config.data_dir.join("nodes.json");
-        
-        // Save jobs
+        self.checkpoint()
+    }
+    
+    /// Atomically replace the checkpoint files with the current state and
+    /// truncate the WAL
+    pub fn checkpoint(&self) -> Result<()> {
         let jobs = self.jobs.read();
-        let jobs_data = serde_json::to_string_pretty(&*jobs)
-            .map_err(|e| Error::Serialization(e.to_string()))?;
-        fs::write(&jobs_path, jobs_data)
-            .map_err(|e| Error::Io(e))?;
-        
-        // Save nodes
         let nodes = self.nodes.read();
-        let nodes_data = serde_json::to_string_pretty(&*nodes)
-            .map_err(|e| Error::Serialization(e.to_string()))?;
-        fs::write(&nodes_path, nodes_data)
-            .map_err(|e| Error::Io(e))?;
+        let arrays = self.arrays.read();
+        let reservations = self.reservations.read();
+        let mut wal = self.wal.lock();
+        
+        self.write_atomic(JOBS_FILE, &*jobs)?;
+        self.write_atomic(NODES_FILE, &*nodes)?;
+        self.write_atomic(ARRAYS_FILE, &*arrays)?;
+        self.write_atomic(RESERVATIONS_FILE, &*reservations)?;
+        File::open(&self.config.data_dir)
+            .and_then(|dir| dir.sync_all())
+            .map_err(Error::Io)?;
+        
+        // Everything logged so far is in the checkpoint
+        if let Some(file) = wal.as_mut() {
+            file.set_len(0).map_err(Error::Io)?;
+            file.sync_all().map_err(Error::Io)?;
+        }
+        *self.last_checkpoint.lock() = Instant::now();
         
         Ok(())
     }
     
+    fn write_atomic<T: Serialize>(&self, name: &str, value: &T) -> Result<()> {
+        let path = self.path(name);
+        let tmp_path = self.path(&format!("{}.tmp", name));
+        let data = serde_json::to_vec_pretty(value)
+            .map_err(|e| Error::Serialization(e.to_string()))?;
+        
+        let mut tmp = File::create(&tmp_path).map_err(Error::Io)?;
+        tmp.write_all(&data).map_err(Error::Io)?;
+        tmp.sync_all().map_err(Error::Io)?;
+        fs::rename(&tmp_path, &path).map_err(Error::Io)
+    }
+    
+    /// Data directory
+    pub fn data_dir(&self) -> &Path {
+        &self.config.data_dir
+    }
+    
     /// Store a job
     pub fn store_job(&self, job: &Job) -> Result<()> {
-        self.jobs.write().insert(job.id.to_string(), job.clone());
-        
-        if self.config.sync_writes {
-            self.save()?;
+        self.store_jobs([job])
+    }
+    
+    /// Store several jobs with a single WAL append (and sync)
+    pub fn store_jobs<'a>(&self, batch: impl IntoIterator<Item = &'a Job>) -> Result<()> {
+        {
+            let mut jobs = self.jobs.write();
+            let records: Vec<WalRecord> = batch.into_iter()
+                .map(|job| {
+                    jobs.insert(job.id.to_string(), job.clone());
+                    WalRecord::PutJob { job: Box::new(job.clone()) }
+                })
+                .collect();
+            if records.is_empty() {
+                return Ok(());
+            }
+            self.log(&records)?;
         }
         
-        Ok(())
+        self.persist()
     }
     
     /// Get a job
@@ -142,32 +374,27 @@ impl StateStore {
     }
     
     /// Update job state
-    pub fn update_job_state(&self, job_id: &str, state: JobState, message: &str) -> Result<()> {
-        let mut jobs = self.jobs.write();
-        
-        if let Some(job) = jobs.get_mut(job_id) {
-            job.transition(state, message);
-            
-            if self.config.sync_writes {
-                drop(jobs);
-                self.save()?;
-            }
-            
-            Ok(())
-        } else {
-            Err(Error::Job(format!("Job not found: {}", job_id)))
+    pub fn update_job_state(&self, job_id: &str, state: JobState, actor: Actor, message: &str) -> Result<()> {
+        {
+            let mut jobs = self.jobs.write();
+            let job = jobs.get_mut(job_id)
+                .ok_or_else(|| Error::Job(format!("Job not found: {}", job_id)))?;
+            job.transition(state, actor, message)?;
+            self.log(&[WalRecord::PutJob { job: Box::new(job.clone()) }])?;
         }
+        
+        self.persist()
     }
     
     /// Delete a job
     pub fn delete_job(&self, job_id: &str) -> Result<()> {
-        self.jobs.write().remove(job_id);
-        
-        if self.config.sync_writes {
-            self.save()?;
+        {
+            let mut jobs = self.jobs.write();
+            jobs.remove(job_id);
+            self.log(&[WalRecord::DeleteJob { job_id: job_id.to_string() }])?;
         }
         
-        Ok(())
+        self.persist()
     }
     
     /// List jobs by state
@@ -186,13 +413,13 @@ impl StateStore {
     
     /// Store node state
     pub fn store_node(&self, node_state: NodeState) -> Result<()> {
-        self.nodes.write().insert(node_state.id.clone(), node_state);
-        
-        if self.config.sync_writes {
-            self.save()?;
+        {
+            let mut nodes = self.nodes.write();
+            nodes.insert(node_state.id.clone(), node_state.clone());
+            self.log(&[WalRecord::PutNode { node: node_state }])?;
         }
         
-        Ok(())
+        self.persist()
     }
     
     /// Get node state
@@ -205,6 +432,49 @@ impl StateStore {
         self.nodes.read().values().cloned().collect()
     }
     
+    /// Store an array job's parent record
+    pub fn store_array(&self, array: &ArrayJob) -> Result<()> {
+        {
+            let mut arrays = self.arrays.write();
+            arrays.insert(array.id.to_string(), array.clone());
+            self.log(&[WalRecord::PutArray { array: Box::new(array.clone()) }])?;
+        }
+        
+        self.persist()
+    }
+    
+    /// List all array job parents
+    pub fn list_arrays(&self) -> Vec<ArrayJob> {
+        self.arrays.read().values().cloned().collect()
+    }
+    
+    /// Store an advance reservation
+    pub fn store_reservation(&self, reservation: &Reservation) -> Result<()> {
+        {
+            let mut reservations = self.reservations.write();
+            reservations.insert(reservation.name.clone(), reservation.clone());
+            self.log(&[WalRecord::PutReservation { reservation: reservation.clone() }])?;
+        }
+        
+        self.persist()
+    }
+    
+    /// Delete an advance reservation
+    pub fn delete_reservation(&self, name: &str) -> Result<()> {
+        {
+            let mut reservations = self.reservations.write();
+            reservations.remove(name);
+            self.log(&[WalRecord::DeleteReservation { name: name.to_string() }])?;
+        }
+        
+        self.persist()
+    }
+    
+    /// List all advance reservations
+    pub fn list_reservations(&self) -> Vec<Reservation> {
+        self.reservations.read().values().cloned().collect()
+    }
+    
     /// Get job counts by state
     pub fn job_counts(&self) -> HashMap<JobState, usize> {
         let jobs = self.jobs.read();
@@ -220,25 +490,29 @@ impl StateStore {
     /// Cleanup completed/failed jobs older than given seconds
     pub fn cleanup_old_jobs(&self, max_age_secs: i64) -> Result<usize> {
         let now = chrono::Utc::now().timestamp();
-        let mut jobs = self.jobs.write();
-        
-        let to_remove: Vec<String> = jobs.iter()
-            .filter(|(_, job)| {
-                matches!(job.state, JobState::Completed | JobState::Failed | JobState::Cancelled) &&
-                job.end_time.map(|t| now - t.timestamp() > max_age_secs).unwrap_or(false)
-            })
-            .map(|(id, _)| id.clone())
-            .collect();
-        
-        let count = to_remove.len();
-        for id in to_remove {
-            jobs.remove(&id);
-        }
-        
-        drop(jobs);
+        let count = {
+            let mut jobs = self.jobs.write();
+            
+            let to_remove: Vec<String> = jobs.iter()
+                .filter(|(_, job)| {
+                    matches!(job.state, JobState::Completed | JobState::Failed | JobState::Cancelled) &&
+                    job.end_time.map(|t| now - t.timestamp() > max_age_secs).unwrap_or(false)
+                })
+                .map(|(id, _)| id.clone())
+                .collect();
+            
+            for id in &to_remove {
+                jobs.remove(id);
+            }
+            let records: Vec<WalRecord> = to_remove.into_iter()
+                .map(|job_id| WalRecord::DeleteJob { job_id })
+                .collect();
+            self.log(&records)?;
+            records.len()
+        };
         
-        if count > 0 && self.config.sync_writes {
-            self.save()?;
+        if count > 0 {
+            self.persist()?;
         }
         
         Ok(count)
@@ -248,7 +522,7 @@ impl StateStore {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::job::{JobDescriptor, ResourceRequirements, LocalityPreferences, SchedulingPolicy};
+    use crate::job::JobDescriptor;
     use tempfile::TempDir;
     
     fn create_test_job() -> Job {
@@ -258,13 +532,8 @@ mod tests {
             project_id: "project1".to_string(),
             command: "python".to_string(),
             arguments: vec!["train.py".to_string()],
-            environment: HashMap::new(),
             working_directory: "/app".to_string(),
-            resources: ResourceRequirements::default(),
-            locality: LocalityPreferences::default(),
-            policy: SchedulingPolicy::default(),
-            labels: HashMap::new(),
-            annotations: HashMap::new(),
+            ..Default::default()
         };
         
         Job::new(descriptor)
@@ -293,10 +562,14 @@ mod tests {
         assert_eq!(retrieved.descriptor.name, "test-job");
         
         // Update state
-        store.update_job_state(&job_id, JobState::Running, "Started").unwrap();
+        store.update_job_state(&job_id, JobState::Queued, Actor::Scheduler, "Queued").unwrap();
         
         let updated = store.get_job(&job_id).unwrap();
-        assert_eq!(updated.state, JobState::Running);
+        assert_eq!(updated.state, JobState::Queued);
+        assert_eq!(updated.events().len(), 1);
+        
+        // Illegal transitions are rejected
+        assert!(store.update_job_state(&job_id, JobState::Running, Actor::Agent, "Started").is_err());
     }
     
     #[test]
@@ -324,4 +597,140 @@ mod tests {
         assert_eq!(counts.get(&JobState::Pending), Some(&2));
         assert_eq!(counts.get(&JobState::Running), Some(&1));
     }
+    
+    fn wal_config(data_dir: &Path) -> StateStoreConfig {
+        StateStoreConfig {
+            data_dir: data_dir.to_path_buf(),
+            enable_wal: true,
+            sync_writes: true,
+            checkpoint_interval_secs: u64::MAX,
+        }
+    }
+    
+    fn snapshot(store: &StateStore) -> serde_json::Value {
+        serde_json::to_value(&*store.jobs.read()).unwrap()
+    }
+    
+    #[test]
+    fn test_wal_replay_and_checkpoint() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = StateStore::new(wal_config(temp_dir.path())).unwrap();
+        
+        let job = create_test_job();
+        let job_id = job.id.to_string();
+        let doomed = create_test_job();
+        store.store_job(&job).unwrap();
+        store.store_job(&doomed).unwrap();
+        store.update_job_state(&job_id, JobState::Queued, Actor::Scheduler, "Queued").unwrap();
+        store.delete_job(&doomed.id.to_string()).unwrap();
+        let expected = snapshot(&store);
+        drop(store);
+        
+        // Nothing checkpointed yet: the state comes from the WAL alone
+        assert!(!temp_dir.path().join(JOBS_FILE).exists());
+        let store = StateStore::new(wal_config(temp_dir.path())).unwrap();
+        assert_eq!(snapshot(&store), expected);
+        assert_eq!(store.get_job(&job_id).unwrap().events().len(), 1);
+        
+        store.checkpoint().unwrap();
+        assert_eq!(fs::metadata(temp_dir.path().join(WAL_FILE)).unwrap().len(), 0);
+        assert!(!temp_dir.path().join(format!("{}.tmp", JOBS_FILE)).exists());
+        
+        // WAL records after the checkpoint apply on top of it
+        store.update_job_state(&job_id, JobState::Cancelled, Actor::User, "Stop").unwrap();
+        let expected = snapshot(&store);
+        drop(store);
+        let store = StateStore::new(wal_config(temp_dir.path())).unwrap();
+        assert_eq!(snapshot(&store), expected);
+    }
+    
+    #[test]
+    fn test_corrupt_wal_record_fails_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = StateStore::new(wal_config(temp_dir.path())).unwrap();
+        store.store_job(&create_test_job()).unwrap();
+        store.store_job(&create_test_job()).unwrap();
+        drop(store);
+        
+        // Damage the first of two complete records
+        let wal_path = temp_dir.path().join(WAL_FILE);
+        let mut wal = fs::read(&wal_path).unwrap();
+        wal[1] = b'#';
+        fs::write(&wal_path, &wal).unwrap();
+        
+        let err = StateStore::new(wal_config(temp_dir.path())).err().unwrap();
+        assert!(err.to_string().contains("corrupt WAL record 1"));
+        // Nothing was cut off
+        assert_eq!(fs::read(&wal_path).unwrap(), wal);
+    }
+    
+    #[test]
+    fn test_idle_checkpoint() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = StateStoreConfig { checkpoint_interval_secs: 60, ..wal_config(temp_dir.path()) };
+        let store = StateStore::new(config).unwrap();
+        let rewind = || *store.last_checkpoint.lock() = Instant::now() - Duration::from_secs(120);
+        
+        store.store_job(&create_test_job()).unwrap();
+        assert!(!store.checkpoint_if_due().unwrap());
+        
+        // The interval passes with no further mutations
+        rewind();
+        assert!(store.checkpoint_if_due().unwrap());
+        assert_eq!(fs::metadata(temp_dir.path().join(WAL_FILE)).unwrap().len(), 0);
+        assert!(temp_dir.path().join(JOBS_FILE).exists());
+        
+        // Nothing new to fold in
+        rewind();
+        assert!(!store.checkpoint_if_due().unwrap());
+    }
+    
+    proptest::proptest! {
+        #![proptest_config(proptest::prelude::ProptestConfig::with_cases(64))]
+        
+        #[test]
+        fn test_crash_recovery_from_torn_wal(cut in 0.0f64..=1.0) {
+            let source = TempDir::new().unwrap();
+            let store = StateStore::new(wal_config(source.path())).unwrap();
+            
+            // A checkpoint followed by a WAL of mutations, with the state
+            // expected after each record
+            let first = create_test_job();
+            store.store_job(&first).unwrap();
+            store.checkpoint().unwrap();
+            let mut states = vec![snapshot(&store)];
+            let second = create_test_job();
+            let first_id = first.id.to_string();
+            store.store_job(&second).unwrap();
+            states.push(snapshot(&store));
+            for (state, actor) in [(JobState::Queued, Actor::Scheduler), (JobState::Scheduled, Actor::Scheduler), (JobState::Running, Actor::Agent)] {
+                store.update_job_state(&first_id, state, actor, "step").unwrap();
+                states.push(snapshot(&store));
+            }
+            store.delete_job(&second.id.to_string()).unwrap();
+            states.push(snapshot(&store));
+            drop(store);
+            
+            // Crash: only a prefix of the WAL reached the disk
+            let wal = fs::read(source.path().join(WAL_FILE)).unwrap();
+            let offset = (wal.len() as f64 * cut) as usize;
+            let complete = wal[..offset].iter().filter(|&&b| b == b'\n').count();
+            
+            let crashed = TempDir::new().unwrap();
+            fs::copy(source.path().join(JOBS_FILE), crashed.path().join(JOBS_FILE)).unwrap();
+            fs::copy(source.path().join(NODES_FILE), crashed.path().join(NODES_FILE)).unwrap();
+            fs::write(crashed.path().join(WAL_FILE), &wal[..offset]).unwrap();
+            
+            let store = StateStore::new(wal_config(crashed.path())).unwrap();
+            proptest::prop_assert_eq!(snapshot(&store), states[complete].clone());
+            
+            // The torn tail is gone, so new records survive the next restart
+            let third = create_test_job();
+            store.store_job(&third).unwrap();
+            let expected = snapshot(&store);
+            drop(store);
+            let store = StateStore::new(wal_config(crashed.path())).unwrap();
+            proptest::prop_assert_eq!(snapshot(&store), expected);
+        }
+    }
 }
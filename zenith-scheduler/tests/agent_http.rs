@@ -0,0 +1,72 @@
+//! Integration Tests - Node agent and scheduler over HTTP on localhost
+
+use std::sync::Arc;
+use std::time::Duration;
+use zenith_scheduler::agent::{NodeAgent, NodeAgentConfig};
+use zenith_scheduler::api::rest::{create_router, AppState};
+use zenith_scheduler::scheduler::SchedulerConfig;
+use zenith_scheduler::job::{Actor, ResourceRequirements};
+use zenith_scheduler::{Job, JobDescriptor, JobState, NodeRegistry, Scheduler};
+
+/// Poll `check` until it holds, failing after five seconds
+async fn wait_for(what: &str, check: impl Fn() -> bool) {
+    for _ in 0..100 {
+        if check() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("timed out waiting for {}", what);
+}
+
+#[tokio::test]
+async fn integration_agent_registers_heartbeats_and_deregisters() {
+    let node_registry = Arc::new(NodeRegistry::new(60));
+    let scheduler = Arc::new(Scheduler::new(node_registry.clone(), SchedulerConfig::default()));
+    let router = create_router(Arc::new(AppState { scheduler: scheduler.clone(), node_registry: node_registry.clone() }));
+    
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    
+    let mut agent = NodeAgent::new(NodeAgentConfig {
+        node_id: "agent-test-node".to_string(),
+        scheduler_addr: format!("http://{}", address),
+        heartbeat_interval_secs: 1,
+        gpu_monitor_interval_secs: 1,
+        tracks_jobs: false,
+    }).unwrap();
+    let stop = agent.stop_handle();
+    let cpu_cores = agent.status().topology.cpu_cores;
+    let agent_task = tokio::spawn(async move { agent.start().await });
+    
+    // Registered with the discovered topology
+    wait_for("registration", || node_registry.get("agent-test-node").is_some()).await;
+    let node = node_registry.get("agent-test-node").unwrap();
+    assert_eq!(node.topology.cpu_cores, cpu_cores);
+    assert!(node.topology.cpu_memory_free > 0);
+    
+    // Heartbeats keep arriving
+    let registered = node.last_heartbeat;
+    wait_for("a heartbeat", || {
+        node_registry.get("agent-test-node").is_some_and(|n| n.last_heartbeat > registered)
+    }).await;
+    
+    // A scheduler that forgot the node gets it back on the next heartbeat;
+    // the agent does not track its jobs, so the node keeps those placed there
+    let job_id = scheduler.submit(Job::new(JobDescriptor {
+        name: "cpu".to_string(),
+        resources: ResourceRequirements { gpu_count: 0, cpu_memory: 0, ..Default::default() },
+        ..Default::default()
+    })).unwrap();
+    assert_eq!(scheduler.schedule_cycle().len(), 1);
+    scheduler.update_job_state(&job_id, JobState::Running, Actor::Agent, "Started").unwrap();
+    node_registry.deregister("agent-test-node").unwrap();
+    wait_for("re-registration", || node_registry.get("agent-test-node").is_some()).await;
+    assert_eq!(scheduler.get_job(&job_id).unwrap().state, JobState::Running);
+    assert_eq!(node_registry.get("agent-test-node").unwrap().running_jobs, vec![job_id]);
+    
+    stop.stop();
+    agent_task.await.unwrap().unwrap();
+    assert!(node_registry.get("agent-test-node").is_none());
+}